@@ -0,0 +1,189 @@
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, get_pintas_dir};
+
+const BUDGET_FILENAME: &str = "budget";
+
+// today's runs of one alias as stored in `~/.pintas/budget`, a tab
+// separated line per alias, days are counted in UTC
+struct Usage {
+    day: u64,
+    runs: u32,
+    over: u32,
+    alias: String,
+}
+
+fn get_budget_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(BUDGET_FILENAME))
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() / 86400)
+}
+
+// the counts of earlier days are dropped, a file that can't be read counts
+// as no runs yet
+fn read_usage() -> Vec<Usage> {
+    let today = today();
+    let content = get_budget_path()
+        .and_then(|path| Ok(fs::read_to_string(path)?))
+        .unwrap_or_default();
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+
+            Some(Usage {
+                day: fields.next()?.parse().ok()?,
+                runs: fields.next()?.parse().ok()?,
+                over: fields.next()?.parse().ok()?,
+                alias: fields.next()?.to_string(),
+            })
+        })
+        .filter(|usage| usage.day == today)
+        .collect()
+}
+
+fn write_usage(usage: &[Usage]) -> Result<()> {
+    let path = get_budget_path()?;
+    let content: String = usage
+        .iter()
+        .map(|usage| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                usage.day, usage.runs, usage.over, usage.alias
+            )
+        })
+        .collect();
+
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    Ok(fs::write(&path, content)?)
+}
+
+// counts a run of an alias with a `budget`, one past it only goes ahead
+// once confirmed on the terminal
+pub fn check(config: &Config, name: &str) -> Result<()> {
+    let Some(budget) = config
+        .aliases
+        .get(name)
+        .and_then(|alias| alias.budget.as_ref())
+    else {
+        return Ok(());
+    };
+
+    let mut usage = read_usage();
+    let index = match usage.iter().position(|usage| usage.alias == name) {
+        Some(index) => index,
+        None => {
+            usage.push(Usage {
+                day: today(),
+                runs: 0,
+                over: 0,
+                alias: name.to_string(),
+            });
+
+            usage.len() - 1
+        }
+    };
+
+    let runs = usage[index].runs;
+    let over = runs >= budget.per_day;
+
+    if over {
+        let message = format!(
+            "Alias '{}' already ran {} times today, its budget is {} per day.",
+            name, runs, budget.per_day
+        );
+
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("{} Run it from a terminal to confirm.", message));
+        }
+
+        if !confirm(&format!("{} Run it anyway? [y/N]", message))? {
+            return Err(anyhow!("Alias '{}' was not run.", name));
+        }
+
+        usage[index].over += 1;
+    }
+
+    usage[index].runs += 1;
+
+    // a count that can't be written never stops the run itself
+    let _ = write_usage(&usage);
+
+    Ok(())
+}
+
+// today's count of a renamed alias is kept under its new name
+pub fn rename(old: &str, new: &str) -> Result<()> {
+    let mut usage = read_usage();
+
+    if !usage.iter().any(|usage| usage.alias == old) {
+        return Ok(());
+    }
+
+    usage.retain(|usage| usage.alias != new);
+
+    for usage in &mut usage {
+        if usage.alias == old {
+            usage.alias = new.to_string();
+        }
+    }
+
+    write_usage(&usage)
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    let mut answer = String::new();
+
+    eprint!("{} ", question);
+    io::stderr().flush()?;
+
+    if io::stdin().read_line(&mut answer)? == 0 {
+        eprintln!();
+    }
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// today's runs of every alias with a budget, for `pintas stats`
+pub fn print_budgets(config: &Config) {
+    let usage = read_usage();
+    let mut budgeted: Vec<_> = config
+        .aliases
+        .iter()
+        .filter_map(|(name, alias)| Some((name, alias.budget.as_ref()?)))
+        .collect();
+
+    if budgeted.is_empty() {
+        return;
+    }
+
+    budgeted.sort_by(|a, b| a.0.cmp(b.0));
+
+    println!();
+    println!("Budgets:");
+
+    for (name, budget) in budgeted {
+        let (runs, over) = usage
+            .iter()
+            .find(|usage| &usage.alias == name)
+            .map_or((0, 0), |usage| (usage.runs, usage.over));
+
+        let mut line = format!("  {:<15} {}/{} runs today", name, runs, budget.per_day);
+
+        if over > 0 {
+            line.push_str(&format!(", {} over budget", over));
+        }
+
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+
+use crate::config::{Alias, Config};
+
+const ALIAS_REF_PREFIX: &str = "pintas:";
+const MAX_DEPTH: usize = 16;
+
+// `pintas:deploy` refers to the alias `deploy` instead of a shell command
+pub fn alias_ref(command: &str) -> Option<&str> {
+    let name = command.trim().strip_prefix(ALIAS_REF_PREFIX)?;
+
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then_some(name)
+}
+
+// `pintas:build && pintas:deploy` runs both aliases in order, mixing them
+// with shell commands isn't supported so anything else is a plain command
+pub fn alias_refs(command: &str) -> Option<Vec<&str>> {
+    command.split("&&").map(alias_ref).collect()
+}
+
+// every alias that running `alias` can run as well
+fn references(alias: &Alias) -> Vec<&str> {
+    let commands = alias.commands().unwrap_or_default();
+    let steps = commands.into_iter().filter_map(alias_refs).flatten();
+    let hooks = [&alias.pre, &alias.post, &alias.cleanup]
+        .into_iter()
+        .filter_map(|hook| hook.as_deref().and_then(alias_ref));
+
+    alias
+        .deps
+        .iter()
+        .map(String::as_str)
+        .chain(steps)
+        .chain(hooks)
+        .collect()
+}
+
+// the aliases that run `name` by reference, sorted
+pub fn referrers<'a>(config: &'a Config, name: &str) -> Vec<&'a str> {
+    let mut referrers: Vec<&str> = config
+        .aliases
+        .iter()
+        .filter(|(_, alias)| refers_to(alias, name))
+        .map(|(referrer, _)| referrer.as_str())
+        .collect();
+
+    referrers.sort();
+
+    referrers
+}
+
+fn refers_to(alias: &Alias, name: &str) -> bool {
+    references(alias).contains(&name) || alias.sub.values().any(|sub| refers_to(sub, name))
+}
+
+pub fn has_references(alias: &Alias) -> bool {
+    !references(alias).is_empty() || alias.sub.values().any(has_references)
+}
+
+// walks the references up front so a loop is reported before anything runs
+pub fn check_cycles(config: &Config, name: &str) -> Result<()> {
+    visit(config, name, &mut CallStack::default(), &mut HashSet::new())
+}
+
+fn visit<'a>(
+    config: &'a Config,
+    name: &'a str,
+    stack: &mut CallStack,
+    done: &mut HashSet<&'a str>,
+) -> Result<()> {
+    if done.contains(name) {
+        return Ok(());
+    }
+
+    let Some(alias) = config.lookup(name) else {
+        return Ok(());
+    };
+
+    stack.enter(name)?;
+
+    for target in references(alias) {
+        visit(config, target, stack, done)?;
+    }
+
+    stack.leave();
+    done.insert(name);
+
+    Ok(())
+}
+
+// tracks which aliases are being resolved so references between them can't
+// loop forever, the error shows the path that led back to the start
+#[derive(Default)]
+pub struct CallStack {
+    names: Vec<String>,
+}
+
+impl CallStack {
+    pub fn enter(&mut self, name: &str) -> Result<()> {
+        if self.names.iter().any(|entered| entered == name) {
+            let path: Vec<&str> = self
+                .names
+                .iter()
+                .map(String::as_str)
+                .skip_while(|entered| *entered != name)
+                .chain([name])
+                .collect();
+
+            return Err(anyhow!("Alias cycle detected: {}", path.join(" -> ")));
+        }
+
+        if self.names.len() >= MAX_DEPTH {
+            return Err(anyhow!(
+                "Alias references are nested more than {} levels deep: {} -> {}",
+                MAX_DEPTH,
+                self.names.join(" -> "),
+                name
+            ));
+        }
+
+        self.names.push(name.to_string());
+
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.names.pop();
+    }
+}
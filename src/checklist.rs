@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::get_pintas_dir;
+use crate::history;
+use crate::shims::shim_name;
+
+// what happened to every step of every run is appended to
+// `~/.pintas/checklists/<alias>.log`, one tab separated line per step
+pub struct Record {
+    file: File,
+    started: String,
+}
+
+impl Record {
+    pub fn open(alias: &str) -> Result<Record> {
+        let dir = get_pintas_dir()?.join("checklists");
+        let path = dir.join(format!("{}.log", shim_name(alias)));
+
+        fs::create_dir_all(&dir).context("Failed to create checklists directory")?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open '{}'", path.to_string_lossy()))?;
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |started| started.as_secs());
+
+        Ok(Record {
+            file,
+            started: history::format_timestamp(started),
+        })
+    }
+
+    pub fn step(
+        &mut self,
+        step: usize,
+        total: usize,
+        description: &str,
+        result: &str,
+    ) -> Result<()> {
+        writeln!(
+            self.file,
+            "{}\t{}/{}\t{}\t{}",
+            self.started,
+            step,
+            total,
+            result,
+            description.replace(['\t', '\n'], " ")
+        )
+        .context("Failed to record checklist progress")
+    }
+}
+
+// the first letter of the answer, asked again until it's one of `choices`,
+// the end of input counts as aborting
+pub fn rename_log(old: &str, new: &str) -> Result<()> {
+    let dir = get_pintas_dir()?.join("checklists");
+    let from = dir.join(format!("{}.log", shim_name(old)));
+
+    match fs::rename(&from, dir.join(format!("{}.log", shim_name(new)))) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to rename '{}'", from.to_string_lossy()))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn ask(question: &str, choices: &str) -> Result<char> {
+    loop {
+        let mut answer = String::new();
+
+        eprint!("{} ", question);
+
+        if io::stdin().read_line(&mut answer)? == 0 {
+            eprintln!();
+
+            return Ok('a');
+        }
+
+        let choice = answer.trim().chars().next().map(|c| c.to_ascii_lowercase());
+
+        if let Some(choice) = choice.filter(|c| choices.contains(*c)) {
+            return Ok(choice);
+        }
+    }
+}
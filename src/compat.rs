@@ -0,0 +1,343 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::io::Write;
+use std::process::{Command as OsCommand, Stdio};
+
+#[cfg(feature = "completions")]
+use crate::completions;
+use crate::config::Config;
+use crate::export;
+use crate::shims;
+use crate::sys;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dialect {
+    // shims, launchers and the init snippet, run by whatever `/bin/sh` is
+    Posix,
+    // completions, sourced into the user's bash
+    Bash,
+}
+
+// the shells every generated script is parsed with, the oldest bash still
+// around is the 3.2 macOS ships and the smallest sh is busybox ash
+const SHELLS: [(&str, &[&str], Dialect); 4] = [
+    ("sh", &["sh"], Dialect::Posix),
+    ("dash", &["dash"], Dialect::Posix),
+    ("busybox ash", &["busybox", "ash"], Dialect::Posix),
+    ("bash", &["bash"], Dialect::Bash),
+];
+
+// what a syntax check can't catch because the parser accepts it but an old
+// shell fails when it gets there
+const NOT_IN_BASH_3_2: [&str; 12] = [
+    "declare -A",
+    "local -A",
+    "declare -n",
+    "local -n",
+    ",,}",
+    "^^}",
+    "@Q}",
+    "mapfile",
+    "readarray",
+    "coproc",
+    "&>>",
+    ";;&",
+];
+
+const NOT_IN_POSIX_SH: [&str; 9] = [
+    "[[",
+    "function ",
+    "$'",
+    "<<<",
+    "&>",
+    "|&",
+    "+=",
+    "source ",
+    "${!",
+];
+
+// `verify --scripts`: every script pintas generates for the current config,
+// parsed by the shells above that are installed and checked for constructs
+// they lack
+pub fn run_compat(config: &Config) -> Result<()> {
+    let scripts = generated_scripts(config)?;
+    let mut problems = Vec::new();
+    let mut checked = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (shell, argv, dialect) in SHELLS {
+        if sys::find_program(argv[0]).is_none() {
+            skipped.push(shell);
+            continue;
+        }
+
+        checked.push(shell);
+
+        for (name, script, script_dialect) in &scripts {
+            if *script_dialect == Dialect::Bash && dialect != Dialect::Bash {
+                continue;
+            }
+
+            if let Some(error) = syntax_error(argv, script)? {
+                problems.push(format!("{} doesn't parse under {}: {}", name, shell, error));
+            }
+        }
+    }
+
+    for (name, script, dialect) in &scripts {
+        for construct in unportable_constructs(script, *dialect) {
+            problems.push(format!("{} uses '{}'", name, construct));
+        }
+    }
+
+    for problem in &problems {
+        println!("Problem: {}", problem);
+    }
+
+    if !skipped.is_empty() {
+        println!(
+            "Skipped shells that aren't installed: {}",
+            skipped.join(", ")
+        );
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("Generated scripts aren't portable."));
+    }
+
+    println!(
+        "Generated scripts are portable, parsed with {}.",
+        checked.join(", ")
+    );
+
+    Ok(())
+}
+
+fn generated_scripts(config: &Config) -> Result<Vec<(String, String, Dialect)>> {
+    let pintas = env::current_exe().context("Failed to get current executable path")?;
+    let project = env::current_dir().context("Failed to get current directory")?;
+    let pintas = pintas.to_string_lossy();
+    let project = project.to_string_lossy();
+    let mut scripts = vec![(
+        "the bash init snippet".to_string(),
+        shims::init_script("bash")?,
+        Dialect::Posix,
+    )];
+
+    for (alias, (file, content)) in shims::expected_shims(config)? {
+        scripts.push((
+            format!("shim '{}' of alias '{}'", file, alias),
+            content,
+            Dialect::Posix,
+        ));
+    }
+
+    #[cfg(feature = "completions")]
+    scripts.push((
+        "the bash completions".to_string(),
+        completions::completion_script(config, "bash")?,
+        Dialect::Bash,
+    ));
+
+    let mut aliases: Vec<_> = config.aliases.iter().collect();
+
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (name, alias) in &aliases {
+        scripts.push((
+            format!("the raycast script of alias '{}'", name),
+            export::raycast_script(name, alias, &pintas, &project),
+            Dialect::Posix,
+        ));
+    }
+
+    scripts.push((
+        "the rofi script".to_string(),
+        export::rofi_script(&aliases, &pintas, &project),
+        Dialect::Posix,
+    ));
+
+    Ok(scripts)
+}
+
+// the first line the shell complained with when asked to only parse
+fn syntax_error(argv: &[&str], script: &str) -> Result<Option<String>> {
+    let mut child = OsCommand::new(argv[0])
+        .args(&argv[1..])
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", argv.join(" ")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(script.as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run '{}'", argv.join(" ")))?;
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    Ok(Some(
+        stderr.lines().next().unwrap_or("syntax error").to_string(),
+    ))
+}
+
+fn unportable_constructs(script: &str, dialect: Dialect) -> Vec<&'static str> {
+    let code = strip_quotes_and_comments(script);
+    let mut constructs: Vec<&str> = NOT_IN_BASH_3_2.to_vec();
+
+    if dialect == Dialect::Posix {
+        constructs.extend(NOT_IN_POSIX_SH);
+    }
+
+    constructs
+        .into_iter()
+        .filter(|construct| code.contains(construct))
+        .collect()
+}
+
+// alias names, paths and descriptions end up in single quotes and comments,
+// where the constructs above are only text
+fn strip_quotes_and_comments(script: &str) -> String {
+    let mut code = String::new();
+    let mut chars = script.chars().peekable();
+    let mut word_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                for quoted in chars.by_ref() {
+                    if quoted == '\'' {
+                        break;
+                    }
+                }
+
+                code.push_str("''");
+            }
+            '\\' => {
+                code.push(c);
+
+                if let Some(escaped) = chars.next() {
+                    code.push(escaped);
+                }
+            }
+            '#' if word_start => while chars.next_if(|&c| c != '\n').is_some() {},
+            _ => code.push(c),
+        }
+
+        word_start = c.is_whitespace() || c == ';';
+    }
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // names, descriptions and commands full of what the checks look for, so
+    // only what pintas itself writes around them is judged
+    const AWKWARD: &str = r#"
+[aliases]
+gs = "git status"
+
+[aliases."it's"]
+cmd = "echo [[ $'x' ]] <<< y &> z"
+description = "function source ${!x} += |& # not a comment"
+aka = ["its"]
+tags = ["odd \"tag\""]
+
+[aliases.deploy]
+steps = ["make", "make install"]
+description = "Don't \"deploy\" on `fridays`\nsecond line"
+
+[aliases.g]
+cmd = "git"
+
+[aliases.g.sub]
+push = "git push"
+"#;
+
+    fn scripts() -> Vec<(String, String, Dialect)> {
+        generated_scripts(&toml::from_str(AWKWARD).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn generated_scripts_use_only_portable_constructs() {
+        let scripts = scripts();
+
+        assert!(scripts.iter().any(|(name, _, _)| name.starts_with("shim ")));
+        assert!(
+            scripts
+                .iter()
+                .any(|(name, _, _)| name.contains("init snippet"))
+        );
+        assert!(scripts.iter().any(|(name, _, _)| name.contains("raycast")));
+        assert!(scripts.iter().any(|(name, _, _)| name.contains("rofi")));
+
+        #[cfg(feature = "completions")]
+        assert!(
+            scripts
+                .iter()
+                .any(|(name, _, dialect)| name.contains("completions") && *dialect == Dialect::Bash)
+        );
+
+        for (name, script, dialect) in &scripts {
+            assert_eq!(
+                unportable_constructs(script, *dialect),
+                Vec::<&str>::new(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn generated_scripts_parse_under_installed_shells() {
+        for (shell, argv, dialect) in SHELLS {
+            if sys::find_program(argv[0]).is_none() {
+                continue;
+            }
+
+            for (name, script, script_dialect) in &scripts() {
+                if *script_dialect == Dialect::Bash && dialect != Dialect::Bash {
+                    continue;
+                }
+
+                assert_eq!(
+                    syntax_error(argv, script).unwrap(),
+                    None,
+                    "{} under {}",
+                    name,
+                    shell
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn constructs_only_count_outside_quotes_and_comments() {
+        let script = "echo '[[ x ]]' # source it\n";
+
+        assert_eq!(
+            unportable_constructs(script, Dialect::Posix),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            unportable_constructs("[[ -n x ]] && source y\n", Dialect::Posix),
+            ["[[", "source "]
+        );
+        assert_eq!(
+            unportable_constructs("[[ -n x ]] && mapfile y\n", Dialect::Bash),
+            ["mapfile"]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Result, anyhow};
+use std::iter;
+
+use crate::config::{Alias, Config};
+use crate::expand::shell_quote;
+use crate::shims::shim_name;
+
+pub fn print_completions(config: &Config, shell: &str) -> Result<()> {
+    print!("{}", completion_script(config, shell)?);
+
+    Ok(())
+}
+
+// plain bash 3.2, the one macOS still ships
+pub fn completion_script(config: &Config, shell: &str) -> Result<String> {
+    if shell != "bash" {
+        return Err(anyhow!("Shell '{}' not supported.", shell));
+    }
+
+    let mut names: Vec<_> = config.aliases.keys().collect();
+    let mut script = String::from(
+        "# pintas alias completions for bash\n#\n# Add the following line to your ~/.bashrc:\n#\n#   eval \"$(pintas completions bash)\"\n\n",
+    );
+
+    names.sort();
+
+    for name in names {
+        let mut rules = Vec::new();
+
+        collect_rules(&config.aliases[name], &[], &mut rules);
+
+        if rules.is_empty() {
+            continue;
+        }
+
+        let command = shim_name(name);
+        let function = format!(
+            "_pintas_complete_{}",
+            command.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        );
+
+        script.push_str(&format!("{}() {{\n", function));
+        script.push_str("    local candidates=\"\"\n");
+
+        for (path, position, words) in rules {
+            script.push_str(&format!(
+                "    [ \"$COMP_CWORD\" -eq {} ] && [ \"${{COMP_WORDS[*]:1:{}}}\" = {} ] && candidates={}\n",
+                position,
+                path.len(),
+                shell_quote(&path.join(" ")),
+                shell_quote(&words.join(" "))
+            ));
+        }
+
+        script.push_str(
+            "    COMPREPLY=($(compgen -W \"$candidates\" -- \"${COMP_WORDS[COMP_CWORD]}\"))\n",
+        );
+        script.push_str("}\n");
+        // `aka` names complete like the alias itself
+        let commands: Vec<String> = iter::once(name)
+            .chain(&config.aliases[name].aka)
+            .map(|name| shell_quote(&shim_name(name)))
+            .collect();
+
+        script.push_str(&format!(
+            "complete -F {} {}\n\n",
+            function,
+            commands.join(" ")
+        ));
+    }
+
+    Ok(script)
+}
+
+// one rule per word position: the sub-alias path typed so far, the position
+// of the word being completed and its candidates, deeper paths come later
+// so they win over the arguments of their parent
+fn collect_rules(
+    alias: &Alias,
+    path: &[String],
+    rules: &mut Vec<(Vec<String>, usize, Vec<String>)>,
+) {
+    let mut subs: Vec<_> = alias.sub.keys().cloned().collect();
+
+    subs.sort();
+
+    for (i, arg) in alias.args.iter().enumerate() {
+        let mut words = arg.choices.clone();
+
+        // sub-aliases compete with the first argument
+        if i == 0 {
+            words.extend(subs.iter().cloned());
+        }
+
+        if !words.is_empty() {
+            rules.push((path.to_vec(), path.len() + i + 1, words));
+        }
+    }
+
+    if alias.args.is_empty() && !subs.is_empty() {
+        rules.push((path.to_vec(), path.len() + 1, subs.clone()));
+    }
+
+    for sub in subs {
+        let mut sub_path = path.to_vec();
+
+        sub_path.push(sub.clone());
+        collect_rules(&alias.sub[&sub], &sub_path, rules);
+    }
+}
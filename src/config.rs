@@ -0,0 +1,940 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::index;
+use crate::session;
+use crate::sys;
+
+const CONFIG_FILENAME: &str = "pintas.toml";
+const SHARD_DIRNAME: &str = "config.d";
+
+pub fn get_pintas_dir() -> Result<PathBuf> {
+    let home = env::var("HOME").context("Failed to get HOME directory from environment")?;
+
+    Ok(PathBuf::from(home).join(".pintas"))
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub settings: Settings,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub hooks: Hooks,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub vars: HashMap<String, String>,
+    // `pintas <name>` runs these pintas commands in order
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, Vec<String>>,
+    // command patterns no alias may run, with what to do instead
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocked: BTreeMap<String, String>,
+    #[serde(default, with = "alias_map")]
+    pub aliases: HashMap<String, Alias>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trash: BTreeMap<String, TrashEntry>,
+}
+
+// a removed alias, kept for `[settings] trash_days`
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct TrashEntry {
+    // seconds since the epoch
+    pub removed: u64,
+    pub alias: Alias,
+}
+
+// the aliases of one namespace in `config.d/<namespace>.toml`, stored
+// without the namespace prefix
+#[derive(Deserialize, Serialize, Default)]
+struct Shard {
+    #[serde(default, with = "alias_map")]
+    aliases: HashMap<String, Alias>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lint_policy: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub history: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_days: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub digest: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub safe_args: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shim_shebang: Option<String>,
+}
+
+impl Settings {
+    // what runs alias commands and hooks, the login shell unless one is set
+    pub fn shell(&self) -> &str {
+        resolve_shell(self.shell.as_deref())
+    }
+}
+
+// `shell = "$SHELL"`, or no shell at all, is the login shell
+pub fn resolve_shell(shell: Option<&str>) -> &str {
+    match shell {
+        Some(shell) if shell != "$SHELL" => shell,
+        _ => sys::login_shell(),
+    }
+}
+
+// commands run around every alias invocation, see `hooks.rs`
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+// tables are written in key order so saving the same config always gives
+// the same bytes
+fn sorted<S: Serializer>(map: &HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+// an alias is either a plain command string or a table with metadata,
+// plain aliases are written back as strings to keep the file tidy
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Alias {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub cmd: AliasCommand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<ChecklistStep>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub parallel: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub fail_fast: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aka: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub protected: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disabled: bool,
+    // added with `add --session`, kept in the layer of the shell session
+    #[serde(skip)]
+    pub session: bool,
+    // set by `add`, `new` and `copy`, as `2024-01-01T09:30:00Z`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tz: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub only_under: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub clean_env: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep_env: Vec<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub pin: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub pin_mismatch: PinMismatch,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub keep_output: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub triage: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub log: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub notify: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub exclusive: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub busy: Busy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ionice: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<Limits>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub no_network: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Sandbox>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub retries: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub backoff: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<Budget>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub requires_tty: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headless: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<AliasArg>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub append_args: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub safe_args: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_args: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_args: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    pub prompts: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", with = "alias_map")]
+    pub sub: HashMap<String, Alias>,
+}
+
+// a step of a runbook, one without a command is done by hand
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct ChecklistStep {
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+}
+
+// how often an expensive alias may run before pintas asks first
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Budget {
+    pub per_day: u32,
+}
+
+// resource limits for the steps of an alias, `memory = "2G"`,
+// `cpu_time = "10m"`, `open_files = 1024`
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Limits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u64>,
+}
+
+// `sandbox = { network = false, readonly = ["/"], writable = ["."] }`, the
+// writable paths win over the read-only ones they are in
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Sandbox {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub readonly: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable: Vec<String>,
+}
+
+// what a run does when a program doesn't have the version in `pin`
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PinMismatch {
+    #[default]
+    Abort,
+    Warn,
+}
+
+// what a run of an `exclusive` alias does while another one is running
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Busy {
+    #[default]
+    Fail,
+    Wait,
+}
+
+// a declared positional argument, used for shell completion
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct AliasArg {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AliasCommand {
+    Plain(String),
+    // `cmd = ["rg", "--hidden"]` is executed directly, without a shell
+    Argv(Vec<String>),
+    PerOs(PerOsCommand),
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct PerOsCommand {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linux: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub macos: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub windows: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl Default for AliasCommand {
+    fn default() -> Self {
+        AliasCommand::Plain(String::new())
+    }
+}
+
+impl AliasCommand {
+    pub fn for_current_os(&self) -> Option<&str> {
+        match self {
+            AliasCommand::Plain(cmd) => Some(cmd),
+            AliasCommand::Argv(_) => None,
+            AliasCommand::PerOs(variants) => variants
+                .current_os_slot()
+                .as_deref()
+                .or(variants.default.as_deref()),
+        }
+    }
+
+    // editing a platform-specific alias only replaces the variant for the
+    // platform we're on, other variants are kept as they are
+    pub fn set_for_current_os(&mut self, cmd: &str) {
+        match self {
+            AliasCommand::Plain(_) | AliasCommand::Argv(_) => {
+                *self = AliasCommand::Plain(cmd.to_string())
+            }
+            AliasCommand::PerOs(variants) => match variants.current_os_slot_mut() {
+                Some(slot) => *slot = Some(cmd.to_string()),
+                None => variants.default = Some(cmd.to_string()),
+            },
+        }
+    }
+}
+
+impl PerOsCommand {
+    fn current_os_slot(&self) -> &Option<String> {
+        match env::consts::OS {
+            "linux" => &self.linux,
+            "macos" => &self.macos,
+            "windows" => &self.windows,
+            _ => &self.default,
+        }
+    }
+
+    fn current_os_slot_mut(&mut self) -> Option<&mut Option<String>> {
+        match env::consts::OS {
+            "linux" => Some(&mut self.linux),
+            "macos" => Some(&mut self.macos),
+            "windows" => Some(&mut self.windows),
+            _ => None,
+        }
+    }
+}
+
+impl Alias {
+    pub fn new(cmd: &str) -> Self {
+        Alias {
+            cmd: AliasCommand::Plain(cmd.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // why the alias doesn't resolve in the current environment, if it doesn't
+    pub fn unavailable_reason(&self) -> Option<String> {
+        if let Some(reason) = self.unavailable_on_machine_reason() {
+            return Some(reason);
+        }
+
+        if let Some(root) = &self.only_under {
+            let root = expand_tilde(root);
+            let root = root.canonicalize().unwrap_or(root);
+            let cwd = env::current_dir().and_then(|cwd| cwd.canonicalize());
+
+            if !cwd.is_ok_and(|cwd| cwd.starts_with(&root)) {
+                return Some(format!(
+                    "it only resolves under '{}'",
+                    root.to_string_lossy()
+                ));
+            }
+        }
+
+        None
+    }
+
+    // the part of the checks that doesn't depend on where pintas is invoked,
+    // used to decide whether the alias gets a shim at all
+    pub fn unavailable_on_machine_reason(&self) -> Option<String> {
+        if self.disabled {
+            return Some("it is disabled, 'pintas enable' turns it back on".to_string());
+        }
+
+        if !self.hosts.is_empty() {
+            let hostname = sys::hostname().unwrap_or_default();
+
+            if !self.hosts.iter().any(|host| host_matches(host, hostname)) {
+                return Some(format!(
+                    "it is limited to hosts {} and this is '{}'",
+                    self.hosts.join(", "),
+                    hostname
+                ));
+            }
+        }
+
+        None
+    }
+
+    // the programs listed in `requires` that can't be found
+    pub fn missing_programs(&self) -> Vec<&str> {
+        self.requires
+            .iter()
+            .map(String::as_str)
+            .filter(|program| sys::find_program(program).is_none())
+            .collect()
+    }
+
+    // the usage message when the number of arguments is out of bounds
+    pub fn arity_error(&self, name: &str, count: usize) -> Option<String> {
+        let min = self.min_args.unwrap_or(0);
+        let max = self.max_args.unwrap_or(usize::MAX);
+
+        if (min..=max).contains(&count) {
+            return None;
+        }
+
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        let expected = match (self.min_args, self.max_args) {
+            (Some(min), Some(max)) if min == max => format!("exactly {} {}", min, plural(min)),
+            (Some(min), Some(max)) => format!("{} to {} arguments", min, max),
+            (Some(min), None) => format!("at least {} {}", min, plural(min)),
+            (None, _) => format!("at most {} {}", max, plural(max)),
+        };
+
+        let usage: Vec<String> = self
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                if i < min {
+                    format!("<{}>", arg.name)
+                } else {
+                    format!("[{}]", arg.name)
+                }
+            })
+            .collect();
+
+        if usage.is_empty() {
+            return Some(format!("{} expects {}, got {}", name, expected, count));
+        }
+
+        Some(format!(
+            "{} expects {}, got {}: {}",
+            name,
+            expected,
+            count,
+            usage.join(" ")
+        ))
+    }
+
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    // the commands a run executes in order: those of a checklist, the steps
+    // of a multi-step alias or the single command for this platform
+    pub fn commands(&self) -> Option<Vec<&str>> {
+        if !self.checklist.is_empty() {
+            return Some(
+                self.checklist
+                    .iter()
+                    .filter_map(|step| step.cmd.as_deref())
+                    .collect(),
+            );
+        }
+
+        if !self.steps.is_empty() {
+            return Some(self.steps.iter().map(String::as_str).collect());
+        }
+
+        // an alias can consist of its deps alone
+        match self.cmd.for_current_os() {
+            Some("") if !self.deps.is_empty() => Some(Vec::new()),
+            cmd => cmd.map(|cmd| vec![cmd]),
+        }
+    }
+
+    // the argv of an alias that runs without a shell
+    pub fn argv(&self) -> Option<&[String]> {
+        match &self.cmd {
+            AliasCommand::Argv(argv) if self.steps.is_empty() => Some(argv),
+            _ => None,
+        }
+    }
+
+    fn plain_steps(&self) -> Option<&[String]> {
+        let plain = Alias {
+            steps: self.steps.clone(),
+            ..Default::default()
+        };
+
+        (!self.steps.is_empty() && *self == plain).then_some(&self.steps)
+    }
+
+    fn plain_command(&self) -> Option<&str> {
+        match &self.cmd {
+            AliasCommand::Plain(cmd) if *self == Alias::new(cmd) => Some(cmd),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    // `g push` is the sub-alias `push` of the alias `g`
+    pub fn lookup(&self, name: &str) -> Option<&Alias> {
+        let mut path = name.split(' ');
+        let alias = self.aliases.get(path.next()?)?;
+
+        path.try_fold(alias, |alias, sub| alias.sub.get(sub))
+    }
+
+    // the alias that lists `name` in its `aka`, a name can't be both
+    pub fn aka_owner(&self, name: &str) -> Option<&str> {
+        if self.aliases.contains_key(name) {
+            return None;
+        }
+
+        self.aliases
+            .iter()
+            .find(|(_, alias)| alias.aka.iter().any(|aka| aka == name))
+            .map(|(owner, _)| owner.as_str())
+    }
+
+    // leading arguments select sub-aliases for as long as they match one,
+    // returns the resolved name and how many arguments were used up
+    pub fn dispatch(&self, name: &str, args: &[String]) -> (String, usize) {
+        let mut name = name.to_string();
+        let mut used = 0;
+
+        while let Some(alias) = self.lookup(&name) {
+            match args.get(used) {
+                Some(arg) if alias.sub.contains_key(arg) => {
+                    name = format!("{} {}", name, arg);
+                    used += 1;
+                }
+                _ => break,
+            }
+        }
+
+        (name, used)
+    }
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path, env::var("HOME")) {
+        ("~", Ok(home)) => PathBuf::from(home),
+        (_, Ok(home)) if path.starts_with("~/") => PathBuf::from(home).join(&path[2..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+// `laptop` matches both `laptop` and `laptop.local`
+fn host_matches(host: &str, hostname: &str) -> bool {
+    let short = hostname.split('.').next().unwrap_or(hostname);
+
+    host.eq_ignore_ascii_case(hostname) || host.eq_ignore_ascii_case(short)
+}
+
+mod alias_map {
+    use super::Alias;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum AliasEntry {
+        Command(String),
+        Steps(Vec<String>),
+        Table(Box<Alias>),
+    }
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum AliasEntryRef<'a> {
+        Command(&'a str),
+        Steps(&'a [String]),
+        Table(&'a Alias),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<String, Alias>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let entries = HashMap::<String, AliasEntry>::deserialize(deserializer)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|(name, entry)| match entry {
+                AliasEntry::Command(cmd) => (name, Alias::new(&cmd)),
+                AliasEntry::Steps(steps) => (
+                    name,
+                    Alias {
+                        steps,
+                        ..Default::default()
+                    },
+                ),
+                AliasEntry::Table(alias) => (name, *alias),
+            })
+            .collect())
+    }
+
+    pub fn serialize<S>(aliases: &HashMap<String, Alias>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entries: BTreeMap<&str, AliasEntryRef> = aliases
+            .iter()
+            .map(|(name, alias)| {
+                let entry = match (alias.plain_command(), alias.plain_steps()) {
+                    (Some(cmd), _) => AliasEntryRef::Command(cmd),
+                    (None, Some(steps)) => AliasEntryRef::Steps(steps),
+                    (None, None) => AliasEntryRef::Table(alias),
+                };
+
+                (name.as_str(), entry)
+            })
+            .collect();
+
+        entries.serialize(serializer)
+    }
+}
+
+pub fn load_config() -> Result<Config> {
+    let content = fs::read_to_string(CONFIG_FILENAME)
+        .with_context(|| format!("Configuration file '{}' not found.", CONFIG_FILENAME))?;
+
+    let mut config: Config = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse '{}'.", CONFIG_FILENAME))?;
+
+    if is_sharded() {
+        load_shards(&mut config)?;
+    }
+
+    session::load_layer(config)
+}
+
+pub fn save_config(config: &Config) -> Result<()> {
+    let lasting = session::without_session(config);
+
+    write_config(&lasting)?;
+    session::save_layer(config)?;
+
+    // the index is only a cache, a stale one is detected and skipped
+    let _ = index::write_index(&lasting);
+
+    Ok(())
+}
+
+fn write_config(config: &Config) -> Result<()> {
+    if !is_sharded() {
+        return write_toml(Path::new(CONFIG_FILENAME), config);
+    }
+
+    let mut root = Config {
+        aliases: HashMap::new(),
+        ..config.clone()
+    };
+    let mut shards: BTreeMap<&str, Shard> = BTreeMap::new();
+
+    for (name, alias) in &config.aliases {
+        match namespace(name) {
+            Some((namespace, rest)) => {
+                shards
+                    .entry(namespace)
+                    .or_default()
+                    .aliases
+                    .insert(rest.to_string(), alias.clone());
+            }
+            None => {
+                root.aliases.insert(name.clone(), alias.clone());
+            }
+        }
+    }
+
+    write_toml(Path::new(CONFIG_FILENAME), &root)?;
+
+    for (namespace, shard) in &shards {
+        write_toml(&shard_path(namespace), shard)?;
+    }
+
+    // a namespace whose last alias was removed loses its file
+    for path in shard_paths()? {
+        let namespace = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        if !shards.contains_key(namespace.as_ref()) {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove '{}'.", path.to_string_lossy()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_toml<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let toml_string = toml::to_string(value).context("Failed to serialize configuration.")?;
+
+    fs::write(path, toml_string)
+        .with_context(|| format!("Failed to write to '{}'.", path.to_string_lossy()))
+}
+
+// `git.co` lives in namespace `git` as `co`
+pub fn namespace(name: &str) -> Option<(&str, &str)> {
+    name.split_once('.')
+        .filter(|(namespace, rest)| !namespace.is_empty() && !rest.is_empty())
+}
+
+// the file an alias is written to, its shard in the sharded layout
+pub fn defining_path(name: &str, alias: &Alias) -> Result<PathBuf> {
+    if alias.session {
+        return session::current_layer_path();
+    }
+
+    Ok(match namespace(name).filter(|_| is_sharded()) {
+        Some((namespace, _)) => shard_path(namespace),
+        None => PathBuf::from(CONFIG_FILENAME),
+    })
+}
+
+// every file the config is read from
+pub fn source_paths() -> Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILENAME)];
+
+    if is_sharded() {
+        paths.extend(shard_paths()?);
+    }
+
+    Ok(paths)
+}
+
+// the sharded layout is opt-in: it's used as soon as the directory exists
+fn is_sharded() -> bool {
+    Path::new(SHARD_DIRNAME).is_dir()
+}
+
+fn shard_path(namespace: &str) -> PathBuf {
+    Path::new(SHARD_DIRNAME).join(format!("{}.toml", namespace))
+}
+
+fn shard_paths() -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(SHARD_DIRNAME)
+        .with_context(|| format!("Failed to read '{}'.", SHARD_DIRNAME))?
+    {
+        let path = entry?.path();
+
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "toml") {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn load_shards(config: &mut Config) -> Result<()> {
+    for path in shard_paths()? {
+        let namespace = path.file_stem().unwrap_or_default().to_string_lossy();
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read '{}'.", path.to_string_lossy()))?;
+        let shard: Shard = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse '{}'.", path.to_string_lossy()))?;
+
+        for (name, alias) in shard.aliases {
+            let name = format!("{}.{}", namespace, name);
+
+            if config.aliases.contains_key(&name) {
+                return Err(anyhow!(
+                    "Alias '{}' is defined in both '{}' and '{}'.",
+                    name,
+                    CONFIG_FILENAME,
+                    path.to_string_lossy()
+                ));
+            }
+
+            config.aliases.insert(name, alias);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(content: &str) -> Config {
+        toml::from_str(content).unwrap()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    const NESTED: &str = r#"
+[aliases]
+gs = "git status"
+
+[aliases.g]
+cmd = "git"
+
+[aliases.g.sub]
+push = "git push"
+
+[aliases.g.sub.remote]
+cmd = "git remote -v"
+aka = ["gr"]
+
+[aliases.g.sub.remote.sub]
+add = "git remote add"
+"#;
+
+    #[test]
+    fn lookup_follows_sub_aliases() {
+        let config = config(NESTED);
+
+        assert!(config.lookup("gs").is_some());
+        assert!(config.lookup("g push").is_some());
+        assert!(config.lookup("g remote add").is_some());
+        assert!(config.lookup("g pull").is_none());
+        assert!(config.lookup("gs push").is_none());
+        assert!(config.lookup("push").is_none());
+        assert!(config.lookup("").is_none());
+    }
+
+    #[test]
+    fn dispatch_uses_leading_arguments_that_name_sub_aliases() {
+        let config = config(NESTED);
+
+        assert_eq!(config.dispatch("g", &[]), ("g".to_string(), 0));
+        assert_eq!(
+            config.dispatch("g", &args(&["push", "origin"])),
+            ("g push".to_string(), 1)
+        );
+        assert_eq!(
+            config.dispatch("g", &args(&["remote", "add", "up", "url"])),
+            ("g remote add".to_string(), 2)
+        );
+        assert_eq!(
+            config.dispatch("g", &args(&["origin", "push"])),
+            ("g".to_string(), 0)
+        );
+        assert_eq!(
+            config.dispatch("gs", &args(&["push"])),
+            ("gs".to_string(), 0)
+        );
+        assert_eq!(
+            config.dispatch("missing", &args(&["push"])),
+            ("missing".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn every_dispatched_name_can_be_looked_up() {
+        let config = config(NESTED);
+
+        for line in [&["push"][..], &["remote"], &["remote", "add", "x"], &["x"]] {
+            let (name, _) = config.dispatch("g", &args(line));
+
+            assert!(config.lookup(&name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn aka_owner_only_answers_for_names_that_are_not_aliases() {
+        let config = config(
+            r#"
+[aliases.status]
+cmd = "git status"
+aka = ["st", "gs"]
+
+[aliases]
+gs = "git status --short"
+"#,
+        );
+
+        assert_eq!(config.aka_owner("st"), Some("status"));
+        assert_eq!(config.aka_owner("gs"), None);
+        assert_eq!(config.aka_owner("nope"), None);
+    }
+
+    #[test]
+    fn tilde_is_expanded_at_the_start_only() {
+        let Ok(home) = env::var("HOME") else {
+            return;
+        };
+
+        assert_eq!(expand_tilde("~"), PathBuf::from(&home));
+        assert_eq!(expand_tilde("~/logs"), PathBuf::from(&home).join("logs"));
+        assert_eq!(expand_tilde("logs/~"), PathBuf::from("logs/~"));
+        assert_eq!(expand_tilde("~other"), PathBuf::from("~other"));
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, get_pintas_dir, load_config};
+use crate::history::{self, Entry};
+use crate::list;
+use crate::sha256;
+use crate::stats;
+
+const CHANGELOG_FILENAME: &str = "changelog";
+const DIGEST_FILENAME: &str = "digest";
+const WEEK: u64 = 7 * 86400;
+
+#[derive(Args, Clone)]
+pub struct DigestArgs {
+    #[arg(long, default_value_t = 7)]
+    pub days: u64,
+}
+
+// one change of an alias as stored in `~/.pintas/changelog`, a tab
+// separated line per change, written by `pintas sync`
+struct Change {
+    time: u64,
+    kind: String,
+    hash: String,
+    alias: String,
+}
+
+fn get_changelog_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(CHANGELOG_FILENAME))
+}
+
+fn get_digest_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(DIGEST_FILENAME))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+fn read_changes() -> Result<Vec<Change>> {
+    let content = match fs::read_to_string(get_changelog_path()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context("Failed to read alias changelog"),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+
+            Some(Change {
+                time: fields.next()?.parse().ok()?,
+                kind: fields.next()?.to_string(),
+                hash: fields.next()?.to_string(),
+                alias: fields.next()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+// appends the aliases that were added, changed or removed since the last
+// sync, the first sync only notes what's there as `known`, session aliases
+// come and go with their shell and aren't tracked
+pub fn record_changes(config: &Config) -> Result<()> {
+    let path = get_changelog_path()?;
+    let first = !path.exists();
+    let mut known: HashMap<String, String> = HashMap::new();
+
+    for change in read_changes()? {
+        if change.kind == "removed" {
+            known.remove(&change.alias);
+        } else {
+            known.insert(change.alias, change.hash);
+        }
+    }
+
+    let mut current = BTreeMap::new();
+
+    for (name, alias) in config.aliases.iter().filter(|(_, alias)| !alias.session) {
+        let definition = toml::to_string(alias).context("Failed to serialize alias")?;
+
+        current.insert(name, sha256::hex_digest(definition.as_bytes()));
+    }
+
+    let now = now();
+    let mut lines = String::new();
+
+    for (name, hash) in &current {
+        let kind = match known.get(name.as_str()) {
+            _ if first => "known",
+            None => "added",
+            Some(known) if known != hash => "changed",
+            Some(_) => continue,
+        };
+
+        lines.push_str(&format!("{}\t{}\t{}\t{}\n", now, kind, hash, name));
+    }
+
+    let mut removed: Vec<_> = known
+        .keys()
+        .filter(|name| !current.contains_key(name))
+        .collect();
+
+    removed.sort();
+
+    for name in removed {
+        lines.push_str(&format!("{}\tremoved\t-\t{}\n", now, name));
+    }
+
+    if lines.is_empty() && !first {
+        return Ok(());
+    }
+
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context("Failed to open alias changelog")?;
+
+    file.write_all(lines.as_bytes())
+        .context("Failed to write alias changelog")
+}
+
+pub fn run_digest(config: &Config, digest: &DigestArgs) -> Result<()> {
+    print_digest(config, digest.days * 86400)
+}
+
+// `[settings] digest = true` shows the week's digest on stderr after the
+// first command run at a terminal seven days after the last one, so piped
+// output stays as it is
+pub fn show_scheduled() {
+    if !io::stderr().is_terminal() {
+        return;
+    }
+
+    let Ok(config) = load_config() else {
+        return;
+    };
+
+    if !config.settings.digest {
+        return;
+    }
+
+    let Ok(path) = get_digest_path() else {
+        return;
+    };
+
+    let shown = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u64>().ok());
+    let now = now();
+
+    if shown.is_some_and(|shown| now < shown + WEEK) {
+        return;
+    }
+
+    // the digest isn't shown again if its time can't be written
+    if fs::create_dir_all(path.parent().unwrap_or(&path))
+        .and_then(|_| fs::write(&path, format!("{}\n", now)))
+        .is_err()
+    {
+        return;
+    }
+
+    let mut report = Vec::new();
+
+    if write_digest(&mut report, &config, WEEK).is_ok() {
+        eprintln!();
+        eprint!("{}", String::from_utf8_lossy(&report));
+    }
+}
+
+fn print_digest(config: &Config, period: u64) -> Result<()> {
+    write_digest(&mut io::stdout().lock(), config, period)
+}
+
+fn write_digest(out: &mut impl Write, config: &Config, period: u64) -> Result<()> {
+    let now = now();
+    let since = now.saturating_sub(period);
+    let mut runs = history::read_entries()?;
+
+    runs.retain(|entry| entry.started >= since);
+
+    writeln!(
+        out,
+        "Your pintas digest, {} to {}:",
+        &history::format_timestamp(since)[..10],
+        &history::format_timestamp(now)[..10]
+    )?;
+
+    if !config.settings.history {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Runs aren't recorded, set history = true in [settings] to see them here."
+        )?;
+    } else if runs.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "No runs yet, your aliases are waiting for you.")?;
+    } else {
+        write_runs(out, config, &runs)?;
+    }
+
+    write_changes(out, since)
+}
+
+fn write_runs(out: &mut impl Write, config: &Config, runs: &[Entry]) -> Result<()> {
+    // the same name in different projects counts as one alias here
+    let mut by_alias: BTreeMap<&str, (usize, usize, i32)> = BTreeMap::new();
+
+    for entry in runs {
+        let counts = by_alias.entry(&entry.alias).or_default();
+
+        counts.0 += 1;
+
+        if entry.exit_code != 0 {
+            counts.1 += 1;
+            counts.2 = entry.exit_code;
+        }
+    }
+
+    let failed: usize = by_alias.values().map(|counts| counts.1).sum();
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} of {}, {}.",
+        stats::plural(runs.len(), "run"),
+        stats::plural(by_alias.len(), "alias"),
+        match failed {
+            0 => "none of them failed".to_string(),
+            failed => format!("{} failed", failed),
+        }
+    )?;
+
+    let mut most_used: Vec<_> = by_alias.iter().collect();
+
+    most_used.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(b.0)));
+
+    writeln!(out)?;
+    writeln!(out, "Most used:")?;
+
+    for (name, (count, _, _)) in most_used.iter().take(5) {
+        writeln!(out, "  {:<15} {}", name, stats::plural(*count, "run"))?;
+    }
+
+    let failures: Vec<_> = most_used
+        .iter()
+        .filter(|(_, (_, failed, _))| *failed > 0)
+        .collect();
+
+    if !failures.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Failures:")?;
+
+        for (name, (count, failed, exit_code)) in failures {
+            writeln!(
+                out,
+                "  {:<15} {} of {}, last exit code {}",
+                name,
+                failed,
+                stats::plural(*count, "run"),
+                exit_code
+            )?;
+        }
+    }
+
+    let mut slowest: Vec<_> = runs.iter().collect();
+
+    slowest.sort_by_key(|entry| Reverse(entry.duration_ms));
+
+    writeln!(out)?;
+    writeln!(out, "Slowest runs:")?;
+
+    for entry in slowest.iter().take(3) {
+        writeln!(
+            out,
+            "  {:<15} {} on {}",
+            entry.alias,
+            stats::format_duration(entry.duration_ms),
+            &history::format_timestamp(entry.started)[..10]
+        )?;
+    }
+
+    // what the shims typed instead of you, for aliases that still exist
+    let typed: usize = runs
+        .iter()
+        .filter_map(|entry| {
+            let command = list::command_line(config.aliases.get(&entry.alias)?)?;
+
+            Some(command.len().saturating_sub(entry.alias.len()))
+        })
+        .sum();
+
+    if typed > 0 {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Your aliases saved you typing about {} characters, nice work.",
+            typed
+        )?;
+    }
+
+    Ok(())
+}
+
+// an alias added and removed within the period doesn't show up, one added
+// and then changed is still new
+fn write_changes(out: &mut impl Write, since: u64) -> Result<()> {
+    let mut by_alias: BTreeMap<String, (bool, String)> = BTreeMap::new();
+
+    for change in read_changes()?
+        .into_iter()
+        .filter(|change| change.time >= since && change.kind != "known")
+    {
+        let seen = by_alias.entry(change.alias).or_default();
+
+        seen.0 |= change.kind == "added";
+        seen.1 = change.kind;
+    }
+
+    let changes: Vec<_> = by_alias
+        .into_iter()
+        .filter_map(|(name, (added, last))| match last.as_str() {
+            "removed" if added => None,
+            "removed" => Some(('-', name, "removed")),
+            _ if added => Some(('+', name, "added")),
+            _ => Some(('~', name, "changed")),
+        })
+        .collect();
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "New and changed aliases:")?;
+
+    for (mark, name, kind) in changes {
+        writeln!(out, "  {} {:<13} {}", mark, name, kind)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::config::{Alias, Config, load_config};
+use crate::scripts;
+use crate::shims::{self, get_shims_dir};
+
+pub fn run_doctor() -> Result<()> {
+    let mut problems = 0;
+
+    let config = match load_config() {
+        Ok(config) => {
+            println!("Config: {} aliases loaded.", config.aliases.len());
+
+            config
+        }
+        Err(e) => {
+            println!("Config: {:#}", e);
+
+            return Ok(());
+        }
+    };
+
+    let shims_dir = get_shims_dir()?;
+    let on_path =
+        env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir == shims_dir));
+
+    if !shims_dir.is_dir() {
+        problems += 1;
+        println!(
+            "Shims: '{}' doesn't exist, run 'pintas sync'.",
+            shims_dir.to_string_lossy()
+        );
+    } else if !on_path {
+        problems += 1;
+        println!(
+            "Shims: '{}' is not on PATH, see 'pintas init bash'.",
+            shims_dir.to_string_lossy()
+        );
+    } else {
+        println!("Shims: '{}' is on PATH.", shims_dir.to_string_lossy());
+    }
+
+    if shims::shared_shims_dir().is_some() {
+        problems += check_shared(&config, &shims_dir)?;
+    }
+
+    // the shims of a provisioned directory are for other users' aliases too
+    let stale = if shims::is_provisioned() {
+        Vec::new()
+    } else {
+        shims::stale_shims(&config)?
+    };
+
+    if !stale.is_empty() {
+        problems += 1;
+        println!(
+            "Stale shims: left by removed aliases {}, run 'pintas prune'.",
+            stale.join(", ")
+        );
+    }
+
+    let missing = missing_programs(&config);
+
+    if !missing.is_empty() {
+        problems += 1;
+
+        let report: Vec<String> = missing
+            .iter()
+            .map(|(program, aliases)| format!("{} (needed by {})", program, aliases.join(", ")))
+            .collect();
+
+        println!("Missing programs: {}", report.join(", "));
+    }
+
+    let missing_scripts = scripts::missing_scripts(&config);
+
+    if !missing_scripts.is_empty() {
+        problems += 1;
+        println!(
+            "Missing scripts: the files of {} don't exist.",
+            missing_scripts.join(", ")
+        );
+    }
+
+    let unused_scripts = scripts::unused_scripts(&config)?;
+
+    if !unused_scripts.is_empty() {
+        problems += 1;
+        println!(
+            "Unused scripts: {} in '{}', run 'pintas prune'.",
+            unused_scripts.join(", "),
+            scripts::get_scripts_dir()?.to_string_lossy()
+        );
+    }
+
+    if problems == 0 {
+        println!("No problems found.");
+    } else {
+        println!("Problems found: {}", problems);
+    }
+
+    Ok(())
+}
+
+// a shared directory on everyone's PATH must only be writable by its owner,
+// anyone else who can write there could run commands as every user
+fn check_shared(config: &Config, shims_dir: &Path) -> Result<usize> {
+    let mut problems = 0;
+    let parent = shims_dir.parent().unwrap_or(Path::new("/"));
+
+    for dir in [shims_dir, parent] {
+        let writable_by_others =
+            fs::metadata(dir).is_ok_and(|meta| meta.permissions().mode() & 0o022 != 0);
+
+        if writable_by_others {
+            problems += 1;
+            println!(
+                "Shared shims: '{}' can be written by other users, make it writable by its owner only.",
+                dir.to_string_lossy()
+            );
+        }
+    }
+
+    let missing: Vec<String> = shims::expected_shims(config)?
+        .into_values()
+        .map(|(file, _)| file)
+        .filter(|file| !shims_dir.join(file).is_file())
+        .collect();
+
+    if shims::is_provisioned() && !missing.is_empty() {
+        println!(
+            "Shared shims: no shim for {} yet, use 'pintas run' until the owner of '{}' syncs it.",
+            missing.join(", "),
+            shims_dir.to_string_lossy()
+        );
+    } else if shims::is_provisioned() {
+        println!(
+            "Shared shims: '{}' has a shim for every alias.",
+            shims_dir.to_string_lossy()
+        );
+    }
+
+    Ok(problems)
+}
+
+// every program in `requires` that can't be found, with the aliases that
+// need it
+fn missing_programs(config: &Config) -> BTreeMap<String, Vec<String>> {
+    let mut missing = BTreeMap::new();
+
+    for (name, alias) in &config.aliases {
+        collect_missing(name, alias, &mut missing);
+    }
+
+    for aliases in missing.values_mut() {
+        aliases.sort();
+    }
+
+    missing
+}
+
+fn collect_missing(name: &str, alias: &Alias, missing: &mut BTreeMap<String, Vec<String>>) {
+    for program in alias.missing_programs() {
+        missing
+            .entry(program.to_string())
+            .or_default()
+            .push(name.to_string());
+    }
+
+    for (sub, alias) in &alias.sub {
+        collect_missing(&format!("{} {}", name, sub), alias, missing);
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::Path;
+
+// supports the common subset of the format: `KEY=value`, an optional
+// `export` prefix, `#` comments, and single or double quoted values
+pub fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file '{}'.", path.to_string_lossy()))?;
+
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+
+            Some(parse_line(line).ok_or_else(|| {
+                anyhow!(
+                    "Invalid line {} in env file '{}'.",
+                    i + 1,
+                    path.to_string_lossy()
+                )
+            }))
+        })
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    Some((key.to_string(), parse_value(value.trim())))
+}
+
+fn parse_value(value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return inner.to_string();
+    }
+
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return inner
+            .replace("\\n", "\n")
+            .replace("\\t", "\t")
+            .replace("\\\"", "\"");
+    }
+
+    // unquoted values can carry a trailing comment
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
@@ -0,0 +1,576 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::process::Command as OsCommand;
+
+// replaces `${name}` with the value from the `[vars]` table, references to
+// unknown names are left untouched so regular shell parameters still work
+pub fn expand_vars(command: &str, vars: &HashMap<String, String>) -> String {
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+
+        let after = &rest[start + 2..];
+
+        match after.find('}') {
+            Some(end) if vars.contains_key(&after[..end]) => {
+                expanded.push_str(&vars[&after[..end]]);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                expanded.push_str("${");
+                rest = after;
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+// where a placeholder sits in the shell command, its value has to be quoted
+// differently in each
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Quoting {
+    Unquoted,
+    Single,
+    Double,
+}
+
+// replaces `{name}` and `{name:argument}` placeholders, `resolve` returns
+// None for names it doesn't know so brace expansion like `{a,b}` or the
+// `{}` of `find -exec` reach the shell untouched, the filters of
+// `{name|upper}` are passed along for `apply_filters` and the quotes the
+// placeholder is in for `quote_for`
+pub fn expand_placeholders<F>(command: &str, mut resolve: F) -> Result<String>
+where
+    F: FnMut(&str, Option<&str>, &[&str], Quoting) -> Option<Result<String>>,
+{
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+    let mut quoting = Quoting::Unquoted;
+    let mut escaped = false;
+
+    while let Some(start) = rest.find('{') {
+        let dollar = rest[..start].ends_with('$');
+
+        quoting = track_quotes(&rest[..start], quoting, &mut escaped);
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let placeholder = rest.find('}').filter(|_| !dollar).and_then(|end| {
+            let mut filters: Vec<&str> = rest[1..end].split('|').collect();
+            let (name, argument) = parse_placeholder(filters.remove(0))?;
+
+            Some((resolve(name, argument, &filters, quoting)?, end))
+        });
+
+        match placeholder {
+            Some((value, end)) => {
+                expanded.push_str(&value?);
+                rest = &rest[end + 1..];
+                escaped = false;
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+                escaped = false;
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+// the quotes open after `text`, a backslash outside single quotes escapes
+// the next character and may end `text`, so it's carried in `escaped`
+fn track_quotes(text: &str, mut quoting: Quoting, escaped: &mut bool) -> Quoting {
+    for c in text.chars() {
+        if *escaped {
+            *escaped = false;
+            continue;
+        }
+
+        quoting = match (quoting, c) {
+            (Quoting::Single, '\'') => Quoting::Unquoted,
+            (Quoting::Single, _) => Quoting::Single,
+            (_, '\\') => {
+                *escaped = true;
+                quoting
+            }
+            (Quoting::Unquoted, '\'') => Quoting::Single,
+            (Quoting::Unquoted, '"') => Quoting::Double,
+            (Quoting::Double, '"') => Quoting::Unquoted,
+            (quoting, _) => quoting,
+        };
+    }
+
+    quoting
+}
+
+// a value that stays a single word and is never run as code where it's
+// placed: in quotes of its own outside any, or escaped for the quotes it's in
+pub fn quote_for(value: &str, quoting: Quoting) -> String {
+    match quoting {
+        Quoting::Unquoted => shell_quote(value),
+        Quoting::Single => value.replace('\'', "'\\''"),
+        Quoting::Double => {
+            let mut quoted = String::with_capacity(value.len());
+
+            for c in value.chars() {
+                if matches!(c, '$' | '`' | '\\' | '"') {
+                    quoted.push('\\');
+                }
+
+                quoted.push(c);
+            }
+
+            quoted
+        }
+    }
+}
+
+fn parse_placeholder(content: &str) -> Option<(&str, Option<&str>)> {
+    let (name, argument) = match content.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (content, None),
+    };
+
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    valid.then_some((name, argument))
+}
+
+// `{1|slugify}` or `{cwd|basename}`, filters apply left to right
+pub fn apply_filters(value: String, filters: &[&str]) -> Result<String> {
+    filters.iter().try_fold(value, |value, filter| {
+        let filtered = match filter.trim() {
+            "upper" => value.to_uppercase(),
+            "lower" => value.to_lowercase(),
+            "trim" => value.trim().to_string(),
+            "slugify" => slugify(&value),
+            "basename" => value
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            "dirname" => match value.trim_end_matches('/').rsplit_once('/') {
+                Some(("", _)) => "/".to_string(),
+                Some((dir, _)) => dir.to_string(),
+                None => ".".to_string(),
+            },
+            other => {
+                return Err(anyhow!(
+                    "Unknown filter '{}', expected upper, lower, trim, slugify, basename or dirname",
+                    other
+                ));
+            }
+        };
+
+        Ok(filtered)
+    })
+}
+
+// lowercase words joined by dashes, `Fix: the Login bug!` is
+// `fix-the-login-bug`
+fn slugify(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// `{var:region}` is `region` from the `[vars]` table, like `${region}` but
+// with filters
+pub fn var(
+    vars: &HashMap<String, String>,
+    name: &str,
+    argument: Option<&str>,
+) -> Option<Result<String>> {
+    let key = argument.filter(|_| name == "var")?;
+
+    Some(vars.get(key).cloned().ok_or_else(|| {
+        anyhow!(
+            "Failed to resolve placeholder {{var:{}}}: no such variable in [vars]",
+            key
+        )
+    }))
+}
+
+pub fn builtin(name: &str, argument: Option<&str>) -> Option<Result<String>> {
+    let value = match (name, argument) {
+        ("cwd", None) => env::current_dir()
+            .map(|cwd| cwd.to_string_lossy().into_owned())
+            .context("Failed to get the current directory"),
+        ("repo_root", None) => git(&["rev-parse", "--show-toplevel"]),
+        ("branch", None) => git(&["rev-parse", "--abbrev-ref", "HEAD"]),
+        ("date", None) => date("%Y-%m-%d"),
+        ("date", Some(format)) => date(format),
+        _ => return None,
+    };
+
+    Some(value.with_context(|| format!("Failed to resolve placeholder {{{}}}", name)))
+}
+
+// `{1}` is the first argument and `{2:-main}` the second one or `main`,
+// `ask` is called for missing values without a default
+pub fn positional(
+    args: &[String],
+    name: &str,
+    argument: Option<&str>,
+    ask: &mut dyn FnMut(&str) -> Result<String>,
+) -> Option<Result<String>> {
+    let index: usize = name.parse().ok().filter(|&index| index > 0)?;
+
+    fill(name, args.get(index - 1), argument, ask)
+}
+
+// `{env}` is the value of `--env` for aliases that declare `env` in params
+pub fn named(
+    params: &HashMap<String, String>,
+    declared: &[String],
+    name: &str,
+    argument: Option<&str>,
+    ask: &mut dyn FnMut(&str) -> Result<String>,
+) -> Option<Result<String>> {
+    if !declared.iter().any(|param| param == name) {
+        return None;
+    }
+
+    fill(name, params.get(name), argument, ask)
+}
+
+fn fill(
+    name: &str,
+    value: Option<&String>,
+    argument: Option<&str>,
+    ask: &mut dyn FnMut(&str) -> Result<String>,
+) -> Option<Result<String>> {
+    // `{1:main}` is most likely a `{1:-main}` missing its dash, left as it
+    // is it would reach the shell as text
+    let default = match argument {
+        Some(argument) => match argument.strip_prefix('-') {
+            Some(default) => Some(default),
+            None => {
+                return Some(Err(anyhow!(
+                    "Failed to resolve placeholder {{{}:{}}}: a default is written {{{}:-{}}}",
+                    name,
+                    argument,
+                    name,
+                    argument
+                )));
+            }
+        },
+        None => None,
+    };
+
+    let value = match (value, default) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, Some(default)) => Ok(default.to_string()),
+        (None, None) => ask(name),
+    };
+
+    Some(value)
+}
+
+// asks on the terminal for a value that is missing, scripts and pipes get an
+// error instead of a prompt nobody answers
+pub fn prompt(name: &str, message: &str) -> Result<String> {
+    let stdin = io::stdin();
+
+    if !stdin.is_terminal() {
+        return Err(anyhow!(
+            "Failed to resolve placeholder {{{}}}: missing value",
+            name
+        ));
+    }
+
+    loop {
+        let mut answer = String::new();
+
+        eprint!("{}: ", message);
+
+        if stdin.read_line(&mut answer)? == 0 {
+            return Err(anyhow!(
+                "Failed to resolve placeholder {{{}}}: no value given",
+                name
+            ));
+        }
+
+        let answer = answer.trim_end_matches(['\n', '\r']);
+
+        if !answer.is_empty() {
+            return Ok(answer.to_string());
+        }
+    }
+}
+
+// pulls `--name value` and `--name=value` for the declared params out of
+// the arguments, a `--` ends the params and everything after it is kept
+pub fn split_params(
+    declared: &[String],
+    args: &[String],
+) -> Result<(HashMap<String, String>, Vec<String>)> {
+    let mut params = HashMap::new();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" && !declared.is_empty() {
+            rest.extend(args.cloned());
+            break;
+        }
+
+        let Some(flag) = arg.strip_prefix("--") else {
+            rest.push(arg.clone());
+            continue;
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+
+        if !declared.iter().any(|param| param == name) {
+            rest.push(arg.clone());
+            continue;
+        }
+
+        let value = match value {
+            Some(value) => value,
+            None => args
+                .next()
+                .cloned()
+                .ok_or_else(|| anyhow!("Parameter '--{}' needs a value.", name))?,
+        };
+
+        params.insert(name.to_string(), value);
+    }
+
+    Ok((params, rest))
+}
+
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// an argv command as it would be typed, only quoting the words that need it
+pub fn join_argv(argv: &[String]) -> String {
+    let words: Vec<String> = argv
+        .iter()
+        .map(|word| {
+            let plain = !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c));
+
+            if plain {
+                word.clone()
+            } else {
+                shell_quote(word)
+            }
+        })
+        .collect();
+
+    words.join(" ")
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = OsCommand::new("git")
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!("not inside a git repository"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// leaves the formatting to date(1) so the local timezone and the full set
+// of strftime directives behave as users expect
+fn date(format: &str) -> Result<String> {
+    let output = OsCommand::new("date")
+        .arg(format!("+{}", format))
+        .output()
+        .context("Failed to run date")?;
+
+    if !output.status.success() {
+        return Err(anyhow!("invalid date format '{}'", format));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // fills `{1}` with `value` the way a posix alias does, quoted for
+    // where the placeholder sits
+    fn fill_quoted(command: &str, value: &str) -> String {
+        expand_placeholders(command, |name, _, _, quoting| {
+            (name == "1").then(|| Ok(quote_for(value, quoting)))
+        })
+        .unwrap()
+    }
+
+    // what sh prints for the command
+    fn sh(command: &str) -> String {
+        let output = OsCommand::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .unwrap();
+
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn placeholders_know_the_quotes_they_are_in() {
+        let mut seen = Vec::new();
+
+        expand_placeholders(
+            r#"a {1} '{2}' "{3}" '\' {4} "\" {5}" \' {6} ${x} {a,b}"#,
+            |name, _, _, quoting| {
+                seen.push((name.to_string(), quoting));
+                None
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            seen,
+            [
+                ("1".to_string(), Quoting::Unquoted),
+                ("2".to_string(), Quoting::Single),
+                ("3".to_string(), Quoting::Double),
+                ("4".to_string(), Quoting::Unquoted),
+                ("5".to_string(), Quoting::Double),
+                ("6".to_string(), Quoting::Unquoted),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values_stay_one_word_and_never_run() {
+        let values = [
+            "plain",
+            "foo; echo PWNED",
+            "it's",
+            "$(echo PWNED) `echo PWNED` $HOME",
+            "\"quoted\" \\ back\\slash",
+            "",
+        ];
+
+        for value in values {
+            assert_eq!(sh(&fill_quoted("printf %s {1}", value)), value);
+            assert_eq!(
+                sh(&fill_quoted("printf %s 'x{1}x'", value)),
+                format!("x{}x", value)
+            );
+            assert_eq!(
+                sh(&fill_quoted("printf %s \"x{1}x\"", value)),
+                format!("x{}x", value)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_placeholders_reach_the_shell_untouched() {
+        let command = "echo {a,b} {} ${HOME} {1}";
+        let expanded = expand_placeholders(command, |_, _, _, _| None).unwrap();
+
+        assert_eq!(expanded, command);
+    }
+
+    #[test]
+    fn positional_defaults_need_a_dash() {
+        let mut ask = |name: &str| Err(anyhow!("asked for {}", name));
+        let args = vec!["given".to_string()];
+
+        assert_eq!(
+            positional(&args, "1", Some("-main"), &mut ask)
+                .unwrap()
+                .unwrap(),
+            "given"
+        );
+        assert_eq!(
+            positional(&[], "1", Some("-main"), &mut ask)
+                .unwrap()
+                .unwrap(),
+            "main"
+        );
+        assert!(
+            positional(&[], "1", Some("main"), &mut ask)
+                .unwrap()
+                .is_err()
+        );
+        assert!(
+            positional(&args, "1", Some("main"), &mut ask)
+                .unwrap()
+                .is_err()
+        );
+        assert!(positional(&[], "1", None, &mut ask).unwrap().is_err());
+        assert!(positional(&args, "0", None, &mut ask).is_none());
+    }
+
+    #[test]
+    fn vars_replace_known_names_only() {
+        let vars = HashMap::from([("region".to_string(), "eu".to_string())]);
+
+        assert_eq!(
+            expand_vars("deploy ${region} ${HOME} ${", &vars),
+            "deploy eu ${HOME} ${"
+        );
+    }
+
+    #[test]
+    fn filters_apply_left_to_right() {
+        assert_eq!(
+            apply_filters(" Fix: the Login bug! ".to_string(), &["trim", "slugify"]).unwrap(),
+            "fix-the-login-bug"
+        );
+        assert_eq!(
+            apply_filters("/a/b/c/".to_string(), &["dirname", "basename"]).unwrap(),
+            "b"
+        );
+        assert_eq!(apply_filters("/a".to_string(), &["dirname"]).unwrap(), "/");
+        assert!(apply_filters("x".to_string(), &["reverse"]).is_err());
+    }
+
+    #[test]
+    fn params_are_split_from_the_other_arguments() {
+        let declared = vec!["env".to_string()];
+        let args: Vec<String> = ["--env", "prod", "-v", "--other", "--", "--env=x"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let (params, rest) = split_params(&declared, &args).unwrap();
+
+        assert_eq!(params["env"], "prod");
+        assert_eq!(rest, ["-v", "--other", "--env=x"]);
+        assert!(split_params(&declared, &["--env".to_string()]).is_err());
+    }
+
+    #[test]
+    fn argv_is_joined_as_it_would_be_typed() {
+        let argv: Vec<String> = ["git", "commit", "-m", "it's done", ""]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+
+        assert_eq!(join_argv(&argv), "git commit -m 'it'\\''s done' ''");
+    }
+}
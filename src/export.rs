@@ -0,0 +1,197 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, ValueEnum};
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::config::{Alias, Config};
+use crate::expand::shell_quote;
+use crate::json::Json;
+use crate::sha256;
+use crate::shims::shim_name;
+
+#[derive(Args, Clone)]
+pub struct ExportArgs {
+    #[arg(long = "as", value_enum)]
+    pub format: Launcher,
+    // where raycast script commands are written, one file per alias
+    #[arg(long, value_name = "DIR")]
+    pub out: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Launcher {
+    Raycast,
+    Alfred,
+    Rofi,
+}
+
+// a stable handle for launchers, alias names can hold spaces, slashes and
+// other things that don't survive file names and menu entries
+pub fn alias_id(name: &str) -> String {
+    sha256::hex_digest(name.as_bytes())[..12].to_string()
+}
+
+pub fn alias_for_id<'a>(config: &'a Config, id: &str) -> Option<&'a str> {
+    config
+        .aliases
+        .keys()
+        .find(|name| alias_id(name) == id)
+        .map(String::as_str)
+}
+
+// launchers start pintas from their own directory, the generated files
+// change into the project the config was exported from first
+pub fn run_export(config: &Config, export: &ExportArgs) -> Result<()> {
+    let pintas = env::current_exe().context("Failed to get current executable path")?;
+    let project = env::current_dir().context("Failed to get current directory")?;
+    let pintas = pintas.to_string_lossy();
+    let project = project.to_string_lossy();
+
+    let mut aliases: Vec<(&String, &Alias)> = config
+        .aliases
+        .iter()
+        .filter(|(_, alias)| alias.unavailable_on_machine_reason().is_none())
+        .collect();
+
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+
+    match export.format {
+        Launcher::Raycast => {
+            let out = export.out.as_deref().ok_or_else(|| {
+                anyhow!("Raycast script commands are one file per alias, pass --out <DIR>.")
+            })?;
+
+            export_raycast(&aliases, &pintas, &project, Path::new(out))
+        }
+        Launcher::Alfred => {
+            println!("{}", alfred_items(&aliases, &project));
+
+            Ok(())
+        }
+        Launcher::Rofi => {
+            print!("{}", rofi_script(&aliases, &pintas, &project));
+
+            Ok(())
+        }
+    }
+}
+
+fn export_raycast(
+    aliases: &[(&String, &Alias)],
+    pintas: &str,
+    project: &str,
+    out: &Path,
+) -> Result<()> {
+    fs::create_dir_all(out)
+        .with_context(|| format!("Failed to create '{}'", out.to_string_lossy()))?;
+
+    for (name, alias) in aliases {
+        let path = out.join(format!("pintas-{}.sh", shim_name(name)));
+
+        fs::write(&path, raycast_script(name, alias, pintas, project))
+            .with_context(|| format!("Failed to write '{}'", path.to_string_lossy()))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!(
+        "Successfully exported {} aliases to '{}'.",
+        aliases.len(),
+        out.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+pub fn raycast_script(name: &str, alias: &Alias, pintas: &str, project: &str) -> String {
+    let mut script = String::from("#!/bin/sh\n\n");
+
+    script.push_str("# @raycast.schemaVersion 1\n");
+    script.push_str(&format!("# @raycast.title {}\n", name.replace('\n', " ")));
+    script.push_str("# @raycast.mode compact\n");
+    script.push_str("# @raycast.packageName pintas\n");
+
+    if let Some(description) = &alias.description {
+        script.push_str(&format!(
+            "# @raycast.description {}\n",
+            description.replace('\n', " ")
+        ));
+    }
+
+    // a single text field, split into words like on the command line
+    if alias.max_args != Some(0) {
+        let argument = Json::object([
+            ("type", Json::string("text")),
+            ("placeholder", Json::string("arguments")),
+            ("optional", Json::Bool(alias.min_args.unwrap_or(0) == 0)),
+        ]);
+
+        script.push_str(&format!("# @raycast.argument1 {}\n", argument));
+    }
+
+    script.push_str(&format!(
+        "\ncd {} || exit 1\nexec {} run --id {} $1\n",
+        shell_quote(project),
+        shell_quote(pintas),
+        alias_id(name)
+    ));
+
+    script
+}
+
+// the JSON of an Alfred script filter, the project directory is handed to
+// the workflow's run script as the `pintas_project` variable
+fn alfred_items(aliases: &[(&String, &Alias)], project: &str) -> Json {
+    let items = aliases.iter().map(|(name, alias)| {
+        Json::object([
+            ("uid", Json::string(alias_id(name))),
+            ("title", Json::string(name.as_str())),
+            (
+                "subtitle",
+                Json::string(alias.description.clone().unwrap_or_default()),
+            ),
+            ("arg", Json::string(alias_id(name))),
+            ("autocomplete", Json::string(name.as_str())),
+        ])
+    });
+
+    Json::object([
+        (
+            "variables",
+            Json::object([("pintas_project", Json::string(project))]),
+        ),
+        ("items", Json::Array(items.collect())),
+    ])
+}
+
+// a rofi script mode: listing the aliases when called without a selection,
+// starting the selected one detached from rofi otherwise
+pub fn rofi_script(aliases: &[(&String, &Alias)], pintas: &str, project: &str) -> String {
+    let mut script = String::from("#!/bin/sh\n");
+
+    script.push_str("# generated by 'pintas export --as rofi', use it with\n");
+    script.push_str("# rofi -show pintas -modi pintas:<path to this file>\n\n");
+    script.push_str(&format!("cd {} || exit 1\n\n", shell_quote(project)));
+    script.push_str("if [ -n \"$ROFI_INFO\" ]; then\n");
+    script.push_str(&format!(
+        "    setsid {} run --id \"$ROFI_INFO\" >/dev/null 2>&1 &\n",
+        shell_quote(pintas)
+    ));
+    script.push_str("    exit 0\nfi\n\n");
+
+    for (name, alias) in aliases {
+        let entry = match &alias.description {
+            Some(description) => format!("{}: {}", name, description),
+            None => name.to_string(),
+        };
+
+        script.push_str(&format!(
+            "printf '%s\\000info\\037%s\\n' {} {}\n",
+            shell_quote(&entry.replace('\n', " ")),
+            alias_id(name)
+        ));
+    }
+
+    script
+}
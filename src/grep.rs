@@ -0,0 +1,91 @@
+use anyhow::{Result, anyhow};
+use clap::Args;
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::config::Config;
+use crate::list;
+use crate::regex::Regex;
+
+const HIGHLIGHT: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Args, Clone)]
+pub struct GrepArgs {
+    #[arg(required = true)]
+    pub pattern: String,
+    #[arg(short = 'i', long)]
+    pub ignore_case: bool,
+}
+
+// `pintas grep`: the pattern is matched against the names of the aliases
+// and every option `list --long` shows, so a fragment of a command finds
+// the alias it belongs to
+pub fn run_grep(config: &Config, grep: &GrepArgs) -> Result<()> {
+    let pattern = if grep.ignore_case {
+        format!("(?i){}", grep.pattern)
+    } else {
+        grep.pattern.clone()
+    };
+    let regex = Regex::new(&pattern)?;
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+
+    let mut aliases: Vec<_> = config.aliases.iter().collect();
+
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut found = 0;
+
+    for (name, alias) in aliases {
+        let details: Vec<_> = list::details(alias)
+            .into_iter()
+            .filter(|(_, value)| regex.is_match(value))
+            .collect();
+
+        if details.is_empty() && !regex.is_match(name) {
+            continue;
+        }
+
+        found += 1;
+
+        println!("{}", highlight(name, &regex, color));
+
+        for (key, value) in details {
+            // a script only shows the lines that match
+            for line in value.lines().filter(|line| regex.is_match(line)) {
+                println!(
+                    "  {:12} {}",
+                    format!("{}:", key),
+                    highlight(line.trim(), &regex, color)
+                );
+            }
+        }
+    }
+
+    if found == 0 {
+        return Err(anyhow!("No alias matches '{}'.", grep.pattern));
+    }
+
+    Ok(())
+}
+
+fn highlight(text: &str, regex: &Regex, color: bool) -> String {
+    if !color {
+        return text.to_string();
+    }
+
+    let mut highlighted = String::new();
+    let mut last = 0;
+
+    for (start, end) in regex.find_all(text) {
+        highlighted.push_str(&text[last..start]);
+        highlighted.push_str(HIGHLIGHT);
+        highlighted.push_str(&text[start..end]);
+        highlighted.push_str(RESET);
+        last = end;
+    }
+
+    highlighted.push_str(&text[last..]);
+
+    highlighted
+}
@@ -0,0 +1,539 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, Subcommand, ValueEnum};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, get_pintas_dir};
+use crate::expand;
+use crate::json::Json;
+use crate::stats;
+use crate::sys;
+
+const HISTORY_FILENAME: &str = "history";
+const LAST_USED_FILENAME: &str = "last_used";
+
+#[derive(Subcommand, Clone)]
+pub enum HistoryCommand {
+    Export {
+        // only runs started on or after this day, as YYYY-MM-DD in UTC
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+    },
+}
+
+// `pintas history [alias]`, the runs that match every filter given
+#[derive(Args, Clone)]
+pub struct HistoryArgs {
+    pub alias: Option<String>,
+    // only runs started on or after this day, as YYYY-MM-DD in UTC
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<String>,
+    #[arg(long)]
+    pub failed: bool,
+    // only the last N runs
+    #[arg(short = 'n', long, value_name = "N")]
+    pub limit: Option<usize>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+// one run as stored in `~/.pintas/history`, a tab separated line per run,
+// lines written before the command and args were recorded have neither
+pub struct Entry {
+    pub started: u64,
+    pub duration_ms: u128,
+    pub exit_code: i32,
+    pub alias: String,
+    pub project: String,
+    pub args: String,
+    pub command: String,
+}
+
+fn get_history_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(HISTORY_FILENAME))
+}
+
+fn get_last_used_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(LAST_USED_FILENAME))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+// appends the run when `[settings] history` is on, a history that can't be
+// written never fails the run itself, `command` is what the alias expanded
+// to with the args in place
+pub fn record(
+    config: &Config,
+    alias: &str,
+    args: &[String],
+    command: &str,
+    duration: Duration,
+    status: &ExitStatus,
+) {
+    mark_used(alias, Some(duration));
+
+    if !config.settings.history {
+        return;
+    }
+
+    let started = SystemTime::now()
+        .checked_sub(duration)
+        .and_then(|started| started.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |started| started.as_secs());
+    let project = env::current_dir()
+        .map(|cwd| cwd.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let line = format_entry(&Entry {
+        started,
+        duration_ms: duration.as_millis(),
+        exit_code: sys::exit_code(status),
+        alias: alias.to_string(),
+        project,
+        args: expand::join_argv(args),
+        command: command.to_string(),
+    });
+
+    let _ = get_history_path().and_then(|path| {
+        fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(file.write_all(line.as_bytes())?)
+    });
+}
+
+// how often an alias ran, when it last ran and how long its runs took, as
+// stored in `~/.pintas/last_used` with or without `[settings] history`,
+// runs a shim handed its process over to have no duration
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub last: u64,
+    pub runs: usize,
+    pub timed_runs: usize,
+    pub total_ms: u128,
+}
+
+impl Usage {
+    pub fn average_ms(&self) -> Option<u128> {
+        (self.timed_runs > 0).then(|| self.total_ms / self.timed_runs as u128)
+    }
+}
+
+// a tab separated line per alias, lines written before runs were counted
+// only have the time
+pub fn counts() -> HashMap<String, Usage> {
+    let content = get_last_used_path()
+        .and_then(|path| Ok(fs::read_to_string(path)?))
+        .unwrap_or_default();
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.splitn(5, '\t').collect();
+
+            match fields.as_slice() {
+                [last, runs, timed_runs, total_ms, alias] => Some((
+                    alias.to_string(),
+                    Usage {
+                        last: last.parse().ok()?,
+                        runs: runs.parse().ok()?,
+                        timed_runs: timed_runs.parse().ok()?,
+                        total_ms: total_ms.parse().ok()?,
+                    },
+                )),
+                _ => {
+                    let (last, alias) = line.split_once('\t')?;
+
+                    Some((
+                        alias.to_string(),
+                        Usage {
+                            last: last.parse().ok()?,
+                            ..Default::default()
+                        },
+                    ))
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn last_used() -> HashMap<String, u64> {
+    counts()
+        .into_iter()
+        .map(|(alias, usage)| (alias, usage.last))
+        .collect()
+}
+
+fn write_counts(counts: &HashMap<String, Usage>) -> Result<()> {
+    let path = get_last_used_path()?;
+    let mut lines: Vec<_> = counts
+        .iter()
+        .map(|(alias, usage)| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                usage.last, usage.runs, usage.timed_runs, usage.total_ms, alias
+            )
+        })
+        .collect();
+
+    lines.sort();
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    Ok(fs::write(&path, lines.concat())?)
+}
+
+// like the history, a count that can't be written never fails the run
+pub fn mark_used(alias: &str, duration: Option<Duration>) {
+    let mut counts = counts();
+    let usage = counts.entry(alias.to_string()).or_default();
+
+    usage.last = now();
+    usage.runs += 1;
+
+    if let Some(duration) = duration {
+        usage.timed_runs += 1;
+        usage.total_ms += duration.as_millis();
+    }
+
+    let _ = write_counts(&counts);
+}
+
+// the runs of a renamed alias in the current project carry its new name,
+// an alias of the same name in another project is a different one
+pub fn rename(old: &str, new: &str) -> Result<()> {
+    let mut counts = counts();
+
+    if let Some(usage) = counts.remove(old) {
+        counts.insert(new.to_string(), usage);
+        write_counts(&counts)?;
+    }
+
+    let path = get_history_path()?;
+    let project = env::current_dir()
+        .map(|cwd| cwd.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context("Failed to read run history"),
+    };
+
+    let renamed: String = content
+        .lines()
+        .map(|line| match parse_entry(line) {
+            Some(entry) if entry.alias == old && entry.project == project => format_entry(&Entry {
+                alias: new.to_string(),
+                ..entry
+            }),
+            _ => format!("{}\n", line),
+        })
+        .collect();
+
+    if renamed == content {
+        return Ok(());
+    }
+
+    fs::write(&path, renamed).context("Failed to write run history")
+}
+
+pub fn run_history(command: Option<&HistoryCommand>, filter: &HistoryArgs) -> Result<()> {
+    match command {
+        Some(HistoryCommand::Export { since, format }) => export(since.as_deref(), *format),
+        None => print_history(filter),
+    }
+}
+
+// oldest first like a shell history, so the last run is right above the
+// prompt
+fn print_history(filter: &HistoryArgs) -> Result<()> {
+    let since = filter.since.as_deref().map(parse_date).transpose()?;
+    let mut entries = read_entries()?;
+
+    if entries.is_empty() {
+        println!("No runs recorded yet, set history = true in [settings] to record them.");
+
+        return Ok(());
+    }
+
+    entries.retain(|entry| {
+        filter
+            .alias
+            .as_ref()
+            .is_none_or(|alias| entry.alias == *alias)
+            && since.is_none_or(|since| entry.started >= since)
+            && (!filter.failed || entry.exit_code != 0)
+    });
+
+    if let Some(limit) = filter.limit {
+        entries.drain(..entries.len().saturating_sub(limit));
+    }
+
+    if entries.is_empty() {
+        println!("No runs match.");
+
+        return Ok(());
+    }
+
+    for entry in &entries {
+        let mut invocation = entry.alias.clone();
+
+        if !entry.args.is_empty() {
+            invocation.push(' ');
+            invocation.push_str(&entry.args);
+        }
+
+        println!(
+            "{}  {} (exit code: {}, {}) in {}",
+            format_timestamp(entry.started),
+            invocation,
+            entry.exit_code,
+            stats::format_duration(entry.duration_ms),
+            entry.project
+        );
+
+        if !entry.command.is_empty() {
+            println!("    {}", entry.command.trim_end().replace('\n', "\n    "));
+        }
+    }
+
+    Ok(())
+}
+
+fn export(since: Option<&str>, format: Format) -> Result<()> {
+    let since = since.map(parse_date).transpose()?.unwrap_or(0);
+    let mut entries = read_entries()?;
+
+    entries.retain(|entry| entry.started >= since);
+
+    match format {
+        Format::Csv => {
+            println!("started_at,alias,project,duration_ms,exit_code,args,command");
+
+            for entry in &entries {
+                println!(
+                    "{},{},{},{},{},{},{}",
+                    format_timestamp(entry.started),
+                    csv_field(&entry.alias),
+                    csv_field(&entry.project),
+                    entry.duration_ms,
+                    entry.exit_code,
+                    csv_field(&entry.args),
+                    csv_field(&entry.command)
+                );
+            }
+        }
+        Format::Json => {
+            let entries = entries.iter().map(|entry| {
+                Json::object([
+                    ("started_at", Json::string(format_timestamp(entry.started))),
+                    ("alias", Json::string(&entry.alias)),
+                    ("project", Json::string(&entry.project)),
+                    ("duration_ms", Json::Number(entry.duration_ms as f64)),
+                    ("exit_code", Json::Number(entry.exit_code.into())),
+                    ("args", Json::string(&entry.args)),
+                    ("command", Json::string(&entry.command)),
+                ])
+            });
+
+            println!("{}", Json::Array(entries.collect()));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn read_entries() -> Result<Vec<Entry>> {
+    let content = match fs::read_to_string(get_history_path()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context("Failed to read run history"),
+    };
+
+    Ok(content.lines().filter_map(parse_entry).collect())
+}
+
+// when an alias of the current project started and how it exited, oldest
+// first
+pub fn runs(alias: &str) -> Result<Vec<(u64, i32)>> {
+    let project = env::current_dir()
+        .map(|cwd| cwd.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Ok(read_entries()?
+        .into_iter()
+        .filter(|entry| entry.alias == alias && entry.project == project)
+        .map(|entry| (entry.started, entry.exit_code))
+        .collect())
+}
+
+// how often every alias ran, when it last started and how long it took,
+// across projects, from the history when it's kept and from the counts in
+// `last_used` otherwise
+pub fn usage(config: &Config) -> Result<HashMap<String, Usage>> {
+    if !config.settings.history {
+        return Ok(counts());
+    }
+
+    let mut usage: HashMap<String, Usage> = HashMap::new();
+
+    for entry in read_entries()? {
+        let usage = usage.entry(entry.alias).or_default();
+
+        usage.runs += 1;
+        usage.timed_runs += 1;
+        usage.total_ms += entry.duration_ms;
+        usage.last = usage.last.max(entry.started);
+    }
+
+    Ok(usage)
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.splitn(7, '\t');
+
+    Some(Entry {
+        started: fields.next()?.parse().ok()?,
+        duration_ms: fields.next()?.parse().ok()?,
+        exit_code: fields.next()?.parse().ok()?,
+        alias: fields.next()?.to_string(),
+        project: fields.next()?.to_string(),
+        args: fields.next().map(unescape).unwrap_or_default(),
+        command: fields.next().map(unescape).unwrap_or_default(),
+    })
+}
+
+fn format_entry(entry: &Entry) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        entry.started,
+        entry.duration_ms,
+        entry.exit_code,
+        entry.alias,
+        entry.project,
+        escape(&entry.args),
+        escape(&entry.command)
+    )
+}
+
+// a script keeps its tabs and newlines in the one line of its run
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// `2024-01-01` as seconds since the epoch, midnight UTC
+fn parse_date(date: &str) -> Result<u64> {
+    let invalid = || anyhow!("Invalid date '{}', expected YYYY-MM-DD.", date);
+    let mut parts = date.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+    );
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return Err(invalid());
+    }
+
+    Ok((days_from_civil(year, month, day) * 86400) as u64)
+}
+
+// the `2024-01-01T09:30:00Z` that `format_timestamp` writes
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+    let mut parts = time.splitn(3, ':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+
+    Some(parse_date(date).ok()? + hours * 3600 + minutes * 60 + seconds)
+}
+
+pub fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// proleptic Gregorian calendar conversions, after Howard Hinnant's date
+// algorithms
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
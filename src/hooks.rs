@@ -0,0 +1,103 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::io;
+use std::process::{Command as OsCommand, ExitStatus};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::sys;
+
+// set for everything a global hook runs, so a hook calling `pintas run`
+// doesn't trigger the hooks again
+const IN_HOOK_VAR: &str = "PINTAS_IN_HOOK";
+
+// `[hooks] pre_run` runs before every alias invocation and stops it when it
+// fails
+pub fn before_run(config: &Config, alias: &str, to_stderr: bool) -> Result<()> {
+    let Some(hook) = &config.hooks.pre_run else {
+        return Ok(());
+    };
+
+    if env::var_os(IN_HOOK_VAR).is_some() {
+        return Ok(());
+    }
+
+    let status =
+        run_hook(config, hook, alias, None, to_stderr).context("Failed to run the pre_run hook")?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "The pre_run hook failed (exit code: {}), alias '{}' was not run.",
+            sys::exit_code(&status),
+            alias
+        ));
+    }
+
+    Ok(())
+}
+
+// `post_run` follows every invocation and `on_failure` the ones that
+// failed, their own failures are only reported
+pub fn after_run(
+    config: &Config,
+    alias: &str,
+    duration: Duration,
+    status: &ExitStatus,
+    to_stderr: bool,
+) {
+    if env::var_os(IN_HOOK_VAR).is_some() {
+        return;
+    }
+
+    let failed = !status.success();
+    let hooks = [
+        ("post_run", config.hooks.post_run.as_ref()),
+        (
+            "on_failure",
+            config.hooks.on_failure.as_ref().filter(|_| failed),
+        ),
+    ];
+
+    for (kind, hook) in hooks {
+        let Some(hook) = hook else {
+            continue;
+        };
+
+        match run_hook(config, hook, alias, Some((duration, status)), to_stderr) {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: the {} hook failed (exit code: {})",
+                kind,
+                sys::exit_code(&status)
+            ),
+            Err(e) => eprintln!("Warning: failed to run the {} hook: {}", kind, e),
+        }
+    }
+}
+
+fn run_hook(
+    config: &Config,
+    hook: &str,
+    alias: &str,
+    result: Option<(Duration, &ExitStatus)>,
+    to_stderr: bool,
+) -> Result<ExitStatus> {
+    let mut cmd = OsCommand::new(config.settings.shell());
+
+    cmd.arg("-c")
+        .arg(hook)
+        .env(IN_HOOK_VAR, "1")
+        .env("PINTAS_ALIAS", alias);
+
+    if let Some((duration, status)) = result {
+        cmd.env("PINTAS_EXIT_CODE", sys::exit_code(status).to_string());
+        cmd.env("PINTAS_DURATION_MS", duration.as_millis().to_string());
+    }
+
+    // a JSON envelope on stdout has to stay the only thing there
+    if to_stderr {
+        cmd.stdout(io::stderr());
+    }
+
+    Ok(cmd.status()?)
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, ValueEnum};
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use crate::config::{Alias, Config};
+use crate::list::details;
+
+#[derive(Args, Clone)]
+pub struct ImportArgs {
+    #[arg(required = true)]
+    pub file: String,
+    // how to settle every collision without asking
+    #[arg(long, value_enum)]
+    pub strategy: Option<Strategy>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    KeepMine,
+    TakeTheirs,
+    Rename,
+}
+
+// merges the aliases of another pintas.toml, returns the names that were
+// added or replaced
+pub fn import_aliases(config: &mut Config, import: &ImportArgs) -> Result<Vec<String>> {
+    let content = fs::read_to_string(&import.file)
+        .with_context(|| format!("Failed to read '{}'.", import.file))?;
+    let theirs: Config =
+        toml::from_str(&content).with_context(|| format!("Failed to parse '{}'.", import.file))?;
+
+    let interactive = import.strategy.is_none() && io::stdin().is_terminal();
+    let mut names: Vec<_> = theirs.aliases.into_iter().collect();
+    let mut changed = Vec::new();
+    let mut kept = Vec::new();
+
+    names.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, alias) in names {
+        let Some(mine) = config.aliases.get(&name) else {
+            config.aliases.insert(name.clone(), alias);
+            changed.push(name);
+            continue;
+        };
+
+        if *mine == alias {
+            continue;
+        }
+
+        let strategy = match import.strategy {
+            Some(strategy) => strategy,
+            None if interactive => ask(&name, mine, &alias)?,
+            None => {
+                return Err(anyhow!(
+                    "Alias '{}' already exists. Use '--strategy' to import without a terminal.",
+                    name
+                ));
+            }
+        };
+
+        match strategy {
+            Strategy::KeepMine => kept.push(name),
+            Strategy::TakeTheirs => {
+                config.aliases.insert(name.clone(), alias);
+                changed.push(name);
+            }
+            Strategy::Rename => {
+                let renamed = free_name(config, &name);
+
+                println!("Importing '{}' as '{}'.", name, renamed);
+
+                config.aliases.insert(renamed.clone(), alias);
+                changed.push(renamed);
+            }
+        }
+    }
+
+    if !kept.is_empty() {
+        println!("Kept existing aliases: {}", kept.join(", "));
+    }
+
+    Ok(changed)
+}
+
+fn ask(name: &str, mine: &Alias, theirs: &Alias) -> Result<Strategy> {
+    let describe = |alias: &Alias| -> Vec<String> {
+        details(alias)
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect()
+    };
+    let (left, right) = (describe(mine), describe(theirs));
+    let width = left
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("mine".len());
+
+    println!("Alias '{}' already exists:", name);
+    println!("  {:width$} | theirs", "mine");
+
+    for i in 0..left.len().max(right.len()) {
+        println!(
+            "  {:width$} | {}",
+            left.get(i).map_or("", String::as_str),
+            right.get(i).map_or("", String::as_str)
+        );
+    }
+
+    loop {
+        let mut answer = String::new();
+
+        eprint!("[k]eep mine, [t]ake theirs, [r]ename theirs? ");
+
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Err(anyhow!("Import cancelled."));
+        }
+
+        match answer.trim() {
+            "k" => return Ok(Strategy::KeepMine),
+            "t" => return Ok(Strategy::TakeTheirs),
+            "r" => return Ok(Strategy::Rename),
+            _ => {}
+        }
+    }
+}
+
+// `deploy` becomes `deploy-imported`, then `deploy-imported-2` and so on
+fn free_name(config: &Config, name: &str) -> String {
+    let base = format!("{}-imported", name);
+
+    if !config.aliases.contains_key(&base) {
+        return base;
+    }
+
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !config.aliases.contains_key(candidate))
+        .unwrap_or(base)
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::chain;
+use crate::config::{Config, get_pintas_dir, load_config, source_paths};
+use crate::sha256;
+
+// a cache next to the shims that lets `run --internal` parse a single alias
+// instead of the whole config: a header with the stamps of the files it was
+// built from and the offset of every alias, followed by one small toml
+// document per alias
+const INDEX_VERSION: &str = "pintas-index 1";
+
+fn get_index_path() -> Result<PathBuf> {
+    let config_dir = env::current_dir().context("Failed to get the current directory")?;
+
+    // a hash that stays the same across Rust releases, so an upgrade of
+    // pintas finds the indexes it wrote before
+    let digest = sha256::hex_digest(config_dir.as_os_str().as_encoded_bytes());
+
+    Ok(get_pintas_dir()?.join("index").join(&digest[..16]))
+}
+
+// size and modification time, enough to notice a hand edit
+fn stamp(path: &Path) -> Option<String> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some(format!("{} {}", meta.len(), modified.as_nanos()))
+}
+
+pub fn write_index(config: &Config) -> Result<()> {
+    let path = get_index_path()?;
+    let base = Config {
+        aliases: HashMap::new(),
+        ..config.clone()
+    };
+
+    let mut names: Vec<_> = config.aliases.keys().collect();
+    let mut body = toml::to_string(&base).context("Failed to serialize configuration.")?;
+    let mut header = vec![INDEX_VERSION.to_string()];
+
+    names.sort();
+
+    for source in source_paths()? {
+        let stamp = stamp(&source).ok_or_else(|| anyhow!("Failed to stat config file."))?;
+
+        header.push(format!("source {} {}", stamp, source.to_string_lossy()));
+    }
+
+    header.push(format!("base 0 {}", body.len()));
+
+    for name in names {
+        let single = Config {
+            aliases: HashMap::from([(name.clone(), config.aliases[name].clone())]),
+            ..Default::default()
+        };
+        let document = toml::to_string(&single).context("Failed to serialize configuration.")?;
+
+        header.push(format!("alias {} {} {}", body.len(), document.len(), name));
+        body.push_str(&document);
+    }
+
+    header.push("end".to_string());
+
+    fs::create_dir_all(path.parent().unwrap_or(&path))
+        .context("Failed to create index directory")?;
+    fs::write(&path, format!("{}\n{}", header.join("\n"), body))
+        .context("Failed to write alias index")
+}
+
+// the config with only `name` in it, straight from the index when it's up to
+// date and from a full parse otherwise
+pub fn load_alias(name: &str) -> Result<Config> {
+    match read_alias(name) {
+        Some(config)
+            if config.aliases.contains_key(name)
+                && !config.aliases.values().any(chain::has_references) =>
+        {
+            Ok(config)
+        }
+        _ => load_config(),
+    }
+}
+
+fn read_alias(name: &str) -> Option<Config> {
+    let mut reader = BufReader::new(File::open(get_index_path().ok()?).ok()?);
+    let mut sources = Vec::new();
+    let mut base = None;
+    let mut entry = None;
+    let mut header_len = 0;
+    let mut version = true;
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        header_len += line.len();
+
+        let line = line.trim_end_matches('\n');
+
+        if std::mem::take(&mut version) {
+            if line != INDEX_VERSION {
+                return None;
+            }
+
+            continue;
+        }
+
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+        match kind {
+            "source" => {
+                let mut parts = rest.splitn(3, ' ');
+                let (len, modified, path) = (parts.next()?, parts.next()?, parts.next()?);
+
+                sources.push((format!("{} {}", len, modified), PathBuf::from(path)));
+            }
+            "base" => base = Some(parse_range(rest)?),
+            "alias" => {
+                let (range, alias) = rest.rsplit_once(' ')?;
+
+                if alias == name {
+                    entry = Some(parse_range(range)?);
+                }
+            }
+            "end" => break,
+            _ => return None,
+        }
+    }
+
+    // any change to the files or to the set of files makes the index stale
+    let current = source_paths().ok()?;
+
+    if current.len() != sources.len()
+        || sources.iter().any(|(recorded, path)| {
+            !current.contains(path) || stamp(path).as_ref() != Some(recorded)
+        })
+    {
+        return None;
+    }
+
+    let mut config: Config = toml::from_str(&read_range(&mut reader, header_len, base?)?).ok()?;
+
+    if let Some(range) = entry {
+        let single: Config = toml::from_str(&read_range(&mut reader, header_len, range)?).ok()?;
+
+        config.aliases = single.aliases;
+    }
+
+    Some(config)
+}
+
+fn parse_range(text: &str) -> Option<(u64, usize)> {
+    let (offset, len) = text.split_once(' ')?;
+
+    Some((offset.parse().ok()?, len.parse().ok()?))
+}
+
+fn read_range(
+    reader: &mut BufReader<File>,
+    start: usize,
+    (offset, len): (u64, usize),
+) -> Option<String> {
+    let mut buffer = vec![0; len];
+
+    reader.seek(SeekFrom::Start(start as u64 + offset)).ok()?;
+    reader.read_exact(&mut buffer).ok()?;
+
+    String::from_utf8(buffer).ok()
+}
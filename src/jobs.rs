@@ -0,0 +1,286 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::get_pintas_dir;
+use crate::expand;
+use crate::history;
+use crate::run::{self, RunArgs};
+use crate::sys;
+
+// how often `logs --follow` looks for more output, and `kill` for the job
+// to end, which it gives five seconds
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+const KILL_WAIT_POLLS: u32 = 25;
+
+pub fn get_jobs_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("jobs"))
+}
+
+// `run --detach`: the run happens in a pintas of its own, in a session of
+// its own without the terminal, its output goes to `~/.pintas/jobs/<id>.log`
+// and `<id>.job` holds its pid, start time, alias and args as one tab
+// separated line
+pub fn run_detached(run: &RunArgs) -> Result<()> {
+    let dir = get_jobs_dir()?;
+
+    fs::create_dir_all(&dir).context("Failed to create jobs directory")?;
+
+    let (id, mut job) = claim_id(&dir)?;
+    let log_path = dir.join(format!("{}.log", id));
+    let log = File::create(&log_path)
+        .with_context(|| format!("Failed to create '{}'", log_path.to_string_lossy()))?;
+    let mut cmd = run::rerun_command(run)?;
+
+    cmd.stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    unsafe {
+        cmd.pre_exec(sys::start_session);
+    }
+
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = fs::remove_file(dir.join(format!("{}.job", id)));
+
+            return Err(e).context("Failed to start the detached run");
+        }
+    };
+
+    writeln!(
+        job,
+        "{}\t{}\t{}\t{}",
+        child.id(),
+        history::now(),
+        run.alias,
+        expand::join_argv(&run.args)
+    )
+    .context("Failed to write job file")?;
+
+    println!(
+        "Started alias '{}' as job {} (pid {}), its output goes to '{}'.",
+        run.alias,
+        id,
+        child.id(),
+        log_path.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+// the lowest free number above the ones in use, two detached runs started
+// at once still get their own
+fn claim_id(dir: &Path) -> Result<(u32, File)> {
+    let highest = fs::read_dir(dir)
+        .context("Failed to read jobs directory")?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .strip_suffix(".job")?
+                .parse::<u32>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0);
+
+    for id in highest + 1.. {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(format!("{}.job", id)))
+        {
+            Ok(file) => return Ok((id, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).context("Failed to create job file"),
+        }
+    }
+
+    unreachable!()
+}
+
+// one line of a `<id>.job` file
+pub struct Job {
+    pub id: u32,
+    pub pid: u32,
+    pub started: u64,
+    pub alias: String,
+    pub args: String,
+}
+
+impl Job {
+    fn invocation(&self) -> String {
+        if self.args.is_empty() {
+            self.alias.clone()
+        } else {
+            format!("{} {}", self.alias, self.args)
+        }
+    }
+}
+
+fn parse_job(id: u32, content: &str) -> Option<Job> {
+    let mut fields = content.trim_end_matches('\n').splitn(4, '\t');
+
+    Some(Job {
+        id,
+        pid: fields.next()?.parse().ok()?,
+        started: fields.next()?.parse().ok()?,
+        alias: fields.next()?.to_string(),
+        args: fields.next().unwrap_or_default().to_string(),
+    })
+}
+
+// jobs that are still running by id, the files of the ones that ended are
+// removed on the way, their logs stay until the id is used again
+fn running_jobs() -> Result<Vec<Job>> {
+    let dir = get_jobs_dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read jobs directory"),
+    };
+
+    let mut jobs = Vec::new();
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Some(id) = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_suffix(".job")?.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        // a job that is still being started has an empty file
+        let Some(job) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| parse_job(id, &content))
+        else {
+            continue;
+        };
+
+        if sys::is_running(job.pid as i32) {
+            jobs.push(job);
+        } else {
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    jobs.sort_by_key(|job| job.id);
+
+    Ok(jobs)
+}
+
+// a job number, or the name of an alias for its latest running job
+fn find_job(job: &str) -> Result<Job> {
+    let jobs = running_jobs()?;
+
+    let found = match job.parse::<u32>() {
+        Ok(id) => jobs.into_iter().find(|running| running.id == id),
+        Err(_) => jobs.into_iter().rev().find(|running| running.alias == job),
+    };
+
+    found.ok_or_else(|| anyhow!("No running job '{}', 'pintas jobs' lists them.", job))
+}
+
+pub fn run_jobs() -> Result<()> {
+    let jobs = running_jobs()?;
+
+    if jobs.is_empty() {
+        println!("No jobs running.");
+
+        return Ok(());
+    }
+
+    let now = history::now();
+
+    println!("{:<5} {:<8} {:<10} ALIAS", "JOB", "PID", "UPTIME");
+
+    for job in jobs {
+        println!(
+            "{:<5} {:<8} {:<10} {}",
+            job.id,
+            job.pid,
+            format_uptime(now.saturating_sub(job.started)),
+            job.invocation()
+        );
+    }
+
+    Ok(())
+}
+
+// the log so far, with `follow` also what the job prints until it ends,
+// the log of a job that ended is there until its number is used again
+pub fn run_logs(job: &str, follow: bool) -> Result<()> {
+    let (id, pid) = match find_job(job) {
+        Ok(running) => (running.id, Some(running.pid)),
+        Err(e) => (job.parse::<u32>().map_err(|_| e)?, None),
+    };
+    let path = get_jobs_dir()?.join(format!("{}.log", id));
+    let mut log = File::open(&path)
+        .with_context(|| format!("Failed to open '{}'", path.to_string_lossy()))?;
+    let mut out = io::stdout().lock();
+
+    loop {
+        io::copy(&mut log, &mut out).context("Failed to read job log")?;
+        out.flush()?;
+
+        if !follow || !pid.is_some_and(|pid| sys::is_running(pid as i32)) {
+            break;
+        }
+
+        thread::sleep(FOLLOW_INTERVAL);
+    }
+
+    // whatever came in between the last read and the end
+    io::copy(&mut log, &mut out).context("Failed to read job log")?;
+
+    Ok(())
+}
+
+// SIGTERM to the job and everything it started, or SIGKILL with `force`,
+// and a few seconds for it to end
+pub fn run_kill(job: &str, force: bool) -> Result<()> {
+    let job = find_job(job)?;
+
+    sys::kill_group(job.pid, if force { sys::SIGKILL } else { sys::SIGTERM });
+
+    let stopped = (0..KILL_WAIT_POLLS).any(|_| {
+        thread::sleep(FOLLOW_INTERVAL);
+
+        !sys::is_running(job.pid as i32)
+    });
+
+    if !stopped {
+        return Err(anyhow!(
+            "Job {} ({}) is still running, 'pintas kill --force {}' stops it for sure.",
+            job.id,
+            job.invocation(),
+            job.id
+        ));
+    }
+
+    let _ = fs::remove_file(get_jobs_dir()?.join(format!("{}.job", job.id)));
+
+    println!("Stopped job {} ({}).", job.id, job.invocation());
+
+    Ok(())
+}
+
+// a day and more, hours, minutes or seconds, whichever two are biggest
+fn format_uptime(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
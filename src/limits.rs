@@ -0,0 +1,105 @@
+use anyhow::{Result, anyhow};
+use std::os::unix::process::CommandExt;
+use std::process::Command as OsCommand;
+
+use crate::config::{Alias, Limits};
+use crate::sys;
+use crate::timeout;
+
+// `512M`, `2G` or a bare number of bytes, in powers of 1024
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(split);
+    let value: u64 = value.parse().ok()?;
+
+    let scale: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return None,
+    };
+
+    value.checked_mul(scale).filter(|bytes| *bytes > 0)
+}
+
+// the limits as resources with soft and hard values for setrlimit, cpu time
+// in whole seconds rounded up, with a second more before the hard limit so
+// the program gets SIGXCPU before it's killed
+fn resolve(name: &str, limits: &Limits) -> Result<Vec<(i32, u64, u64)>> {
+    let mut resolved = Vec::new();
+
+    if let Some(memory) = &limits.memory {
+        let bytes = parse_size(memory).ok_or_else(|| {
+            anyhow!(
+                "Alias '{}' has an invalid memory limit '{}', expected a size like 512M or 2G.",
+                name,
+                memory
+            )
+        })?;
+
+        resolved.push((sys::RLIMIT_AS, bytes, bytes));
+    }
+
+    if let Some(cpu_time) = &limits.cpu_time {
+        let duration = timeout::parse(cpu_time).ok_or_else(|| {
+            anyhow!(
+                "Alias '{}' has an invalid cpu_time limit '{}', expected a duration like 30s, 5m or 1h.",
+                name,
+                cpu_time
+            )
+        })?;
+
+        let seconds = duration.as_secs_f64().ceil() as u64;
+
+        resolved.push((sys::RLIMIT_CPU, seconds, seconds + 1));
+    }
+
+    if let Some(open_files) = limits.open_files {
+        if open_files == 0 {
+            return Err(anyhow!(
+                "Alias '{}' has an open_files limit of 0, which leaves it no stdin or stdout.",
+                name
+            ));
+        }
+
+        resolved.push((sys::RLIMIT_NOFILE, open_files, open_files));
+    }
+
+    Ok(resolved)
+}
+
+// checks `limits` before anything runs
+pub fn check(name: &str, alias: &Alias) -> Result<()> {
+    if let Some(limits) = &alias.limits {
+        resolve(name, limits)?;
+    }
+
+    Ok(())
+}
+
+// the child limits itself between fork and exec, so the limits hold for
+// the program and everything it starts but not for pintas
+pub fn apply(cmd: &mut OsCommand, name: &str, alias: &Alias) -> Result<()> {
+    let Some(limits) = &alias.limits else {
+        return Ok(());
+    };
+
+    let resolved = resolve(name, limits)?;
+
+    unsafe {
+        cmd.pre_exec(move || {
+            for &(resource, soft, hard) in &resolved {
+                sys::set_limit(resource, soft, hard)?;
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
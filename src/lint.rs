@@ -0,0 +1,205 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::fs;
+
+use crate::config::{Alias, AliasCommand, Config, expand_tilde};
+use crate::regex::Regex;
+use crate::{dotenv, expand, scripts};
+
+#[derive(Deserialize, Default)]
+struct LintPolicy {
+    #[serde(default)]
+    require_description: bool,
+    #[serde(default)]
+    allowed_tags: Option<Vec<String>>,
+    #[serde(default)]
+    secret_patterns: Vec<String>,
+}
+
+struct Linter {
+    policy: LintPolicy,
+    secret_patterns: Vec<(String, Regex)>,
+}
+
+impl Linter {
+    fn load(path: &str) -> Result<Linter> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lint policy '{}'.", path))?;
+        let policy: LintPolicy = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse lint policy '{}'.", path))?;
+        let secret_patterns = policy
+            .secret_patterns
+            .iter()
+            .map(|pattern| Ok((pattern.clone(), Regex::new(pattern)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Linter {
+            policy,
+            secret_patterns,
+        })
+    }
+
+    fn check(&self, config: &Config, alias: &Alias) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.policy.require_description
+            && alias
+                .description
+                .as_deref()
+                .is_none_or(|description| description.trim().is_empty())
+        {
+            problems.push("missing a description".to_string());
+        }
+
+        if let Some(allowed) = &self.policy.allowed_tags {
+            for tag in alias.tags.iter().filter(|tag| !allowed.contains(tag)) {
+                problems.push(format!("tag '{}' is not in the allowed set", tag));
+            }
+        }
+
+        for text in searchable_text(config, alias) {
+            for (pattern, regex) in &self.secret_patterns {
+                if regex.is_match(&text) {
+                    problems.push(format!("looks like it contains a secret ('{}')", pattern));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+// everything that ends up in the command line or environment of a run:
+// commands with their [vars] filled in, env_file and script contents
+fn searchable_text(config: &Config, alias: &Alias) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    match &alias.cmd {
+        AliasCommand::Plain(cmd) => commands.push(cmd.as_str()),
+        AliasCommand::Argv(argv) => commands.extend(argv.iter().map(String::as_str)),
+        AliasCommand::PerOs(variants) => commands.extend(
+            [
+                &variants.linux,
+                &variants.macos,
+                &variants.windows,
+                &variants.default,
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::as_str),
+        ),
+    }
+
+    commands.extend(alias.steps.iter().map(String::as_str));
+    commands.extend(
+        alias
+            .checklist
+            .iter()
+            .filter_map(|step| step.cmd.as_deref()),
+    );
+    commands.extend(alias.headless.as_deref());
+    commands.extend(alias.pre.as_deref());
+    commands.extend(alias.post.as_deref());
+    commands.extend(alias.cleanup.as_deref());
+
+    let mut texts: Vec<String> = commands
+        .iter()
+        .map(|command| expand::expand_vars(command, &config.vars))
+        .collect();
+
+    // `{var:name}` placeholders are only filled at run time
+    let mut names: Vec<_> = config
+        .vars
+        .keys()
+        .filter(|name| {
+            let placeholder = format!("{{var:{}}}", name);
+
+            commands
+                .iter()
+                .any(|command| command.contains(&placeholder))
+        })
+        .collect();
+
+    names.sort();
+    texts.extend(names.into_iter().map(|name| config.vars[name].clone()));
+    texts.extend(alias.env.values().cloned());
+
+    // files that can't be read yet are reported when the alias runs
+    if let Some(Ok(variables)) = alias
+        .env_file
+        .as_deref()
+        .map(|path| dotenv::load(&expand_tilde(path)))
+    {
+        texts.extend(variables.into_iter().map(|(_, value)| value));
+    }
+
+    if let Some(Ok(content)) = scripts::script_path(alias)
+        .and_then(|path| path.ok())
+        .map(fs::read_to_string)
+    {
+        texts.push(content);
+    }
+
+    texts.extend(
+        alias
+            .sub
+            .values()
+            .flat_map(|sub| searchable_text(config, sub)),
+    );
+
+    texts
+}
+
+pub fn run_lint(config: &Config, policy: Option<&str>) -> Result<()> {
+    let path = policy
+        .or(config.settings.lint_policy.as_deref())
+        .ok_or_else(|| {
+            anyhow!("No lint policy given. Use '--policy <file>' or set [settings] lint_policy.")
+        })?;
+    let linter = Linter::load(path)?;
+
+    let mut names: Vec<_> = config.aliases.keys().collect();
+    let mut count = 0;
+
+    names.sort();
+
+    for name in names {
+        for problem in linter.check(config, &config.aliases[name]) {
+            println!("{}: {}", name, problem);
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        return Err(anyhow!(
+            "Lint found {} problems with policy '{}'.",
+            count,
+            path
+        ));
+    }
+
+    println!("All aliases pass policy '{}'.", path);
+
+    Ok(())
+}
+
+// add and edit refuse changes that break the policy configured for the
+// project in [settings] lint_policy
+pub fn enforce(config: &Config, name: &str, alias: &Alias) -> Result<()> {
+    let Some(path) = &config.settings.lint_policy else {
+        return Ok(());
+    };
+
+    let problems = Linter::load(path)?.check(config, alias);
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Alias '{}' violates lint policy '{}': {}.",
+        name,
+        path,
+        problems.join(", ")
+    ))
+}
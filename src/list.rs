@@ -0,0 +1,822 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, ValueEnum};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::io::{self, Write};
+
+use crate::config::{Alias, AliasCommand, Busy, Config, PinMismatch, defining_path};
+use crate::expand;
+use crate::export;
+use crate::history;
+use crate::json::Json;
+use crate::shims;
+use crate::timeout;
+
+// commands longer than this are cut in the short listing to keep the
+// description column readable, `--long` always shows them in full
+const MAX_COMMAND_WIDTH: usize = 48;
+
+#[derive(Args, Clone)]
+pub struct ListArgs {
+    #[arg(long)]
+    pub long: bool,
+    #[arg(long, conflicts_with = "long")]
+    pub tree: bool,
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    #[arg(long, conflicts_with_all = ["long", "tree", "toml"])]
+    pub json: bool,
+    #[arg(long, conflicts_with_all = ["long", "tree"])]
+    pub toml: bool,
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["long", "tree", "json", "toml"])]
+    pub format: Option<String>,
+    #[arg(long, conflicts_with_all = ["long", "tree", "json", "toml", "format"])]
+    pub names_only: bool,
+    #[arg(long, value_enum, default_value_t = Sort::Name, conflicts_with_all = ["tree", "toml"])]
+    pub sort: Sort,
+    // only aliases that haven't run for this long, like `90d`
+    #[arg(long, value_name = "AGE")]
+    pub unused_since: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Sort {
+    Name,
+    // most runs first
+    Used,
+    // last run first
+    Recent,
+}
+
+// what `--format` can insert, e.g. `--format '{name}\t{cmd}'`
+const FORMAT_FIELDS: [&str; 6] = ["name", "cmd", "description", "tags", "aka", "id"];
+
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+pub fn list_aliases(config: &Config, list: &ListArgs) -> Result<()> {
+    if list.json || list.toml {
+        return print_structured(config, list);
+    }
+
+    if let Some(template) = &list.format {
+        return print_formatted(config, list, template);
+    }
+
+    if list.names_only {
+        return print_formatted(config, list, "{name}");
+    }
+
+    let mut sorted_aliases = selected(config, list)?;
+    let tagged = sorted_aliases.len();
+
+    println!("Available aliases:");
+
+    sorted_aliases.retain(|(_, alias)| alias.unavailable_reason().is_none());
+
+    let hidden = tagged - sorted_aliases.len();
+
+    if sorted_aliases.is_empty() {
+        println!("No aliases found.");
+    } else {
+        sort_aliases(config, &mut sorted_aliases, list.sort)?;
+
+        if list.long {
+            print_long(&sorted_aliases);
+        } else if list.tree {
+            print_tree(&Group::build(&sorted_aliases), 0);
+        } else {
+            let names: Vec<_> = sorted_aliases
+                .iter()
+                .map(|(name, alias)| (name.as_str(), *alias))
+                .collect();
+
+            print_columns(&names, "");
+        }
+    }
+
+    if hidden > 0 {
+        println!("({} hidden, not available here)", hidden);
+    }
+
+    Ok(())
+}
+
+// the aliases with the tags, and with `--unused-since` only the ones that
+// last ran before then, or were created before then and never ran, an alias
+// without either time counts as unused
+fn selected<'a>(config: &'a Config, list: &ListArgs) -> Result<Vec<(&'a String, &'a Alias)>> {
+    let mut aliases: Vec<_> = config
+        .aliases
+        .iter()
+        .filter(|(_, alias)| alias.has_tags(&list.tags))
+        .collect();
+
+    let Some(age) = &list.unused_since else {
+        return Ok(aliases);
+    };
+
+    let age = timeout::parse(age).ok_or_else(|| {
+        anyhow!(
+            "Invalid --unused-since '{}', expected a duration like '90d' or '12h'.",
+            age
+        )
+    })?;
+    let cutoff = history::now().saturating_sub(age.as_secs());
+    let last_used = history::last_used();
+
+    aliases.retain(|(name, alias)| {
+        let active = last_used.get(name.as_str()).copied().or_else(|| {
+            alias
+                .created_at
+                .as_deref()
+                .and_then(history::parse_timestamp)
+        });
+
+        active.is_none_or(|active| active < cutoff)
+    });
+
+    Ok(aliases)
+}
+
+// by name, or by how often and how recently aliases ran with ties and
+// aliases that never ran kept by name
+fn sort_aliases(config: &Config, aliases: &mut [(&String, &Alias)], sort: Sort) -> Result<()> {
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+
+    if sort == Sort::Name {
+        return Ok(());
+    }
+
+    let usage = history::usage(config)?;
+    let usage_of = |name: &str| usage.get(name).copied().unwrap_or_default();
+
+    match sort {
+        Sort::Used => aliases.sort_by_key(|(name, _)| Reverse(usage_of(name).runs)),
+        Sort::Recent => aliases.sort_by_key(|(name, _)| Reverse(usage_of(name).last)),
+        Sort::Name => {}
+    }
+
+    Ok(())
+}
+
+// one line per available alias, like the plain listing but shaped for
+// dmenu, rofi or fzf and without a header
+fn print_formatted(config: &Config, list: &ListArgs, template: &str) -> Result<()> {
+    let segments = parse_format(template)?;
+    let mut aliases = selected(config, list)?;
+
+    aliases.retain(|(_, alias)| alias.unavailable_reason().is_none());
+
+    sort_aliases(config, &mut aliases, list.sort)?;
+
+    let mut out = io::stdout().lock();
+
+    for (name, alias) in aliases {
+        let line: String = segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Field(field) => format_field(name, alias, field),
+            })
+            .collect();
+
+        // a pipe into `head` or a picker may close early, that's not an error
+        match writeln!(out, "{}", line) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            written => written?,
+        }
+    }
+
+    Ok(())
+}
+
+// `{field}` inserts a field, `{{` and `}}` are literal braces, and `\t`,
+// `\n` and `\\` are escapes, since shells pass them on as they are
+fn parse_format(template: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+            '{' => {
+                let mut field = String::new();
+                let mut closed = false;
+
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+
+                    field.push(c);
+                }
+
+                if !closed {
+                    return Err(anyhow!("Unclosed '{{' in --format."));
+                }
+
+                if !FORMAT_FIELDS.contains(&field.as_str()) {
+                    return Err(anyhow!(
+                        "Unknown field '{{{}}}' in --format, expected one of {}.",
+                        field,
+                        FORMAT_FIELDS
+                            .map(|field| format!("{{{}}}", field))
+                            .join(", ")
+                    ));
+                }
+
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+                segments.push(Segment::Field(field));
+            }
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            _ => text.push(c),
+        }
+    }
+
+    segments.push(Segment::Text(text));
+
+    Ok(segments)
+}
+
+// every field stays on its line
+fn format_field(name: &str, alias: &Alias, field: &str) -> String {
+    let value = match field {
+        "name" => name.to_string(),
+        "cmd" => match &alias.script {
+            Some(script) => script.clone(),
+            None if !alias.checklist.is_empty() => String::new(),
+            None => command_line(alias).unwrap_or_default(),
+        },
+        "description" => alias.description.clone().unwrap_or_default(),
+        "tags" => alias.tags.join(","),
+        "aka" => alias.aka.join(","),
+        "id" => export::alias_id(name),
+        _ => String::new(),
+    };
+
+    value.replace(['\n', '\t'], " ")
+}
+
+// for scripts and editor plugins every alias with the tags is included, with
+// the options as they are written in the config and the ones that aren't
+// available here marked instead of hidden
+fn print_structured(config: &Config, list: &ListArgs) -> Result<()> {
+    let aliases: HashMap<String, Alias> = selected(config, list)?
+        .into_iter()
+        .map(|(name, alias)| (name.clone(), alias.clone()))
+        .collect();
+
+    if list.toml {
+        let selected = Config {
+            aliases,
+            ..Default::default()
+        };
+
+        print!(
+            "{}",
+            toml::to_string(&selected).context("Failed to serialize aliases.")?
+        );
+
+        return Ok(());
+    }
+
+    let mut sorted: Vec<_> = aliases.iter().collect();
+
+    sort_aliases(config, &mut sorted, list.sort)?;
+
+    let mut records = Vec::new();
+
+    for (name, alias) in sorted {
+        let options = toml::Value::try_from(alias).context("Failed to serialize aliases.")?;
+        let mut fields = vec![
+            ("name".to_string(), Json::string(name)),
+            (
+                "available".to_string(),
+                Json::Bool(alias.unavailable_reason().is_none()),
+            ),
+        ];
+
+        if let Json::Object(options) = toml_to_json(options) {
+            fields.extend(options);
+        }
+
+        records.push(Json::Object(fields));
+    }
+
+    println!("{}", Json::Array(records));
+
+    Ok(())
+}
+
+fn toml_to_json(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(value) => Json::String(value),
+        toml::Value::Integer(value) => Json::Number(value as f64),
+        toml::Value::Float(value) => Json::Number(value),
+        toml::Value::Boolean(value) => Json::Bool(value),
+        toml::Value::Datetime(value) => Json::string(value.to_string()),
+        toml::Value::Array(values) => Json::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Json::object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value))),
+        ),
+    }
+}
+
+pub fn command_summary(alias: &Alias) -> String {
+    if let Some(script) = &alias.script {
+        return format!("(script {})", script);
+    }
+
+    if !alias.checklist.is_empty() {
+        return format!("(checklist of {} steps)", alias.checklist.len());
+    }
+
+    match command_line(alias) {
+        Some(line) => format!("\"{}\"", line),
+        None => format!("(no command for {})", env::consts::OS),
+    }
+}
+
+// the command of an alias on one line, a script is summed up by its first
+// line and parallel steps are joined like they run, with `&`
+pub fn command_line(alias: &Alias) -> Option<String> {
+    if let Some(argv) = alias.argv() {
+        return Some(expand::join_argv(argv));
+    }
+
+    let commands: Vec<String> = alias
+        .commands()?
+        .iter()
+        .map(|command| match command.trim().split_once('\n') {
+            Some((first, _)) => format!("{} ...", first),
+            None => command.to_string(),
+        })
+        .collect();
+
+    Some(commands.join(if alias.parallel { " & " } else { " && " }))
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let cut: String = text.chars().take(width - 1).collect();
+
+    format!("{}…", cut)
+}
+
+fn print_columns(aliases: &[(&str, &Alias)], indent: &str) {
+    let commands: Vec<String> = aliases
+        .iter()
+        .map(|(_, alias)| truncate(&command_summary(alias), MAX_COMMAND_WIDTH))
+        .collect();
+
+    let name_width = aliases
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let command_width = commands
+        .iter()
+        .map(|command| command.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for ((name, alias), command) in aliases.iter().zip(&commands) {
+        match &alias.description {
+            Some(description) => println!(
+                "{} - {:name_width$}  {:command_width$}  {}",
+                indent, name, command, description
+            ),
+            None => println!("{} - {:name_width$}  {}", indent, name, command),
+        }
+    }
+}
+
+// `git.st` and `docker/clean` are grouped under `git` and `docker`,
+// namespaces can nest with either separator
+#[derive(Default)]
+struct Group<'a> {
+    aliases: Vec<(&'a str, &'a Alias)>,
+    groups: BTreeMap<&'a str, Group<'a>>,
+}
+
+impl<'a> Group<'a> {
+    fn build(aliases: &[(&'a String, &'a Alias)]) -> Group<'a> {
+        let mut root = Group::default();
+
+        for (name, alias) in aliases {
+            let mut segments: Vec<&str> = name.split(['.', '/']).collect();
+
+            // leading, trailing or doubled separators don't make a namespace
+            if segments.iter().any(|segment| segment.is_empty()) {
+                segments = vec![name.as_str()];
+            }
+
+            let leaf = segments.pop().unwrap_or_default();
+            let group = segments.into_iter().fold(&mut root, |group, segment| {
+                group.groups.entry(segment).or_default()
+            });
+
+            group.aliases.push((leaf, alias));
+        }
+
+        root
+    }
+}
+
+fn print_tree(group: &Group, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let mut aliases = group.aliases.clone();
+
+    // `git.st` sorts before `git/log` by full name, so sort by leaf again
+    aliases.sort_by_key(|(name, _)| *name);
+
+    print_columns(&aliases, &indent);
+
+    for (name, child) in &group.groups {
+        println!("{} {}/", indent, name);
+        print_tree(child, depth + 1);
+    }
+}
+
+fn print_long(aliases: &[(&String, &Alias)]) {
+    let last_used = history::last_used();
+
+    for (i, (name, alias)) in aliases.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let mut details = details(alias);
+
+        details.extend(dates(alias, last_used.get(name.as_str())));
+        print_entry(name, &details);
+    }
+}
+
+// when the alias was created and last ran, for `list --long` and `show`
+fn dates(alias: &Alias, last_used: Option<&u64>) -> Vec<(&'static str, String)> {
+    let mut dates = Vec::new();
+
+    if let Some(created_at) = &alias.created_at {
+        dates.push(("created", created_at.clone()));
+    }
+
+    if let Some(last_used) = last_used {
+        dates.push(("last used", history::format_timestamp(*last_used)));
+    }
+
+    dates
+}
+
+fn print_entry(name: &str, details: &[(&str, String)]) {
+    println!("{}", name);
+
+    for (key, value) in details {
+        // the lines of a script stay aligned with the first one
+        let value = value.trim_end().replace('\n', &format!("\n{:15}", ""));
+
+        println!("  {:12} {}", format!("{}:", key), value);
+    }
+}
+
+// `pintas show`: the options of `list --long` and where the alias comes
+// from, with its runs in the current project if the history is kept
+pub fn show_alias(config: &Config, name: &str) -> Result<()> {
+    let name = config.aka_owner(name).unwrap_or(name);
+    let alias = config
+        .aliases
+        .get(name)
+        .ok_or_else(|| anyhow!("Alias '{}' not found.", name))?;
+    let mut details = details(alias);
+
+    if let Some(reason) = alias.unavailable_reason() {
+        details.push(("unavailable", reason));
+    }
+
+    details.push((
+        "defined in",
+        defining_path(name, alias)?.to_string_lossy().into_owned(),
+    ));
+
+    match shims::shim_path(config, name)? {
+        Some(path) if path.exists() => details.push(("shim", path.to_string_lossy().into_owned())),
+        Some(path) => details.push((
+            "shim",
+            format!("{} (missing, run 'pintas sync')", path.to_string_lossy()),
+        )),
+        None => details.push(("shim", "none".to_string())),
+    }
+
+    details.extend(dates(alias, history::last_used().get(name)));
+
+    // the runs of this project, `last used` counts every project
+    let runs = history::runs(name)?;
+
+    match (runs.first(), runs.last()) {
+        (Some((first, _)), Some((last, exit_code))) => {
+            details.push(("runs", runs.len().to_string()));
+            details.push(("first run", history::format_timestamp(*first)));
+            details.push((
+                "last run",
+                format!(
+                    "{} (exit code: {})",
+                    history::format_timestamp(*last),
+                    exit_code
+                ),
+            ));
+        }
+        _ if config.settings.history => details.push(("runs", "none yet".to_string())),
+        _ => details.push((
+            "runs",
+            "not recorded, set history = true in [settings]".to_string(),
+        )),
+    }
+
+    print_entry(name, &details);
+
+    Ok(())
+}
+
+pub fn details(alias: &Alias) -> Vec<(&'static str, String)> {
+    let mut details = Vec::new();
+
+    for (i, step) in alias.steps.iter().enumerate() {
+        details.push(("step", format!("{}. {}", i + 1, step)));
+    }
+
+    for (i, step) in alias.checklist.iter().enumerate() {
+        match &step.cmd {
+            Some(cmd) => details.push((
+                "check",
+                format!("{}. {} ($ {})", i + 1, step.description, cmd),
+            )),
+            None => details.push(("check", format!("{}. {}", i + 1, step.description))),
+        }
+    }
+
+    match &alias.cmd {
+        AliasCommand::Plain(cmd)
+            if cmd.is_empty()
+                && (!alias.steps.is_empty()
+                    || !alias.checklist.is_empty()
+                    || alias.script.is_some()) => {}
+        AliasCommand::Plain(cmd) => details.push(("command", cmd.clone())),
+        AliasCommand::Argv(argv) => details.push(("argv", expand::join_argv(argv))),
+        AliasCommand::PerOs(variants) => {
+            for (os, cmd) in [
+                ("linux", &variants.linux),
+                ("macos", &variants.macos),
+                ("windows", &variants.windows),
+                ("default", &variants.default),
+            ] {
+                if let Some(cmd) = cmd {
+                    details.push(("command", format!("[{}] {}", os, cmd)));
+                }
+            }
+        }
+    }
+
+    let optional = [
+        ("script", &alias.script),
+        ("description", &alias.description),
+        ("cwd", &alias.cwd),
+        ("shell", &alias.shell),
+        ("timeout", &alias.timeout),
+        ("proxy", &alias.proxy),
+        ("no_proxy", &alias.no_proxy),
+        ("retry_delay", &alias.retry_delay),
+        ("env_file", &alias.env_file),
+        ("locale", &alias.locale),
+        ("tz", &alias.tz),
+        ("only_under", &alias.only_under),
+        ("pre", &alias.pre),
+        ("post", &alias.post),
+        ("cleanup", &alias.cleanup),
+    ];
+
+    for (key, value) in optional {
+        if let Some(value) = value {
+            details.push((key, value.clone()));
+        }
+    }
+
+    if !alias.aka.is_empty() {
+        details.push(("aka", alias.aka.join(", ")));
+    }
+
+    if !alias.tags.is_empty() {
+        details.push(("tags", alias.tags.join(", ")));
+    }
+
+    if alias.session {
+        details.push(("session", "true".to_string()));
+    }
+
+    if alias.disabled {
+        details.push(("disabled", "true".to_string()));
+    }
+
+    if alias.protected {
+        details.push(("protected", "true".to_string()));
+    }
+
+    if !alias.requires.is_empty() {
+        details.push(("requires", alias.requires.join(", ")));
+    }
+
+    if !alias.hosts.is_empty() {
+        details.push(("hosts", alias.hosts.join(", ")));
+    }
+
+    if !alias.deps.is_empty() {
+        details.push(("deps", alias.deps.join(", ")));
+    }
+
+    if alias.keep_output {
+        details.push(("keep_output", "true".to_string()));
+    }
+
+    if alias.triage {
+        details.push(("triage", "true".to_string()));
+    }
+
+    if alias.log {
+        details.push(("log", "true".to_string()));
+    }
+
+    if let Some(log_file) = &alias.log_file {
+        details.push(("log_file", log_file.clone()));
+    }
+
+    if alias.notify {
+        details.push(("notify", "true".to_string()));
+    }
+
+    if alias.exclusive {
+        details.push(("exclusive", "true".to_string()));
+    }
+
+    if alias.busy == Busy::Wait {
+        details.push(("busy", "wait".to_string()));
+    }
+
+    if let Some(nice) = alias.nice {
+        details.push(("nice", nice.to_string()));
+    }
+
+    if let Some(ionice) = &alias.ionice {
+        details.push(("ionice", ionice.clone()));
+    }
+
+    if let Some(limits) = &alias.limits {
+        let limits: Vec<_> = [
+            limits
+                .memory
+                .as_ref()
+                .map(|memory| format!("memory {}", memory)),
+            limits
+                .cpu_time
+                .as_ref()
+                .map(|cpu_time| format!("cpu_time {}", cpu_time)),
+            limits
+                .open_files
+                .map(|open_files| format!("open_files {}", open_files)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        details.push(("limits", limits.join(", ")));
+    }
+
+    if alias.retries > 0 {
+        details.push(("retries", alias.retries.to_string()));
+    }
+
+    if alias.no_network {
+        details.push(("no_network", "true".to_string()));
+    }
+
+    if let Some(sandbox) = &alias.sandbox {
+        let mut parts = Vec::new();
+
+        if let Some(network) = sandbox.network {
+            parts.push(format!("network {}", network));
+        }
+
+        if !sandbox.readonly.is_empty() {
+            parts.push(format!("readonly {}", sandbox.readonly.join(" ")));
+        }
+
+        if !sandbox.writable.is_empty() {
+            parts.push(format!("writable {}", sandbox.writable.join(" ")));
+        }
+
+        details.push(("sandbox", parts.join(", ")));
+    }
+
+    if alias.backoff {
+        details.push(("backoff", "true".to_string()));
+    }
+
+    if let Some(budget) = &alias.budget {
+        details.push(("budget", format!("{} per day", budget.per_day)));
+    }
+
+    if alias.parallel {
+        details.push(("parallel", "true".to_string()));
+    }
+
+    if alias.fail_fast {
+        details.push(("fail_fast", "true".to_string()));
+    }
+
+    if alias.requires_tty {
+        details.push(("requires_tty", "true".to_string()));
+    }
+
+    if let Some(headless) = &alias.headless {
+        details.push(("headless", headless.clone()));
+    }
+
+    if alias.append_args {
+        details.push(("append_args", "true".to_string()));
+    }
+
+    if alias.safe_args {
+        details.push(("safe_args", "true".to_string()));
+    }
+
+    if !alias.params.is_empty() {
+        let flags: Vec<_> = alias
+            .params
+            .iter()
+            .map(|param| format!("--{}", param))
+            .collect();
+
+        details.push(("params", flags.join(", ")));
+    }
+
+    for arg in &alias.args {
+        if arg.choices.is_empty() {
+            details.push(("arg", arg.name.clone()));
+        } else {
+            details.push(("arg", format!("{} ({})", arg.name, arg.choices.join("|"))));
+        }
+    }
+
+    let mut subs: Vec<_> = alias.sub.iter().collect();
+
+    subs.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (name, sub) in subs {
+        details.push(("sub", format!("{} = {}", name, command_summary(sub))));
+    }
+
+    let mut pins: Vec<_> = alias.pin.iter().collect();
+
+    pins.sort();
+
+    for (program, version) in pins {
+        details.push(("pin", format!("{} {}", program, version)));
+    }
+
+    if alias.pin_mismatch == PinMismatch::Warn {
+        details.push(("pin_mismatch", "warn".to_string()));
+    }
+
+    if alias.clean_env {
+        details.push(("clean_env", "true".to_string()));
+    }
+
+    if !alias.keep_env.is_empty() {
+        details.push(("keep_env", alias.keep_env.join(", ")));
+    }
+
+    let mut env: Vec<_> = alias.env.iter().collect();
+
+    env.sort();
+
+    for (key, value) in env {
+        details.push(("env", format!("{}={}", key, value)));
+    }
+
+    details
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+use std::process;
+
+use crate::config::{Alias, Busy, get_pintas_dir};
+use crate::shims::shim_name;
+use crate::sys;
+
+fn get_locks_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("locks"))
+}
+
+// `~/.pintas/locks/<alias>.lock`, with the pid of the run that holds it,
+// the lock is held until the returned file is closed when the run ends or
+// pintas dies
+pub fn acquire(name: &str, alias: &Alias) -> Result<Option<File>> {
+    if !alias.exclusive {
+        return Ok(None);
+    }
+
+    let dir = get_locks_dir()?;
+
+    fs::create_dir_all(&dir).context("Failed to create lock directory")?;
+
+    let path = dir.join(format!("{}.lock", shim_name(name)));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock '{}'", path.to_string_lossy()))?;
+
+    if !sys::lock_file(&file, false).context("Failed to lock alias")? {
+        let mut holder = String::new();
+
+        let _ = file.read_to_string(&mut holder);
+
+        let holder = match holder.trim() {
+            "" => String::new(),
+            pid => format!(" (pid {})", pid),
+        };
+
+        if alias.busy == Busy::Fail {
+            return Err(anyhow!(
+                "Alias '{}' is already running{}, it's exclusive. Wait for that run to finish, or set busy = \"wait\" to wait for it.",
+                name,
+                holder
+            ));
+        }
+
+        eprintln!(
+            "Alias '{}' is already running{}, waiting for it to finish...",
+            name, holder
+        );
+
+        sys::lock_file(&file, true).context("Failed to lock alias")?;
+    }
+
+    // the pid is only for the message above, a stale one does no harm
+    let _ = file
+        .set_len(0)
+        .and_then(|_| file.rewind())
+        .and_then(|_| writeln!(file, "{}", process::id()));
+
+    Ok(Some(file))
+}
@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{Alias, expand_tilde, get_pintas_dir};
+use crate::expand;
+use crate::history;
+use crate::shims::shim_name;
+use crate::stats;
+use crate::sys;
+
+const LOG_FILENAME: &str = "run.log";
+
+// a log past this size is rotated before the next run, the older ones are
+// kept as `.1` to `.3`
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+const KEPT_LOGS: u32 = 3;
+
+// the output of one run of an alias with `log` or `log_file`, appended
+// between a header and the exit code, or of a `run --tee`, steps write to
+// it from their own threads
+pub struct RunLog(Mutex<File>);
+
+pub fn get_logs_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("logs"))
+}
+
+// `log_file` if set, else `~/.pintas/logs/<alias>/run.log`
+pub fn log_path(name: &str, alias: &Alias) -> Result<Option<PathBuf>> {
+    if let Some(file) = &alias.log_file {
+        return Ok(Some(expand_tilde(file)));
+    }
+
+    if !alias.log {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        get_logs_dir()?.join(shim_name(name)).join(LOG_FILENAME),
+    ))
+}
+
+impl RunLog {
+    // `run --tee`: just the output, in a file started afresh
+    pub fn create(path: &Path) -> Result<RunLog> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create '{}'", path.to_string_lossy()))?;
+
+        Ok(RunLog(Mutex::new(file)))
+    }
+
+    pub fn open(name: &str, alias: &Alias, args: &[String]) -> Result<Option<RunLog>> {
+        let Some(path) = log_path(name, alias)? else {
+            return Ok(None);
+        };
+
+        fs::create_dir_all(path.parent().unwrap_or(&path))
+            .context("Failed to create log directory")?;
+        rotate(&path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open log '{}'", path.to_string_lossy()))?;
+        let log = RunLog(Mutex::new(file));
+        let mut invocation = name.to_string();
+
+        if !args.is_empty() {
+            invocation.push(' ');
+            invocation.push_str(&expand::join_argv(args));
+        }
+
+        log.write(
+            format!(
+                "=== {} pintas run {} ===\n",
+                history::format_timestamp(history::now()),
+                invocation
+            )
+            .as_bytes(),
+        );
+
+        Ok(Some(log))
+    }
+
+    // a log that can't be written never fails the run itself
+    pub fn write(&self, output: &[u8]) {
+        if let Ok(mut file) = self.0.lock() {
+            let _ = file.write_all(output);
+        }
+    }
+
+    pub fn finish(&self, status: &Result<ExitStatus>, duration: Duration) {
+        let outcome = match status {
+            Ok(status) => format!("exit code {}", sys::exit_code(status)),
+            Err(e) => format!("failed: {}", e),
+        };
+
+        self.write(
+            format!(
+                "=== {} after {} ===\n",
+                outcome,
+                stats::format_duration(duration.as_millis())
+            )
+            .as_bytes(),
+        );
+    }
+}
+
+fn rotate(path: &Path) -> Result<()> {
+    if fs::metadata(path).map_or(true, |meta| meta.len() < MAX_LOG_SIZE) {
+        return Ok(());
+    }
+
+    let numbered = |n: u32| PathBuf::from(format!("{}.{}", path.to_string_lossy(), n));
+
+    for n in (1..KEPT_LOGS).rev() {
+        if numbered(n).exists() {
+            fs::rename(numbered(n), numbered(n + 1)).context("Failed to rotate log")?;
+        }
+    }
+
+    fs::rename(path, numbered(1)).context("Failed to rotate log")
+}
@@ -1,134 +1,436 @@
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use std::env;
 use std::fs;
-use std::process::{Command as OsCommand, exit};
-
-use std::path::PathBuf;
-
-const CONFIG_FILENAME: &str = "pintas.toml";
-
-fn get_pintas_dir() -> Result<PathBuf> {
-    let home = env::var("HOME").context("Failed to get HOME directory from environment")?;
-
-    Ok(PathBuf::from(home).join(".pintas"))
-}
-
-fn get_shims_dir() -> Result<PathBuf> {
-    Ok(get_pintas_dir()?.join("shims"))
-}
-
-fn sync_shims(config: &Config) -> Result<()> {
-    let pintas_path = env::current_exe().context("Failed to get current executable path")?;
-    let shims_dir = get_shims_dir()?;
-
-    fs::create_dir_all(&shims_dir).context("Failed to create shims directory")?;
-
-    // a simple approach to remove all shims before recreating them
-    // less efficient than comparing, but simpler and more robust
-    for entry in fs::read_dir(&shims_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_file() {
-            fs::remove_file(path)?;
-        }
-    }
-
-    for alias in config.aliases.keys() {
-        let shim_path = shims_dir.join(alias);
-        let shim_content = format!(
-            "#!/bin/sh\nexec \"{}\" run --internal \"{}\" \"$@\"",
-            pintas_path.to_string_lossy(),
-            alias
-        );
-
-        fs::write(&shim_path, shim_content)?;
-
-        use std::os::unix::fs::PermissionsExt;
-
-        fs::set_permissions(&shim_path, fs::Permissions::from_mode(0o755))?;
-    }
-
-    // println!("Successfully synced aliases.");
-
-    Ok(())
-}
+use std::process::exit;
+
+mod budget;
+mod chain;
+mod checklist;
+mod compat;
+#[cfg(feature = "completions")]
+mod completions;
+mod config;
+mod digest;
+mod doctor;
+mod dotenv;
+mod expand;
+mod export;
+mod grep;
+mod history;
+mod hooks;
+mod import;
+mod index;
+mod jobs;
+mod json;
+mod limits;
+#[cfg(feature = "lint")]
+mod lint;
+mod list;
+mod lock;
+mod log;
+mod notify;
+mod output;
+mod parallel;
+mod pin;
+mod policy;
+mod priority;
+mod record;
+mod regex;
+mod run;
+mod sandbox;
+mod scripts;
+mod session;
+mod sha256;
+mod shims;
+mod stats;
+mod sys;
+mod timeout;
+mod trash;
+mod triage;
+mod verify;
+mod version;
+
+use config::{Alias, Config, load_config, save_config};
+use digest::DigestArgs;
+use export::ExportArgs;
+use grep::GrepArgs;
+use history::{HistoryArgs, HistoryCommand};
+use import::ImportArgs;
+use list::{ListArgs, list_aliases};
+use run::{RunArgs, run_alias};
+use shims::{get_shims_dir, prune_shims, sync_shims};
+use stats::TopArgs;
+use trash::TrashCommand;
 
 #[derive(Parser)]
 #[command(name = "pintas")]
 #[command(about = "A lightning-fast command alias manager", long_about = None)]
 struct Cli {
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    #[arg(short = 'V', long)]
+    version: bool,
+    #[arg(long, requires = "version")]
+    build_info: bool,
+    #[arg(long, requires = "version")]
+    json: bool,
 }
 
 #[derive(Subcommand, Clone)]
 enum Commands {
-    Run {
+    Run(RunArgs),
+    Init {
         #[arg(required = true)]
-        alias: String,
-        #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
-        args: Vec<String>,
-        #[arg(long, hide = true)]
-        internal: bool,
+        shell: String,
     },
-    Init {
+    List(ListArgs),
+    #[cfg(feature = "completions")]
+    Completions {
         #[arg(required = true)]
         shell: String,
     },
-    List,
     Add {
         #[arg(required = true)]
         alias: String,
         #[arg(required = true)]
         command: String,
+        #[command(flatten)]
+        meta: AliasMetaArgs,
+        // only for the current shell session
+        #[arg(long)]
+        session: bool,
     },
     Edit {
         #[arg(required = true)]
         alias: String,
         #[arg(required = true)]
         command: String,
+        #[command(flatten)]
+        meta: AliasMetaArgs,
+        #[arg(long)]
+        force: bool,
+    },
+    New {
+        #[arg(required = true)]
+        alias: String,
+        #[arg(long, required = true)]
+        script: bool,
+        #[command(flatten)]
+        meta: AliasMetaArgs,
+    },
+    Copy {
+        #[arg(required = true)]
+        source: String,
+        #[arg(required = true)]
+        destination: String,
+    },
+    Rename {
+        #[arg(required = true)]
+        old: String,
+        #[arg(required = true)]
+        new: String,
+        #[arg(long)]
+        force: bool,
     },
     Remove {
         #[arg(required = true)]
         alias: String,
+        #[arg(long)]
+        force: bool,
+    },
+    Disable {
+        #[arg(required = true)]
+        alias: String,
+    },
+    Enable {
+        #[arg(required = true)]
+        alias: String,
+    },
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    Import(ImportArgs),
+    Export(ExportArgs),
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        #[command(flatten)]
+        filter: HistoryArgs,
+    },
+    Stats,
+    Top(TopArgs),
+    Digest(DigestArgs),
+    Show {
+        #[arg(required = true)]
+        alias: String,
+    },
+    Grep(GrepArgs),
+    Which {
+        #[arg(required = true)]
+        alias: String,
+        #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    Expand {
+        #[arg(required = true)]
+        alias: String,
+        #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    Jobs,
+    Logs {
+        #[arg(required = true)]
+        job: String,
+        #[arg(short = 'f', long)]
+        follow: bool,
+    },
+    Kill {
+        #[arg(required = true)]
+        job: String,
+        #[arg(long)]
+        force: bool,
+    },
+    Sync {
+        #[arg(long)]
+        force: bool,
+    },
+    Prune,
+    Doctor,
+    Verify {
+        #[arg(long)]
+        hashes: bool,
+        #[arg(long, conflicts_with = "hashes")]
+        scripts: bool,
+    },
+    #[cfg(feature = "lint")]
+    Lint {
+        #[arg(long)]
+        policy: Option<String>,
     },
-    Sync,
 }
 
-#[derive(Deserialize, Serialize, Default, Clone)]
-struct Config {
-    aliases: HashMap<String, String>,
+#[derive(Args, Clone)]
+struct AliasMetaArgs {
+    #[arg(long)]
+    description: Option<String>,
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    append_args: Option<bool>,
+}
+
+impl AliasMetaArgs {
+    fn apply(&self, alias: &mut Alias) {
+        if let Some(description) = &self.description {
+            alias.description = Some(description.clone());
+        }
+
+        if !self.tags.is_empty() {
+            alias.tags = self.tags.clone();
+        }
+
+        if let Some(append_args) = self.append_args {
+            alias.append_args = append_args;
+        }
+    }
 }
 
 fn main() -> Result<()> {
+    if let Some(steps) = custom_command() {
+        for command in steps {
+            if let Err(e) = run_command(command) {
+                eprintln!("Error: {:?}", e);
+
+                exit(1);
+            }
+        }
+
+        return Ok(());
+    }
+
     let cli = Cli::parse();
 
-    if let Err(e) = run_command(cli.command) {
+    if cli.version {
+        version::print_version(cli.build_info, cli.json);
+
+        return Ok(());
+    }
+
+    let command = match cli.command {
+        Some(command) => command,
+        None => default_command(),
+    };
+
+    // a run's stderr belongs to the alias, the digest waits for another
+    // command
+    // `expand` output goes to eval, where the digest would only get in the way
+    let scheduled = !matches!(
+        command,
+        Commands::Run(_) | Commands::Digest(_) | Commands::Expand { .. }
+    );
+
+    if let Err(e) = run_command(command) {
         eprintln!("Error: {:?}", e);
 
         exit(1);
     }
 
+    if scheduled {
+        digest::show_scheduled();
+    }
+
     Ok(())
 }
 
+// bare `pintas` runs `[settings] default_command` if one is configured,
+// otherwise it fails the same way clap does for a missing subcommand
+fn default_command() -> Commands {
+    let configured = load_config()
+        .ok()
+        .and_then(|config| config.settings.default_command);
+
+    let Some(default_command) = configured else {
+        Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "'pintas' requires a subcommand but one was not provided",
+            )
+            .exit();
+    };
+
+    let args = ["pintas"]
+        .into_iter()
+        .chain(default_command.split_whitespace());
+
+    match Cli::try_parse_from(args) {
+        Ok(Cli {
+            command: Some(command),
+            ..
+        }) => command,
+        Ok(_) => Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "[settings] default_command is empty",
+            )
+            .exit(),
+        Err(e) => {
+            eprintln!(
+                "Error: Invalid [settings] default_command '{}'.\n\n{}",
+                default_command,
+                e.render().to_string().trim_end()
+            );
+
+            exit(2);
+        }
+    }
+}
+
+// `pintas refresh` with `[commands] refresh = ["prune", "sync"]` in the
+// config, a built-in command of the same name always wins, all steps are
+// parsed before the first one runs
+fn custom_command() -> Option<Vec<Commands>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let name = args.first()?;
+
+    if name.starts_with('-') || Cli::command().find_subcommand(name).is_some() {
+        return None;
+    }
+
+    let steps = load_config().ok()?.commands.remove(name)?;
+
+    if args.len() > 1 {
+        eprintln!(
+            "Error: Command '{}' from [commands] doesn't take arguments.",
+            name
+        );
+
+        exit(2);
+    }
+
+    let commands = steps
+        .iter()
+        .map(|step| {
+            let args = ["pintas"].into_iter().chain(step.split_whitespace());
+
+            match Cli::try_parse_from(args) {
+                Ok(Cli {
+                    command: Some(command),
+                    ..
+                }) => command,
+                Ok(_) => {
+                    eprintln!("Error: Command '{}' has an empty step in [commands].", name);
+
+                    exit(2);
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Error: Invalid step '{}' of command '{}' in [commands].\n\n{}",
+                        step,
+                        name,
+                        e.render().to_string().trim_end()
+                    );
+
+                    exit(2);
+                }
+            }
+        })
+        .collect();
+
+    Some(commands)
+}
+
 fn run_command(command: Commands) -> Result<()> {
     match command {
-        Commands::Run {
-            alias,
-            args,
-            internal,
-        } => run_alias(alias, args, internal),
+        Commands::Run(run) => run_alias(run),
         Commands::Init { shell } => init_shell(&shell),
-        Commands::List => run_readonly_command(command),
-        Commands::Sync => sync_shims(&load_config()?),
-        Commands::Add { .. } | Commands::Edit { .. } | Commands::Remove { .. } => {
-            run_mutating_command(command)
+        Commands::List(_)
+        | Commands::Export(_)
+        | Commands::Stats
+        | Commands::Top(_)
+        | Commands::Digest(_)
+        | Commands::Show { .. }
+        | Commands::Grep(_)
+        | Commands::Which { .. }
+        | Commands::Expand { .. } => run_readonly_command(command),
+        #[cfg(feature = "completions")]
+        Commands::Completions { shell } => completions::print_completions(&load_config()?, &shell),
+        Commands::Sync { force } => {
+            let config = load_config()?;
+
+            index::write_index(&config)?;
+            scripts::sync_scripts(&config)?;
+            sync_shims(&config, force)?;
+            digest::record_changes(&config)
         }
+        Commands::Prune => {
+            let config = load_config()?;
+
+            prune_shims(&config)?;
+            scripts::prune_scripts(&config)
+        }
+        Commands::History { command, filter } => history::run_history(command.as_ref(), &filter),
+        Commands::Jobs => jobs::run_jobs(),
+        Commands::Logs { job, follow } => jobs::run_logs(&job, follow),
+        Commands::Kill { job, force } => jobs::run_kill(&job, force),
+        Commands::Doctor => doctor::run_doctor(),
+        Commands::Verify { scripts: true, .. } => compat::run_compat(&load_config()?),
+        Commands::Verify { hashes, .. } => verify::run_verify(&load_config()?, hashes),
+        #[cfg(feature = "lint")]
+        Commands::Lint { policy } => lint::run_lint(&load_config()?, policy.as_deref()),
+        Commands::Add { .. }
+        | Commands::New { .. }
+        | Commands::Edit { .. }
+        | Commands::Copy { .. }
+        | Commands::Rename { .. }
+        | Commands::Remove { .. }
+        | Commands::Disable { .. }
+        | Commands::Enable { .. }
+        | Commands::Trash { .. }
+        | Commands::Import(_) => run_mutating_command(command),
     }
 }
 
@@ -136,155 +438,342 @@ fn run_readonly_command(command: Commands) -> Result<()> {
     let config = load_config()?;
 
     match command {
-        Commands::List => list_aliases(&config),
+        Commands::List(list) => list_aliases(&config, &list),
+        Commands::Export(export) => export::run_export(&config, &export),
+        Commands::Stats => stats::run_stats(&config),
+        Commands::Top(top) => stats::run_top(&config, &top),
+        Commands::Digest(digest) => digest::run_digest(&config, &digest),
+        Commands::Show { alias } => list::show_alias(&config, &alias),
+        Commands::Grep(grep) => grep::run_grep(&config, &grep),
+        Commands::Which { alias, args } => run::run_which(&config, &alias, &args),
+        Commands::Expand { alias, args } => run::run_expand(&config, &alias, &args),
         _ => unreachable!(),
     }
 }
 
 fn run_mutating_command(command: Commands) -> Result<()> {
-    let mut config = if let Commands::Add { .. } = command {
-        load_config().unwrap_or_default()
-    } else {
-        load_config()?
-    };
+    let mut config =
+        if let Commands::Add { .. } | Commands::New { .. } | Commands::Import(_) = command {
+            load_config().or_else(|_| session::load_layer(Config::default()))?
+        } else {
+            load_config()?
+        };
 
     match command {
-        Commands::Add { alias, command } => add_alias(&mut config, &alias, &command)?,
-        Commands::Edit { alias, command } => edit_alias(&mut config, &alias, &command)?,
-        Commands::Remove { alias } => remove_alias(&mut config, &alias)?,
+        Commands::Add {
+            alias,
+            command,
+            meta,
+            session,
+        } => add_alias(&mut config, &alias, &command, &meta, session)?,
+        Commands::New { alias, meta, .. } => new_script_alias(&mut config, &alias, &meta)?,
+        Commands::Edit {
+            alias,
+            command,
+            meta,
+            force,
+        } => edit_alias(&mut config, &alias, &command, &meta, force)?,
+        Commands::Copy {
+            source,
+            destination,
+        } => copy_alias(&mut config, &source, &destination)?,
+        Commands::Rename { old, new, force } => rename_alias(&mut config, &old, &new, force)?,
+        Commands::Remove { alias, force } => remove_alias(&mut config, &alias, force)?,
+        Commands::Disable { alias } => set_disabled(&mut config, &alias, true)?,
+        Commands::Enable { alias } => set_disabled(&mut config, &alias, false)?,
+        Commands::Trash { command } => trash::run_trash(&mut config, &command)?,
+        Commands::Import(import) => import_aliases(&mut config, &import)?,
         _ => unreachable!(),
     }
 
     save_config(&config)?;
-    sync_shims(&config)
+    sync_shims(&config, false)
 }
 
 fn init_shell(shell: &str) -> Result<()> {
-    let shims_dir = get_shims_dir()?;
+    let script = shims::init_script(shell)?;
 
-    fs::create_dir_all(&shims_dir).context("Failed to create shims directory")?;
+    fs::create_dir_all(get_shims_dir()?).context("Failed to create shims directory")?;
 
-    match shell {
-        "bash" => {
-            println!(
-                "# pintas shell integration for bash\n#\n# Add the following line to your ~/.bashrc or ~/.profile:\n#\n  export PATH=\"{}\":$PATH\n",
-                shims_dir.to_string_lossy()
-            );
+    println!("{}", script);
 
-            Ok(())
-        }
+    Ok(())
+}
 
-        _ => Err(anyhow!("Shell '{}' not supported.", shell)),
+fn add_alias(
+    config: &mut Config,
+    alias: &str,
+    command: &str,
+    meta: &AliasMetaArgs,
+    session: bool,
+) -> Result<()> {
+    if config.aliases.contains_key(alias) {
+        return Err(anyhow!(
+            "Alias '{}' already exists. Use 'edit' to modify it.",
+            alias
+        ));
     }
-}
 
-fn load_config() -> Result<Config> {
-    let content = fs::read_to_string(CONFIG_FILENAME)
-        .with_context(|| format!("Configuration file '{}' not found.", CONFIG_FILENAME))?;
+    if session {
+        session::require_current()?;
+    }
 
-    toml::from_str(&content).with_context(|| format!("Failed to parse '{}'.", CONFIG_FILENAME))
-}
+    let mut alias_def = Alias {
+        session,
+        created_at: Some(history::format_timestamp(history::now())),
+        ..Alias::new(command)
+    };
 
-fn save_config(config: &Config) -> Result<()> {
-    let toml_string = toml::to_string(config).context("Failed to serialize configuration.")?;
+    meta.apply(&mut alias_def);
+    enforce_lint_policy(config, alias, &alias_def)?;
+    shims::check_shim_name(config, alias)?;
 
-    fs::write(CONFIG_FILENAME, toml_string)
-        .with_context(|| format!("Failed to write to '{}'.", CONFIG_FILENAME))?;
+    config.aliases.insert(alias.to_string(), alias_def);
+    chain::check_cycles(config, alias)?;
+
+    println!("Successfully added alias '{}'.", alias);
 
     Ok(())
 }
 
-fn list_aliases(config: &Config) -> Result<()> {
-    println!("Available aliases:");
+// `new --script` is the only kind of `new` so far, the flag leaves room for
+// others
+fn new_script_alias(config: &mut Config, alias: &str, meta: &AliasMetaArgs) -> Result<()> {
+    if config.aliases.contains_key(alias) {
+        return Err(anyhow!(
+            "Alias '{}' already exists. Use 'edit' to modify it.",
+            alias
+        ));
+    }
+
+    let file = shims::shim_name(alias);
+    let mut alias_def = Alias {
+        script: Some(file.clone()),
+        created_at: Some(history::format_timestamp(history::now())),
+        ..Default::default()
+    };
 
-    if config.aliases.is_empty() {
-        println!("No aliases found.");
-    } else {
-        let mut sorted_aliases: Vec<_> = config.aliases.iter().collect();
+    meta.apply(&mut alias_def);
+    enforce_lint_policy(config, alias, &alias_def)?;
+    shims::check_shim_name(config, alias)?;
 
-        sorted_aliases.sort_by(|a, b| a.0.cmp(b.0));
+    let path = scripts::create_script(&file)?;
 
-        for (alias, command) in sorted_aliases {
-            println!(" - {}: \"{}\"", alias, command);
-        }
+    if sys::is_interactive() {
+        scripts::open_editor(&path)?;
+    } else {
+        println!("Edit '{}' to write the script.", path.to_string_lossy());
     }
 
+    config.aliases.insert(alias.to_string(), alias_def);
+
+    println!(
+        "Successfully added alias '{}' with script '{}'.",
+        alias,
+        path.to_string_lossy()
+    );
+
     Ok(())
 }
 
-fn run_alias(alias: String, args: Vec<String>, internal: bool) -> Result<()> {
-    let config = match load_config() {
-        Ok(cfg) => cfg,
-        Err(_) if internal => exit(126), // config not found, so alias can't exist
-        Err(e) => return Err(e).context("Failed to load pintas config"),
-    };
+fn edit_alias(
+    config: &mut Config,
+    alias: &str,
+    new_command: &str,
+    meta: &AliasMetaArgs,
+    force: bool,
+) -> Result<()> {
+    if let Some(mut alias_def) = config.aliases.get(alias).cloned() {
+        check_protected(alias, &alias_def, "edit", force)?;
+
+        if let Some(script) = &alias_def.script {
+            return Err(anyhow!(
+                "Alias '{}' runs script '{}', edit the script file instead.",
+                alias,
+                script
+            ));
+        }
 
-    let command_to_run = match config.aliases.get(&alias) {
-        Some(cmd) => cmd,
-        None if internal => exit(126), // alias not found
-        None => return Err(anyhow!("Alias '{}' not found.", alias)),
-    };
+        alias_def.cmd.set_for_current_os(new_command);
+        alias_def.steps.clear();
+        meta.apply(&mut alias_def);
+        enforce_lint_policy(config, alias, &alias_def)?;
 
-    if !internal {
-        println!("Executing command: '{}'", command_to_run);
-    }
+        config.aliases.insert(alias.to_string(), alias_def);
+        chain::check_cycles(config, alias)?;
 
-    let mut cmd = OsCommand::new("sh");
+        println!("Successfully edited alias '{}'.", alias);
 
-    cmd.arg("-c");
-    cmd.arg(command_to_run);
-    cmd.arg(alias); // this becomes $0 in the script
-    cmd.args(args); // these become $1, $2, ...
+        Ok(())
+    } else {
+        Err(anyhow!("Alias '{}' not found. Cannot edit.", alias))
+    }
+}
+
+// a disabled alias keeps its entry but loses its shim and doesn't resolve,
+// so the program it shadows runs instead
+fn set_disabled(config: &mut Config, alias: &str, disabled: bool) -> Result<()> {
+    let alias_def = config
+        .aliases
+        .get_mut(alias)
+        .ok_or_else(|| anyhow!("Alias '{}' not found.", alias))?;
+    let action = if disabled { "disabled" } else { "enabled" };
 
-    let status = cmd.status().context("Failed to execute command")?;
+    if alias_def.disabled == disabled {
+        println!("Alias '{}' is already {}.", alias, action);
 
-    if internal {
-        exit(status.code().unwrap_or(1));
+        return Ok(());
     }
 
-    if !status.success() {
+    alias_def.disabled = disabled;
+
+    println!("Successfully {} alias '{}'.", action, alias);
+
+    Ok(())
+}
+
+// `protected = true` guards an alias in a shared config against a careless
+// edit, `--force` still goes ahead
+fn check_protected(alias: &str, alias_def: &Alias, action: &str, force: bool) -> Result<()> {
+    if alias_def.protected && !force {
         return Err(anyhow!(
-            "Command finished with an error (exit code: {})\n",
-            status
+            "Alias '{}' is protected. Use '{} --force' to {} it anyway.",
+            alias,
+            action,
+            action
         ));
     }
 
     Ok(())
 }
 
-fn add_alias(config: &mut Config, alias: &str, command: &str) -> Result<()> {
-    if config.aliases.contains_key(alias) {
+// a build without the lint feature refuses changes to a config that asks
+// for a policy instead of silently skipping it
+fn enforce_lint_policy(config: &Config, alias: &str, alias_def: &Alias) -> Result<()> {
+    #[cfg(feature = "lint")]
+    return lint::enforce(config, alias, alias_def);
+
+    #[cfg(not(feature = "lint"))]
+    match &config.settings.lint_policy {
+        Some(path) => Err(anyhow!(
+            "Alias '{}' can't be checked against lint policy '{}': this build has no lint support.",
+            alias,
+            path
+        )),
+        None => {
+            let _ = alias_def;
+
+            Ok(())
+        }
+    }
+}
+
+fn import_aliases(config: &mut Config, import: &ImportArgs) -> Result<()> {
+    let imported = import::import_aliases(config, import)?;
+
+    for alias in &imported {
+        enforce_lint_policy(config, alias, &config.aliases[alias])?;
+        shims::check_shim_name(config, alias)?;
+    }
+
+    if imported.is_empty() {
+        println!("No new aliases in '{}'.", import.file);
+    } else {
+        println!(
+            "Successfully imported aliases from '{}': {}",
+            import.file,
+            imported.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+// synonyms stay with the original, a name can only stand for one alias
+fn copy_alias(config: &mut Config, source: &str, destination: &str) -> Result<()> {
+    let alias_def = config
+        .aliases
+        .get(source)
+        .ok_or_else(|| anyhow!("Alias '{}' not found.", source))?;
+
+    if config.aliases.contains_key(destination) {
         return Err(anyhow!(
             "Alias '{}' already exists. Use 'edit' to modify it.",
-            alias
+            destination
         ));
     }
 
-    config
-        .aliases
-        .insert(alias.to_string(), command.to_string());
+    let alias_def = Alias {
+        aka: Vec::new(),
+        created_at: Some(history::format_timestamp(history::now())),
+        ..alias_def.clone()
+    };
 
-    println!("Successfully added alias '{}'.", alias);
+    enforce_lint_policy(config, destination, &alias_def)?;
+
+    config.aliases.insert(destination.to_string(), alias_def);
+    shims::check_shim_name(config, destination)?;
+    chain::check_cycles(config, destination)?;
+
+    println!(
+        "Successfully copied alias '{}' to '{}'.",
+        source, destination
+    );
 
     Ok(())
 }
 
-fn edit_alias(config: &mut Config, alias: &str, new_command: &str) -> Result<()> {
-    if config.aliases.contains_key(alias) {
-        config
-            .aliases
-            .insert(alias.to_string(), new_command.to_string());
+// the runs recorded under the old name move along, aliases that refer to it
+// are only pointed out
+fn rename_alias(config: &mut Config, old: &str, new: &str, force: bool) -> Result<()> {
+    let alias_def = config
+        .aliases
+        .get(old)
+        .ok_or_else(|| anyhow!("Alias '{}' not found.", old))?
+        .clone();
 
-        println!("Successfully edited alias '{}'.", alias);
+    check_protected(old, &alias_def, "rename", force)?;
 
-        Ok(())
-    } else {
-        Err(anyhow!("Alias '{}' not found. Cannot edit.", alias))
+    if config.aliases.contains_key(new) {
+        return Err(anyhow!(
+            "Alias '{}' already exists. Use 'edit' to modify it.",
+            new
+        ));
+    }
+
+    enforce_lint_policy(config, new, &alias_def)?;
+
+    config.aliases.remove(old);
+    config.aliases.insert(new.to_string(), alias_def);
+    shims::check_shim_name(config, new)?;
+    chain::check_cycles(config, new)?;
+
+    // the logs only follow once the new name is saved, a failed save leaves
+    // them with the alias still under its old name
+    save_config(config)?;
+
+    history::rename(old, new)?;
+    budget::rename(old, new)?;
+    checklist::rename_log(old, new)?;
+
+    for referrer in chain::referrers(config, old) {
+        eprintln!("Warning: alias '{}' still refers to '{}'.", referrer, old);
     }
+
+    println!("Successfully renamed alias '{}' to '{}'.", old, new);
+
+    Ok(())
 }
 
-fn remove_alias(config: &mut Config, alias: &str) -> Result<()> {
-    if config.aliases.remove(alias).is_some() {
+fn remove_alias(config: &mut Config, alias: &str, force: bool) -> Result<()> {
+    if let Some(alias_def) = config.aliases.remove(alias) {
+        check_protected(alias, &alias_def, "remove", force)?;
+
+        // a session alias was never meant to last
+        if !alias_def.session {
+            trash::discard(config, alias, alias_def)?;
+        }
+
         println!("Successfully removed alias '{}'.", alias);
 
         Ok(())
@@ -0,0 +1,51 @@
+use std::process::{Command as OsCommand, ExitStatus, Stdio};
+use std::time::Duration;
+
+use crate::stats;
+use crate::sys;
+
+// `notify = true` or `run --notify`: a desktop notification once the run
+// is over, through osascript on macOS and notify-send elsewhere, a desktop
+// without either just doesn't get one
+pub fn send(name: &str, duration: Duration, status: &ExitStatus) {
+    let title = if status.success() {
+        format!("pintas: {} finished", name)
+    } else {
+        format!("pintas: {} failed", name)
+    };
+    let body = format!(
+        "Exit code {} after {}",
+        sys::exit_code(status),
+        stats::format_duration(duration.as_millis())
+    );
+
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = OsCommand::new("osascript");
+
+        // the texts are passed as arguments, not spliced into the script
+        cmd.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            &title,
+            &body,
+        ]);
+        cmd
+    } else if sys::find_program("notify-send").is_some() {
+        let mut cmd = OsCommand::new("notify-send");
+
+        cmd.args(["--app-name", "pintas", &title, &body]);
+        cmd
+    } else {
+        return;
+    };
+
+    let _ = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command as OsCommand, ExitStatus, Stdio};
+use std::thread;
+
+use crate::config::get_pintas_dir;
+use crate::log::RunLog;
+use crate::timeout::{self, Deadline};
+
+const OUTPUT_FILENAME: &str = "last_output";
+
+// more than this is still shown but not kept
+const MAX_KEPT_OUTPUT: usize = 1024 * 1024;
+
+pub fn get_output_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(OUTPUT_FILENAME))
+}
+
+// runs the command with its stdout copied to ours (or stderr) as it comes
+// and returns what it printed, with logs its stderr is copied as well and
+// both go to the logs too
+pub fn tee(
+    cmd: &mut OsCommand,
+    to_stderr: bool,
+    deadline: Option<&Deadline>,
+    logs: &[&RunLog],
+) -> Result<(ExitStatus, Vec<u8>)> {
+    if !logs.is_empty() {
+        cmd.stderr(Stdio::piped());
+    }
+
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to execute command")?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    thread::scope(|scope| {
+        let copied = scope.spawn(move || copy(stdout, to_stderr, logs));
+        let copied_stderr = scope.spawn(move || copy(stderr, true, logs));
+        let status = timeout::wait(&mut child, deadline)?;
+        let kept = copied
+            .join()
+            .map_err(|_| anyhow!("Failed to read command output"))??;
+
+        copied_stderr
+            .join()
+            .map_err(|_| anyhow!("Failed to read command output"))??;
+
+        Ok((status, kept))
+    })
+}
+
+fn copy(stream: Option<impl Read>, to_stderr: bool, logs: &[&RunLog]) -> Result<Vec<u8>> {
+    let mut kept = Vec::new();
+
+    if let Some(mut stream) = stream {
+        let mut buffer = [0; 8192];
+
+        loop {
+            let len = match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).context("Failed to read command output"),
+            };
+
+            let chunk = &buffer[..len];
+
+            for log in logs {
+                log.write(chunk);
+            }
+
+            if to_stderr {
+                let _ = io::stderr().write_all(chunk);
+            } else {
+                let mut out = io::stdout().lock();
+
+                let _ = out.write_all(chunk).and_then(|_| out.flush());
+            }
+
+            let room = MAX_KEPT_OUTPUT.saturating_sub(kept.len());
+
+            kept.extend_from_slice(&chunk[..len.min(room)]);
+        }
+    }
+
+    Ok(kept)
+}
+
+pub fn save(output: &[u8]) -> Result<()> {
+    let path = get_output_path()?;
+
+    fs::create_dir_all(path.parent().unwrap_or(&path))
+        .context("Failed to create pintas directory")?;
+    fs::write(&path, &output[..output.len().min(MAX_KEPT_OUTPUT)])
+        .context("Failed to keep command output")
+}
+
+// `{last_output}` is the whole output of the last run of an alias with
+// `keep_output`, `{last_output:lines:2}` only its second line
+pub fn placeholder(name: &str, argument: Option<&str>) -> Option<Result<String>> {
+    if name != "last_output" {
+        return None;
+    }
+
+    Some(resolve(argument).with_context(|| format!("Failed to resolve placeholder {{{}}}", name)))
+}
+
+fn resolve(argument: Option<&str>) -> Result<String> {
+    let output = match get_output_path().and_then(|path| Ok(fs::read(path)?)) {
+        Ok(output) => String::from_utf8_lossy(&output).into_owned(),
+        Err(_) => return Err(anyhow!("no output has been kept yet")),
+    };
+
+    let Some(argument) = argument else {
+        return Ok(output.trim_end_matches('\n').to_string());
+    };
+
+    let line: usize = argument
+        .strip_prefix("lines:")
+        .and_then(|line| line.parse().ok())
+        .filter(|&line| line > 0)
+        .ok_or_else(|| anyhow!("expected 'lines:<number>', got '{}'", argument))?;
+
+    output
+        .lines()
+        .nth(line - 1)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("the kept output has only {} lines", output.lines().count()))
+}
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result, anyhow};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command as OsCommand, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use crate::log::RunLog;
+use crate::sys;
+use crate::timeout::{self, Deadline, GRACE_PERIOD, POLL_INTERVAL, TIMED_OUT};
+
+// how a step that didn't end on its own was stopped
+#[derive(Clone, Copy, PartialEq)]
+enum Stop {
+    Cancelled,
+    TimedOut,
+}
+
+struct Outcome {
+    status: ExitStatus,
+    stopped: Option<Stop>,
+}
+
+// starts every step at once and waits for them, each output line is
+// prefixed with the number of its step, the result is the status of the
+// first step that failed or the last one if they all succeeded
+pub fn run(
+    name: &str,
+    steps: Vec<(String, OsCommand)>,
+    fail_fast: bool,
+    to_stderr: bool,
+    deadline: Option<&Deadline>,
+    logs: &[&RunLog],
+) -> Result<ExitStatus> {
+    let (commands, mut children) = spawn(steps)?;
+    let prefixes: Vec<_> = (1..=children.len()).map(|i| format!("[{}]", i)).collect();
+
+    let outcomes = thread::scope(|scope| {
+        for (child, prefix) in children.iter_mut().zip(&prefixes) {
+            if let Some(stdout) = child.stdout.take() {
+                scope.spawn(move || forward(stdout, prefix, to_stderr, logs));
+            }
+
+            if let Some(stderr) = child.stderr.take() {
+                scope.spawn(move || forward(stderr, prefix, true, logs));
+            }
+        }
+
+        wait_steps(&mut children, fail_fast, deadline)
+    })?;
+
+    if outcomes.iter().any(|outcome| !outcome.status.success()) {
+        print_summary(name, &commands, &outcomes);
+    }
+
+    overall_status(&outcomes).ok_or_else(|| anyhow!("Alias '{}' has no steps.", name))
+}
+
+// the output of every step, captured separately
+pub fn capture(
+    steps: Vec<(String, OsCommand)>,
+    fail_fast: bool,
+    deadline: Option<&Deadline>,
+) -> Result<Vec<(String, Output)>> {
+    let (commands, mut children) = spawn(steps)?;
+
+    let (outcomes, streams) = thread::scope(|scope| {
+        let readers: Vec<_> = children
+            .iter_mut()
+            .map(|child| {
+                let stdout = child.stdout.take();
+                let stderr = child.stderr.take();
+
+                (
+                    scope.spawn(move || timeout::read_all(stdout)),
+                    scope.spawn(move || timeout::read_all(stderr)),
+                )
+            })
+            .collect();
+        let outcomes = wait_steps(&mut children, fail_fast, deadline);
+        let streams: Vec<_> = readers
+            .into_iter()
+            .map(|(stdout, stderr)| {
+                (
+                    stdout.join().unwrap_or_default(),
+                    stderr.join().unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        (outcomes, streams)
+    });
+
+    Ok(commands
+        .into_iter()
+        .zip(outcomes?)
+        .zip(streams)
+        .map(|((command, outcome), (stdout, stderr))| {
+            let output = Output {
+                status: outcome.status,
+                stdout,
+                stderr,
+            };
+
+            (command, output)
+        })
+        .collect())
+}
+
+// every step runs in a process group of its own, so stopping one also stops
+// whatever it started, and without the terminal's input, which the steps
+// would otherwise fight over, a step that fails to start leaves the ones
+// already started running on their own, like a shell job would
+fn spawn(steps: Vec<(String, OsCommand)>) -> Result<(Vec<String>, Vec<Child>)> {
+    let mut commands = Vec::new();
+    let mut children = Vec::new();
+
+    for (command, mut cmd) in steps {
+        let child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .context("Failed to execute command")?;
+
+        commands.push(command);
+        children.push(child);
+    }
+
+    Ok((commands, children))
+}
+
+// waits for every step, signals sent to pintas are passed on to all of
+// them, with `fail_fast` the first step that fails stops the others, and
+// past the deadline they are all stopped: SIGTERM first and SIGKILL once
+// the grace period is over
+fn wait_steps(
+    children: &mut [Child],
+    fail_fast: bool,
+    deadline: Option<&Deadline>,
+) -> Result<Vec<Outcome>> {
+    let _guard = sys::SignalGuard::forwarding();
+    let mut outcomes: Vec<Option<Outcome>> = children.iter().map(|_| None).collect();
+    let mut stopping: Option<(Instant, Stop)> = None;
+
+    loop {
+        let mut failed = false;
+
+        for (child, outcome) in children.iter_mut().zip(outcomes.iter_mut()) {
+            if outcome.is_some() {
+                continue;
+            }
+
+            let Some(status) = child.try_wait().context("Failed to wait for command")? else {
+                continue;
+            };
+            let stopped = stopping.map(|(_, stop)| stop);
+
+            failed |= !status.success();
+            *outcome = Some(Outcome {
+                status: match stopped {
+                    Some(Stop::TimedOut) => sys::exit_status(TIMED_OUT),
+                    _ => status,
+                },
+                stopped,
+            });
+        }
+
+        let running: Vec<u32> = children
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, outcome)| outcome.is_none())
+            .map(|(child, _)| child.id())
+            .collect();
+
+        if running.is_empty() {
+            break;
+        }
+
+        let signal = |signum| running.iter().for_each(|&id| sys::kill_group(id, signum));
+
+        if let Some(signum) = sys::take_signal() {
+            signal(signum);
+        }
+
+        match stopping {
+            Some((grace, _)) if Instant::now() >= grace => signal(sys::SIGKILL),
+            Some(_) => {}
+            None => {
+                let stop = if failed && fail_fast {
+                    Some(Stop::Cancelled)
+                } else if deadline.is_some_and(|deadline| Instant::now() >= deadline.at) {
+                    if let Some(deadline) = deadline {
+                        eprintln!(
+                            "Alias '{}' timed out after {}.",
+                            deadline.alias, deadline.limit
+                        );
+                    }
+
+                    Some(Stop::TimedOut)
+                } else {
+                    None
+                };
+
+                if let Some(stop) = stop {
+                    signal(sys::SIGTERM);
+                    stopping = Some((Instant::now() + GRACE_PERIOD, stop));
+                }
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(outcomes.into_iter().flatten().collect())
+}
+
+// the first step that failed on its own wins over the ones it cancelled
+fn overall_status(outcomes: &[Outcome]) -> Option<ExitStatus> {
+    outcomes
+        .iter()
+        .find(|outcome| outcome.stopped.is_none() && !outcome.status.success())
+        .or_else(|| outcomes.iter().find(|outcome| !outcome.status.success()))
+        .or(outcomes.last())
+        .map(|outcome| outcome.status)
+}
+
+fn print_summary(name: &str, commands: &[String], outcomes: &[Outcome]) {
+    eprintln!("Steps of alias '{}':", name);
+    eprintln!("  {:<6}{:<6}{:<11}command", "step", "exit", "result");
+
+    for (i, (command, outcome)) in commands.iter().zip(outcomes).enumerate() {
+        let result = match outcome.stopped {
+            Some(Stop::Cancelled) => "cancelled",
+            Some(Stop::TimedOut) => "timed out",
+            None if outcome.status.success() => "ok",
+            None => "failed",
+        };
+
+        eprintln!(
+            "  {:<6}{:<6}{:<11}{}",
+            format!("{}/{}", i + 1, commands.len()),
+            sys::exit_code(&outcome.status),
+            result,
+            command
+        );
+    }
+}
+
+// copies whole lines so the output of different steps doesn't mix within a
+// line
+fn forward(stream: impl Read, prefix: &str, to_stderr: bool, logs: &[&RunLog]) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+
+    while reader.read_until(b'\n', &mut line).is_ok_and(|len| len > 0) {
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches('\n');
+
+        for log in logs {
+            log.write(format!("{} {}\n", prefix, text).as_bytes());
+        }
+
+        if to_stderr {
+            let _ = writeln!(io::stderr().lock(), "{} {}", prefix, text);
+        } else {
+            let _ = writeln!(io::stdout().lock(), "{} {}", prefix, text);
+        }
+
+        line.clear();
+    }
+}
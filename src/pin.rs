@@ -0,0 +1,136 @@
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command as OsCommand, Stdio};
+use std::time::UNIX_EPOCH;
+
+use crate::config::{Alias, PinMismatch, get_pintas_dir};
+use crate::sys;
+
+const VERSIONS_FILENAME: &str = "versions";
+
+// checks the programs in `pin` before anything runs, a mismatch stops the
+// run unless `pin_mismatch = "warn"`
+pub fn check(name: &str, alias: &Alias) -> Result<()> {
+    let mut pins: Vec<_> = alias.pin.iter().collect();
+
+    pins.sort();
+
+    for (program, pinned) in pins {
+        let found = match sys::find_program(program) {
+            Some(path) => match version_of(&path) {
+                Some(version) if matches(&version, pinned) => continue,
+                Some(version) => version,
+                None => "a version pintas can't tell".to_string(),
+            },
+            None => "not installed".to_string(),
+        };
+
+        let mismatch = format!(
+            "is pinned to {} {}, but {} is {}",
+            program, pinned, program, found
+        );
+
+        match alias.pin_mismatch {
+            PinMismatch::Abort => {
+                return Err(anyhow!(
+                    "Alias '{}' {}. Install the pinned version, or set pin_mismatch = \"warn\" to run it anyway.",
+                    name,
+                    mismatch
+                ));
+            }
+            PinMismatch::Warn => eprintln!("Warning: alias '{}' {}.", name, mismatch),
+        }
+    }
+
+    Ok(())
+}
+
+// `1.27` matches `1.27` and `1.27.4` but not `1.270`
+fn matches(version: &str, pinned: &str) -> bool {
+    let pinned = pinned.trim_start_matches('v');
+
+    version == pinned
+        || version
+            .strip_prefix(pinned)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+// the first thing that looks like a version in what `--version` prints, or
+// `version` for tools like kubectl that only have a subcommand, cached in
+// `~/.pintas/versions` until the program file changes
+fn version_of(path: &Path) -> Option<String> {
+    let stamp = stamp(path)?;
+    let key = format!("{}\t{}", stamp, path.to_string_lossy());
+    let cache = get_versions_path().ok()?;
+    let cached = fs::read_to_string(&cache).unwrap_or_default();
+
+    if let Some(version) = cached
+        .lines()
+        .find_map(|line| line.split_once('\t').filter(|(_, rest)| *rest == key))
+        .map(|(version, _)| version.to_string())
+    {
+        return Some(version);
+    }
+
+    let version = ["--version", "version"]
+        .iter()
+        .find_map(|arg| parse_version(&probe(path, arg)?))?;
+
+    // other programs keep their entries, an older one of this program goes
+    let mut lines: Vec<&str> = cached
+        .lines()
+        .filter(|line| !line.ends_with(&format!("\t{}", path.to_string_lossy())))
+        .collect();
+    let entry = format!("{}\t{}", version, key);
+
+    lines.push(&entry);
+
+    let _ = fs::create_dir_all(cache.parent().unwrap_or(&cache))
+        .and_then(|_| fs::write(&cache, format!("{}\n", lines.join("\n"))));
+
+    Some(version)
+}
+
+fn get_versions_path() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join(VERSIONS_FILENAME))
+}
+
+// size and modification time, an upgrade changes at least one of them
+fn stamp(path: &Path) -> Option<String> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some(format!("{} {}", meta.len(), modified.as_nanos()))
+}
+
+// stdout and stderr together, some tools print their version on stderr
+fn probe(path: &Path, arg: &str) -> Option<String> {
+    let output = OsCommand::new(path)
+        .arg(arg)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    Some(format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+// digits with at least one dot, leading letters like in `v1.27.4` or
+// `go1.21.5` are dropped
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+        .map(|word| word.trim_start_matches(|c: char| c.is_ascii_alphabetic()))
+        .map(|word| word.trim_end_matches('.'))
+        .find(|word| {
+            word.contains('.')
+                && word
+                    .split('.')
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(str::to_string)
+}
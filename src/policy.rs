@@ -0,0 +1,517 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::config::{Config, get_pintas_dir};
+
+const POLICY_FILENAME: &str = "policy.toml";
+
+// operators are kept as standalone tokens so `curl x|sh` and `curl x | sh`
+// look the same to the matcher
+const OPERATOR_CHARS: &[char] = &['|', '&', ';', '(', ')', '<', '>'];
+
+#[derive(Deserialize)]
+struct Policy {
+    #[serde(default = "default_deny")]
+    deny: Vec<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            deny: default_deny(),
+        }
+    }
+}
+
+fn default_deny() -> Vec<String> {
+    [
+        "sudo",
+        "doas",
+        "su",
+        "rm -rf /",
+        "rm -fr /",
+        "rm -rf ~",
+        "curl * | sh",
+        "curl * | bash",
+        "wget * | sh",
+        "wget * | bash",
+        "mkfs*",
+        "dd * of=/dev/*",
+    ]
+    .iter()
+    .map(|pattern| pattern.to_string())
+    .collect()
+}
+
+fn load_policy() -> Result<Policy> {
+    let path = get_pintas_dir()?.join(POLICY_FILENAME);
+
+    if !path.exists() {
+        return Ok(Policy::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read '{}'.", path.to_string_lossy()))?;
+
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse '{}'.", path.to_string_lossy()))
+}
+
+pub fn check_restricted(alias: &str, command: &str) -> Result<()> {
+    let policy = load_policy()?;
+    let command_words = words(tokenize(command));
+
+    for pattern in &policy.deny {
+        if matches_tokens(&words(tokenize(pattern)), &command_words) {
+            return Err(anyhow!(
+                "Alias '{}' refused in restricted mode: command matches denied pattern '{}'.",
+                alias,
+                pattern
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// `safe_args` refuses commands that use their arguments in a way the shell
+// splits, globs or runs as code: `$1`, `$@` or `$*` outside double quotes,
+// or any of them in a command that uses `eval`
+pub fn check_safe_args(alias: &str, command: &str) -> Result<()> {
+    let (unquoted, any) = positional_refs(command);
+    let evals = command
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .any(|word| word == "eval");
+
+    if let Some(param) = unquoted {
+        return Err(anyhow!(
+            "Alias '{}' uses {} outside double quotes, which safe_args doesn't allow. Write \"{}\" or a placeholder like {{1}} instead.",
+            alias,
+            param,
+            param
+        ));
+    }
+
+    if any && evals {
+        return Err(anyhow!(
+            "Alias '{}' passes its arguments to eval, which safe_args doesn't allow because they would run as code.",
+            alias
+        ));
+    }
+
+    Ok(())
+}
+
+// the first reference to a positional parameter outside double quotes, and
+// whether there is one anywhere, references in single quotes or escaped
+// with a backslash aren't expanded by the shell
+fn positional_refs(command: &str) -> (Option<String>, bool) {
+    let chars: Vec<char> = command.chars().collect();
+    let (mut single, mut double, mut any) = (false, false, false);
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if !single => i += 1,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '$' if !single => {
+                let rest: String = chars[i + 1..].iter().take(8).collect();
+                let param = match rest.chars().next() {
+                    Some(c) if c.is_ascii_digit() || c == '@' || c == '*' => {
+                        Some(format!("${}", c))
+                    }
+                    Some('{') => rest[1..]
+                        .split_once('}')
+                        .filter(|(inner, _)| {
+                            !inner.is_empty()
+                                && (inner.chars().all(|c| c.is_ascii_digit())
+                                    || *inner == "@"
+                                    || *inner == "*")
+                        })
+                        .map(|(inner, _)| format!("${{{}}}", inner)),
+                    _ => None,
+                };
+
+                if let Some(param) = param {
+                    any = true;
+
+                    if !double {
+                        return (Some(param), true);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        i += 1;
+    }
+
+    (None, any)
+}
+
+// `[blocked]` applies to every run, matched like the deny patterns of
+// restricted mode against the command with its arguments in place of `$@`
+// and `$1`, or after it when it doesn't use them
+pub fn check_blocked(config: &Config, alias: &str, command: &str, args: &[String]) -> Result<()> {
+    if config.blocked.is_empty() {
+        return Ok(());
+    }
+
+    let command_words = words(with_args(tokenize(command), args));
+
+    for (pattern, message) in &config.blocked {
+        if matches_tokens(&words(tokenize(pattern)), &command_words) {
+            return Err(anyhow!(
+                "Alias '{}' would run '{}', which is blocked: {}",
+                alias,
+                pattern,
+                message
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn with_args(tokens: Vec<String>, args: &[String]) -> Vec<String> {
+    let mut filled = Vec::new();
+    let mut used = false;
+
+    for token in tokens {
+        let position = token
+            .strip_prefix('$')
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&n| n > 0);
+
+        match (token.as_str(), position) {
+            ("$@" | "$*", _) => {
+                filled.extend(args.iter().cloned());
+                used = true;
+            }
+            (_, Some(n)) => {
+                filled.extend(args.get(n - 1).cloned());
+                used = true;
+            }
+            _ => filled.push(token),
+        }
+    }
+
+    if !used {
+        filled.extend(args.iter().cloned());
+    }
+
+    filled
+}
+
+fn tokenize(command: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut operator = String::new();
+
+    for ch in command.chars() {
+        if OPERATOR_CHARS.contains(&ch) {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+
+            operator.push(ch);
+            continue;
+        }
+
+        if !operator.is_empty() {
+            tokens.push(std::mem::take(&mut operator));
+        }
+
+        if ch.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else if ch != '"' && ch != '\'' {
+            current.push(ch);
+        }
+    }
+
+    if !operator.is_empty() {
+        tokens.push(operator);
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+// a word of a command as the matcher sees it, the flags of a command are
+// gathered in one place so `rm -r -f`, `rm -fr` and `rm -rf` are the same
+#[derive(Debug, PartialEq)]
+enum Word {
+    Plain(String),
+    Flags(BTreeSet<String>),
+    // `sudo` in `/usr/bin/sudo` or `env FOO=1 sudo`, patterns start at one
+    Command(String),
+}
+
+// run the command that follows them
+const PREFIXES: &[&str] = &["builtin", "command", "env", "eval", "exec", "nohup", "time"];
+
+// run the string after `-c` as a command
+const SHELLS: &[&str] = &["ash", "bash", "dash", "ksh", "sh", "zsh"];
+
+// the command names are matched without their path, a backslash or one of
+// the `PREFIXES`, assignments before them are left out, the flags of each
+// command follow its name and `/*` or `"$HOME"` are `/` and `~`
+fn words(tokens: Vec<String>) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut flags: Option<usize> = None;
+    let mut start = true;
+    let mut prefixed = false;
+    let mut shell = false;
+
+    for token in tokens {
+        if token.starts_with(OPERATOR_CHARS) {
+            // a redirect is followed by a file, not a command
+            if !token.contains(['<', '>']) {
+                start = true;
+                prefixed = false;
+                flags = None;
+            }
+
+            words.push(Word::Plain(token));
+            continue;
+        }
+
+        if start {
+            let word = token.trim_start_matches(['\\', '`']);
+            let name = match word.rsplit('/').next() {
+                Some(name) if !name.is_empty() => name,
+                _ => word,
+            };
+            let skipped = word.contains('=') || (prefixed && word.starts_with('-'));
+
+            if PREFIXES.contains(&name) {
+                prefixed = true;
+            } else if !skipped {
+                words.push(Word::Command(name.to_string()));
+                start = false;
+                shell = SHELLS.contains(&name);
+            }
+
+            continue;
+        }
+
+        let Some(found) = split_flags(&token) else {
+            words.push(Word::Plain(path_name(&token)));
+            continue;
+        };
+
+        // `sh -c 'sudo make install'` runs `sudo` as a command
+        if shell && found.contains("-c") {
+            start = true;
+        }
+
+        match flags {
+            Some(i) => {
+                if let Word::Flags(known) = &mut words[i] {
+                    known.extend(found);
+                }
+            }
+            None => {
+                let i = words
+                    .iter()
+                    .rposition(|word| matches!(word, Word::Command(_)))
+                    .map_or(0, |i| i + 1);
+
+                words.insert(i, Word::Flags(found));
+                flags = Some(i);
+            }
+        }
+    }
+
+    words
+}
+
+// `-rf` is `-r` and `-f`, `--force` stays as it is, a lone `-` or `--` and
+// negative numbers aren't flags
+fn split_flags(token: &str) -> Option<BTreeSet<String>> {
+    if let Some(long) = token.strip_prefix("--") {
+        return (!long.is_empty()).then(|| BTreeSet::from([token.to_string()]));
+    }
+
+    let short = token.strip_prefix('-')?;
+
+    if short.is_empty() || !short.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    Some(short.chars().map(|c| format!("-{}", c)).collect())
+}
+
+// the ways of writing the root or home directory on their own
+fn path_name(token: &str) -> String {
+    let trimmed = token
+        .strip_suffix("/*")
+        .or_else(|| token.strip_suffix("/."))
+        .unwrap_or(token);
+    let trimmed = trimmed.strip_suffix('/').unwrap_or(trimmed);
+
+    match trimmed {
+        "" if !token.is_empty() => "/".to_string(),
+        "~" | "$HOME" | "${HOME}" => "~".to_string(),
+        _ => token.to_string(),
+    }
+}
+
+// the pattern matches if its words appear contiguously in the command from
+// where one of its commands starts; a bare `*` stands for any run of words,
+// flags in the pattern have to be among the command's and the command may
+// have flags the pattern doesn't mention
+fn matches_tokens(pattern: &[Word], words: &[Word]) -> bool {
+    (0..words.len())
+        .filter(|&start| matches!(words[start], Word::Command(_)))
+        .any(|start| matches_at(pattern, &words[start..]))
+}
+
+fn matches_at(pattern: &[Word], words: &[Word]) -> bool {
+    let Some((first, rest)) = pattern.split_first() else {
+        return true;
+    };
+
+    if matches!(first, Word::Plain(any) if any == "*") {
+        return (0..=words.len()).any(|skip| matches_at(rest, &words[skip..]));
+    }
+
+    let Some((word, remaining)) = words.split_first() else {
+        return false;
+    };
+
+    match (first, word) {
+        (Word::Flags(wanted), Word::Flags(found)) => {
+            wanted.is_subset(found) && matches_at(rest, remaining)
+        }
+        (_, Word::Flags(_)) => matches_at(pattern, remaining),
+        (
+            Word::Command(wanted) | Word::Plain(wanted),
+            Word::Command(found) | Word::Plain(found),
+        ) => glob_match(wanted, found) && matches_at(rest, remaining),
+        _ => false,
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| glob_match_chars(rest, &text[skip..])),
+        Some((ch, rest)) => match text.split_first() {
+            Some((t, remaining)) => t == ch && glob_match_chars(rest, remaining),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denied(command: &str) -> bool {
+        let command_words = words(tokenize(command));
+
+        default_deny()
+            .iter()
+            .any(|pattern| matches_tokens(&words(tokenize(pattern)), &command_words))
+    }
+
+    #[test]
+    fn deny_list_matches_plain_commands() {
+        assert!(denied("sudo make install"));
+        assert!(denied("rm -rf /"));
+        assert!(denied("curl -fsSL https://example.com/install|sh"));
+        assert!(denied("mkfs.ext4 /dev/sda1"));
+        assert!(!denied("make install"));
+        assert!(!denied("rm -rf build"));
+    }
+
+    #[test]
+    fn deny_list_matches_commands_by_their_name() {
+        assert!(denied("/usr/bin/sudo make install"));
+        assert!(denied("\\sudo make install"));
+        assert!(denied("command sudo make install"));
+        assert!(denied("exec sudo make install"));
+        assert!(denied("env -i FOO=1 sudo make install"));
+        assert!(denied("FOO=1 /usr/bin/sudo ls"));
+        assert!(denied("cd / && /bin/rm -rf /"));
+        assert!(denied("curl https://example.com | /bin/bash"));
+    }
+
+    #[test]
+    fn patterns_only_match_where_a_command_starts() {
+        assert!(!denied("echo sudo"));
+        assert!(!denied("grep sudo log"));
+        assert!(!denied("echo x > /tmp/sudo"));
+        assert!(!denied("man rm -rf /"));
+        assert!(denied("echo hi; sudo ls"));
+        assert!(denied("ls | sudo tee /etc/x"));
+        assert!(denied("sh -c 'sudo make install'"));
+        assert!(denied("bash -ec \"sudo make install\""));
+    }
+
+    #[test]
+    fn flags_match_however_they_are_written() {
+        assert!(denied("rm -r -f /"));
+        assert!(denied("rm -fr /"));
+        assert!(denied("rm -rfv /"));
+        assert!(denied("rm -f -r -f /"));
+        assert!(denied("rm / -rf"));
+        assert!(denied("rm -rf --no-preserve-root /"));
+        assert!(!denied("rm -r /"));
+        assert!(!denied("rm -f /"));
+    }
+
+    #[test]
+    fn root_and_home_match_however_they_are_written() {
+        assert!(denied("rm -rf /*"));
+        assert!(denied("rm -rf /."));
+        assert!(denied("rm -rf \"$HOME\""));
+        assert!(denied("rm -rf ${HOME}/"));
+        assert!(denied("rm -rf ~/*"));
+        assert!(!denied("rm -rf /tmp/build"));
+        assert!(!denied("rm -rf ~/build"));
+    }
+
+    #[test]
+    fn flags_the_pattern_leaves_out_still_match() {
+        let blocked = words(tokenize("git push"));
+
+        assert!(matches_tokens(
+            &blocked,
+            &words(tokenize("git push --force"))
+        ));
+        assert!(matches_tokens(
+            &words(tokenize("git push --force")),
+            &words(with_args(
+                tokenize("git push \"$@\""),
+                &["origin".to_string(), "--force".to_string()]
+            ))
+        ));
+    }
+
+    #[test]
+    fn safe_args_refuses_unquoted_arguments() {
+        assert!(check_safe_args("a", "ls $1").is_err());
+        assert!(check_safe_args("a", "ls ${10}").is_err());
+        assert!(check_safe_args("a", "ls $@").is_err());
+        assert!(check_safe_args("a", "eval \"$@\"").is_err());
+        assert!(check_safe_args("a", "ls \"$1\" \"$@\"").is_ok());
+        assert!(check_safe_args("a", "echo '$1' \\$1").is_ok());
+    }
+}
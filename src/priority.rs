@@ -0,0 +1,95 @@
+use anyhow::{Result, anyhow};
+use std::os::unix::process::CommandExt;
+use std::process::Command as OsCommand;
+
+use crate::config::Alias;
+use crate::sys;
+
+// `ionice = "idle"`, `"best-effort"` or `"best-effort:7"`, `"realtime:0"`,
+// as the class and level `ionice -c -n` takes
+fn parse_ionice(ionice: &str) -> Option<(u8, Option<u8>)> {
+    let (class, level) = match ionice.split_once(':') {
+        Some((class, level)) => (class, Some(level.parse().ok().filter(|level| *level <= 7)?)),
+        None => (ionice, None),
+    };
+
+    match class {
+        "realtime" => Some((1, level)),
+        "best-effort" => Some((2, level)),
+        "idle" if level.is_none() => Some((3, None)),
+        _ => None,
+    }
+}
+
+// checks `nice` and `ionice` before anything runs
+pub fn check(name: &str, alias: &Alias) -> Result<()> {
+    if let Some(nice) = alias.nice.filter(|nice| !(-20..=19).contains(nice)) {
+        return Err(anyhow!(
+            "Alias '{}' has an invalid nice {}, expected -20 (highest priority) to 19 (lowest).",
+            name,
+            nice
+        ));
+    }
+
+    let Some(ionice) = &alias.ionice else {
+        return Ok(());
+    };
+
+    if parse_ionice(ionice).is_none() {
+        return Err(anyhow!(
+            "Alias '{}' has an invalid ionice '{}', expected idle, best-effort or realtime, with an optional level from 0 to 7 like best-effort:7.",
+            name,
+            ionice
+        ));
+    }
+
+    if !can_ionice() {
+        eprintln!(
+            "Warning: alias '{}' asks for ionice, but the ionice program isn't available here. Running it at normal I/O priority.",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+fn can_ionice() -> bool {
+    cfg!(target_os = "linux") && sys::find_program("ionice").is_some()
+}
+
+// the words that run a program at the alias's I/O priority, `-t` keeps a
+// class the kernel refuses from failing the run
+pub fn ionice_prefix(alias: &Alias) -> Vec<String> {
+    let Some((class, level)) = alias.ionice.as_deref().and_then(parse_ionice) else {
+        return Vec::new();
+    };
+
+    if !can_ionice() {
+        return Vec::new();
+    }
+
+    let mut prefix = vec!["ionice".to_string(), "-c".to_string(), class.to_string()];
+
+    if let Some(level) = level {
+        prefix.push("-n".to_string());
+        prefix.push(level.to_string());
+    }
+
+    prefix.push("-t".to_string());
+    prefix.push("--".to_string());
+    prefix
+}
+
+// the child lowers its own priority between fork and exec, raising it
+// (below 0) needs root and is skipped without it, like ionice's `-t`
+pub fn apply_nice(cmd: &mut OsCommand, alias: &Alias) {
+    if let Some(nice) = alias.nice {
+        unsafe {
+            cmd.pre_exec(move || {
+                sys::set_nice(nice);
+
+                Ok(())
+            });
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::ffi::{CStr, c_char, c_ulong};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::str;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::get_pintas_dir;
+use crate::history;
+use crate::json::Json;
+use crate::run::{self, RunArgs};
+use crate::shims::shim_name;
+use crate::sys;
+
+const O_RDWR: i32 = 2;
+const TCSANOW: i32 = 0;
+
+#[cfg(not(target_os = "macos"))]
+const O_NOCTTY: i32 = 0o400;
+#[cfg(not(target_os = "macos"))]
+const TIOCGWINSZ: c_ulong = 0x5413;
+#[cfg(not(target_os = "macos"))]
+const TIOCSWINSZ: c_ulong = 0x5414;
+#[cfg(not(target_os = "macos"))]
+const TIOCSCTTY: c_ulong = 0x540e;
+
+#[cfg(target_os = "macos")]
+const O_NOCTTY: i32 = 0x20000;
+#[cfg(target_os = "macos")]
+const TIOCGWINSZ: c_ulong = 0x40087468;
+#[cfg(target_os = "macos")]
+const TIOCSWINSZ: c_ulong = 0x80087467;
+#[cfg(target_os = "macos")]
+const TIOCSCTTY: c_ulong = 0x20007461;
+
+#[repr(C)]
+#[derive(Default)]
+struct Winsize {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+}
+
+// the layout of termios differs between platforms, pintas only hands it
+// from tcgetattr to cfmakeraw and back, so a buffer big enough for all of
+// them does
+#[repr(C, align(8))]
+#[derive(Clone)]
+struct Termios([u8; 256]);
+
+unsafe extern "C" {
+    fn posix_openpt(flags: i32) -> i32;
+    fn grantpt(fd: i32) -> i32;
+    fn unlockpt(fd: i32) -> i32;
+    fn ptsname(fd: i32) -> *const c_char;
+    fn ioctl(fd: i32, request: c_ulong, ...) -> i32;
+    fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+    fn tcsetattr(fd: i32, action: i32, termios: *const Termios) -> i32;
+    fn cfmakeraw(termios: *mut Termios);
+}
+
+// keys go to the recorded program as they are typed, the terminal gets its
+// settings back when the recording ends
+struct RawMode(Termios);
+
+impl RawMode {
+    fn enable() -> Option<RawMode> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+
+        let mut saved = Termios([0; 256]);
+
+        if unsafe { tcgetattr(0, &mut saved) } != 0 {
+            return None;
+        }
+
+        let mut raw = saved.clone();
+
+        unsafe {
+            cfmakeraw(&mut raw);
+            tcsetattr(0, TCSANOW, &raw);
+        }
+
+        Some(RawMode(saved))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            tcsetattr(0, TCSANOW, &self.0);
+        }
+    }
+}
+
+pub fn get_recordings_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("recordings"))
+}
+
+// `run --record`: the run happens in a pintas of its own on a pseudo
+// terminal, whose output is shown and written to an asciicast v2 file that
+// `asciinema play` replays
+pub fn run_recorded(run: &RunArgs) -> Result<ExitStatus> {
+    let dir = get_recordings_dir()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let stem = format!(
+        "{}-{}",
+        shim_name(&run.alias),
+        history::format_timestamp(now)
+    );
+
+    fs::create_dir_all(&dir).context("Failed to create recordings directory")?;
+
+    // a second recording within the same second gets a number
+    let mut path = dir.join(format!("{}.cast", stem));
+
+    for n in 2.. {
+        if !path.exists() {
+            break;
+        }
+
+        path = dir.join(format!("{}-{}.cast", stem, n));
+    }
+
+    let mut cast = File::create(&path)
+        .with_context(|| format!("Failed to create '{}'", path.to_string_lossy()))?;
+    let (master, slave) = open_pty()?;
+    let size = window_size();
+
+    unsafe {
+        ioctl(slave.as_raw_fd(), TIOCSWINSZ, &size as *const Winsize);
+    }
+
+    let header = Json::object([
+        ("version", Json::Number(2.0)),
+        ("width", Json::Number(size.cols.into())),
+        ("height", Json::Number(size.rows.into())),
+        ("timestamp", Json::Number(now as f64)),
+        ("title", Json::string(format!("pintas run {}", run.alias))),
+        (
+            "env",
+            Json::object([
+                (
+                    "TERM",
+                    Json::optional_string(env::var("TERM").ok().as_deref()),
+                ),
+                (
+                    "SHELL",
+                    Json::optional_string(env::var("SHELL").ok().as_deref()),
+                ),
+            ]),
+        ),
+    ]);
+
+    writeln!(cast, "{}", header).context("Failed to write recording")?;
+
+    let mut child = {
+        let mut cmd = run::rerun_command(run)?;
+
+        cmd.stdin(Stdio::from(slave.try_clone()?));
+        cmd.stdout(Stdio::from(slave.try_clone()?));
+        cmd.stderr(Stdio::from(slave));
+
+        // the pseudo terminal becomes the controlling terminal of the run,
+        // so Ctrl-C typed into it reaches the program
+        unsafe {
+            cmd.pre_exec(|| {
+                sys::start_session()?;
+
+                if ioctl(0, TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        // the copies of the slave in `cmd` are closed once it's dropped,
+        // after that reading the master ends with the run
+        cmd.spawn().context("Failed to start the recorded run")?
+    };
+
+    let raw_mode = RawMode::enable();
+    let mut input = master.try_clone()?;
+
+    // blocks on our stdin until pintas exits, so it isn't joined
+    thread::spawn(move || io::copy(&mut io::stdin(), &mut input));
+
+    let copied = record_output(master, &mut cast);
+    let status = child.wait().context("Failed to wait for the recorded run");
+
+    drop(raw_mode);
+    copied?;
+
+    eprintln!(
+        "Recorded the run to '{}', replay it with 'asciinema play {}'.",
+        path.to_string_lossy(),
+        path.to_string_lossy()
+    );
+
+    status
+}
+
+fn open_pty() -> Result<(File, File)> {
+    let fd = unsafe { posix_openpt(O_RDWR | O_NOCTTY) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to open a pseudo terminal");
+    }
+
+    let master = unsafe { File::from_raw_fd(fd) };
+
+    if unsafe { grantpt(fd) } != 0 || unsafe { unlockpt(fd) } != 0 {
+        return Err(io::Error::last_os_error()).context("Failed to set up a pseudo terminal");
+    }
+
+    let name = unsafe { ptsname(fd) };
+
+    if name.is_null() {
+        return Err(anyhow!("Failed to find the pseudo terminal device."));
+    }
+
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(O_NOCTTY)
+        .open(&name)
+        .with_context(|| format!("Failed to open '{}'", name))?;
+
+    Ok((master, slave))
+}
+
+// the size of our terminal, or the classic 80x24 without one
+fn window_size() -> Winsize {
+    let mut size = Winsize::default();
+
+    if unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut Winsize) } != 0 || size.cols == 0 {
+        size = Winsize {
+            rows: 24,
+            cols: 80,
+            ..Default::default()
+        };
+    }
+
+    size
+}
+
+// every chunk is shown and written as an output event, a UTF-8 sequence
+// split between two reads waits for the rest
+fn record_output(mut master: File, cast: &mut File) -> Result<()> {
+    let started = Instant::now();
+    let mut buffer = [0; 8192];
+    let mut pending = Vec::new();
+
+    loop {
+        let len = match master.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // the slave was closed, which Linux reports as EIO
+            Err(_) => break,
+        };
+
+        let mut out = io::stdout().lock();
+
+        let _ = out.write_all(&buffer[..len]).and_then(|_| out.flush());
+
+        pending.extend_from_slice(&buffer[..len]);
+
+        let complete = match str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+        let text = String::from_utf8_lossy(&pending[..complete]).into_owned();
+
+        pending.drain(..complete);
+
+        let event = Json::Array(vec![
+            Json::Number(started.elapsed().as_micros() as f64 / 1_000_000.0),
+            Json::string("o"),
+            Json::string(text),
+        ]);
+
+        writeln!(cast, "{}", event).context("Failed to write recording")?;
+    }
+
+    Ok(())
+}
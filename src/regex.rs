@@ -0,0 +1,729 @@
+use anyhow::{Result, anyhow};
+
+// a small regex engine covering the everyday syntax: literals, `.`, classes
+// with ranges, negation and `[:alpha:]` style names, `\d \w \s` (and their
+// negations), `\b \B`, groups, alternation, greedy and lazy `* + ? {n,m}`,
+// `^ $` anchors and a leading `(?i)` for case-insensitive matching
+//
+// patterns are compiled to a program that runs all its threads in one pass
+// over the text, so the time is linear in the text whatever the pattern,
+// syntax it doesn't know is an error rather than a literal
+pub struct Regex {
+    program: Vec<Inst>,
+    ignore_case: bool,
+}
+
+// a program of that size is a pattern that repeats too much, like `a{5000}`
+const MAX_PROGRAM: usize = 10_000;
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Boundary(bool),
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, usize, bool),
+}
+
+#[derive(Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Clone, Copy)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+    Named(fn(&char) -> bool),
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Boundary(bool),
+    // both ways continue, the first one is preferred
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex> {
+        let (ignore_case, body) = match pattern.strip_prefix("(?i)") {
+            Some(body) => (true, body),
+            None => (false, pattern),
+        };
+
+        let mut parser = Parser {
+            chars: body.chars().collect(),
+            pos: 0,
+        };
+        let invalid = |e: String| anyhow!("Invalid regex '{}': {}", pattern, e);
+        let alternatives = parser.parse_alternatives().map_err(invalid)?;
+
+        if parser.pos < parser.chars.len() {
+            return Err(invalid("unmatched ')'".to_string()));
+        }
+
+        let mut program = Vec::new();
+
+        compile_alternatives(&alternatives, &mut program).map_err(invalid)?;
+        program.push(Inst::Match);
+
+        Ok(Regex {
+            program,
+            ignore_case,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    // byte range of the leftmost match
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.find_from(text, 0)
+    }
+
+    // byte ranges of the matches that don't overlap, left to right
+    pub fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut offset = 0;
+
+        while let Some((start, end)) = self.find_from(text, offset) {
+            if end > start {
+                matches.push((start, end));
+            }
+
+            // an empty match moves on by one character
+            offset = match text[end..].chars().next() {
+                Some(c) if end == start => end + c.len_utf8(),
+                Some(_) => end,
+                None => break,
+            };
+        }
+
+        matches
+    }
+
+    fn find_from(&self, text: &str, offset: usize) -> Option<(usize, usize)> {
+        let chars: Vec<char> = text.chars().collect();
+        let byte_offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect();
+        let first = byte_offsets.iter().position(|&byte| byte >= offset)?;
+        let mut runner = Runner {
+            regex: self,
+            chars: &chars,
+            seen: vec![usize::MAX; self.program.len()],
+        };
+
+        runner
+            .run(first)
+            .map(|(start, end)| (byte_offsets[start], byte_offsets[end]))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+
+        self.pos += 1;
+        ch
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_seq()?];
+
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_seq()?);
+        }
+
+        Ok(alternatives)
+    }
+
+    fn parse_seq(&mut self) -> Result<Vec<Node>, String> {
+        let mut seq = Vec::new();
+
+        while let Some(ch) = self.peek() {
+            if ch == '|' || ch == ')' {
+                break;
+            }
+
+            let atom = self.parse_atom()?;
+
+            seq.push(self.parse_quantifier(atom)?);
+        }
+
+        Ok(seq)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('(') => {
+                if self.peek() == Some('?') {
+                    if self.chars.get(self.pos + 1) != Some(&':') {
+                        return Err("only (?: groups and a leading (?i) are supported".to_string());
+                    }
+
+                    self.pos += 2;
+                }
+
+                let alternatives = self.parse_alternatives()?;
+
+                match self.next() {
+                    Some(')') => Ok(Node::Group(alternatives)),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some('[') => self.parse_class().map(Node::Class),
+            Some('\\') => match self.next() {
+                Some('b') => Ok(Node::Boundary(true)),
+                Some('B') => Ok(Node::Boundary(false)),
+                Some(ch) => Ok(match escape(ch)? {
+                    Some(item) => Node::Class(Class {
+                        negated: false,
+                        items: vec![item],
+                    }),
+                    None => Node::Char(literal_escape(ch)),
+                }),
+                None => Err("trailing '\\'".to_string()),
+            },
+            Some(ch @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before '{}'", ch)),
+            Some(ch) => Ok(Node::Char(ch)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Class, String> {
+        let negated = self.peek() == Some('^');
+
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = Vec::new();
+        let mut first = true;
+
+        loop {
+            let ch = match self.next() {
+                Some(']') if !first => break,
+                Some('[') if matches!(self.peek(), Some(':' | '=' | '.')) => {
+                    items.push(self.parse_named_class()?);
+                    first = false;
+                    continue;
+                }
+                Some('\\') => match self.next() {
+                    Some(ch) => match escape(ch)? {
+                        Some(item) => {
+                            items.push(item);
+                            first = false;
+                            continue;
+                        }
+                        None => literal_escape(ch),
+                    },
+                    None => return Err("trailing '\\'".to_string()),
+                },
+                Some(ch) => ch,
+                None => return Err("missing ']'".to_string()),
+            };
+
+            first = false;
+
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+
+                let end = match self.next() {
+                    Some('\\') => self.next().map(literal_escape),
+                    other => other,
+                }
+                .ok_or("missing ']'")?;
+
+                if end < ch {
+                    return Err(format!("range '{}-{}' is out of order", ch, end));
+                }
+
+                items.push(ClassItem::Range(ch, end));
+            } else {
+                items.push(ClassItem::Range(ch, ch));
+            }
+        }
+
+        Ok(Class { negated, items })
+    }
+
+    // `[:alpha:]` and the other POSIX names inside a class, `[=a=]` and
+    // `[.a.]` aren't supported
+    fn parse_named_class(&mut self) -> Result<ClassItem, String> {
+        let kind = self.next().unwrap_or_default();
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let close = format!("{}]", kind);
+        let name = rest
+            .find(&close)
+            .map(|end| &rest[..end])
+            .ok_or(format!("missing '{}'", close))?;
+
+        if kind != ':' {
+            return Err(format!("'[{}{}{}' is not supported", kind, name, close));
+        }
+
+        let test: fn(&char) -> bool = match name {
+            "alpha" => char::is_ascii_alphabetic,
+            "digit" => char::is_ascii_digit,
+            "alnum" => char::is_ascii_alphanumeric,
+            "upper" => char::is_ascii_uppercase,
+            "lower" => char::is_ascii_lowercase,
+            "space" => char::is_ascii_whitespace,
+            "blank" => |c| *c == ' ' || *c == '\t',
+            "punct" => char::is_ascii_punctuation,
+            "xdigit" => char::is_ascii_hexdigit,
+            "cntrl" => char::is_ascii_control,
+            "graph" => char::is_ascii_graphic,
+            "print" => |c| c.is_ascii_graphic() || *c == ' ',
+            _ => return Err(format!("unknown class '[:{}:]'", name)),
+        };
+
+        self.pos += name.chars().count() + 2;
+
+        Ok(ClassItem::Named(test))
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some(ch @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+
+                match ch {
+                    '*' => (0, usize::MAX),
+                    '+' => (1, usize::MAX),
+                    _ => (0, 1),
+                }
+            }
+            Some('{') => match self.parse_braces()? {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+
+        if matches!(atom, Node::Start | Node::End | Node::Boundary(_)) {
+            return Err("anchors can't be repeated".to_string());
+        }
+
+        // `*?` and the like repeat as few times as they can
+        let greedy = self.peek() != Some('?');
+
+        if !greedy {
+            self.pos += 1;
+        }
+
+        if matches!(self.peek(), Some('*' | '+' | '?')) {
+            return Err("nested quantifiers need a group".to_string());
+        }
+
+        Ok(Node::Repeat(Box::new(atom), min, max, greedy))
+    }
+
+    // `{n}`, `{n,}` or `{n,m}`, anything else is a literal brace
+    fn parse_braces(&mut self) -> Result<Option<(usize, usize)>, String> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let Some(close) = rest.find('}') else {
+            return Ok(None);
+        };
+        let inner = &rest[..close];
+        let number = |n: &str| n.parse::<usize>().ok();
+
+        let bounds = match inner.split_once(',') {
+            None => number(inner).map(|n| (n, n)),
+            Some((min, "")) => number(min).map(|min| (min, usize::MAX)),
+            Some((min, max)) => number(min).zip(number(max)),
+        };
+
+        let Some((min, max)) = bounds else {
+            return Ok(None);
+        };
+
+        if min > max {
+            return Err(format!("repetition '{{{}}}' is out of order", inner));
+        }
+
+        self.pos += inner.chars().count() + 2;
+
+        Ok(Some((min, max)))
+    }
+}
+
+// `\d` and the other classes, None for an escaped literal, letters and
+// digits that mean something elsewhere, like `\p` or `\1`, are refused
+fn escape(ch: char) -> Result<Option<ClassItem>, String> {
+    match ch {
+        'd' => Ok(Some(ClassItem::Digit(true))),
+        'D' => Ok(Some(ClassItem::Digit(false))),
+        'w' => Ok(Some(ClassItem::Word(true))),
+        'W' => Ok(Some(ClassItem::Word(false))),
+        's' => Ok(Some(ClassItem::Space(true))),
+        'S' => Ok(Some(ClassItem::Space(false))),
+        'n' | 't' | 'r' => Ok(None),
+        ch if ch.is_ascii_alphanumeric() => Err(format!("'\\{}' is not supported", ch)),
+        _ => Ok(None),
+    }
+}
+
+fn literal_escape(ch: char) -> char {
+    match ch {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        ch => ch,
+    }
+}
+
+fn compile_alternatives(alternatives: &[Vec<Node>], program: &mut Vec<Inst>) -> Result<(), String> {
+    let mut jumps = Vec::new();
+
+    for (i, seq) in alternatives.iter().enumerate() {
+        if i + 1 == alternatives.len() {
+            compile_seq(seq, program)?;
+            break;
+        }
+
+        let split = program.len();
+
+        program.push(Inst::Jump(0));
+        compile_seq(seq, program)?;
+        jumps.push(program.len());
+        program.push(Inst::Jump(0));
+        program[split] = Inst::Split(split + 1, program.len());
+    }
+
+    let end = program.len();
+
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+
+    Ok(())
+}
+
+fn compile_seq(seq: &[Node], program: &mut Vec<Inst>) -> Result<(), String> {
+    seq.iter().try_for_each(|node| compile_node(node, program))
+}
+
+// repetitions are compiled once for each time they have to match and once
+// for each time they may
+fn compile_node(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    if program.len() > MAX_PROGRAM {
+        return Err("the pattern is too large".to_string());
+    }
+
+    match node {
+        Node::Char(ch) => program.push(Inst::Char(*ch)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Boundary(at) => program.push(Inst::Boundary(*at)),
+        Node::Group(alternatives) => compile_alternatives(alternatives, program)?,
+        Node::Repeat(inner, min, max, greedy) => {
+            for _ in 0..*min {
+                compile_node(inner, program)?;
+            }
+
+            if *max == usize::MAX {
+                let start = program.len();
+
+                program.push(Inst::Jump(0));
+                compile_node(inner, program)?;
+                program.push(Inst::Jump(start));
+                program[start] = split(*greedy, start + 1, program.len());
+
+                return Ok(());
+            }
+
+            let mut splits = Vec::new();
+
+            for _ in *min..*max {
+                splits.push(program.len());
+                program.push(Inst::Jump(0));
+                compile_node(inner, program)?;
+            }
+
+            let end = program.len();
+
+            for at in splits {
+                program[at] = split(*greedy, at + 1, end);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn split(greedy: bool, taken: usize, skipped: usize) -> Inst {
+    if greedy {
+        Inst::Split(taken, skipped)
+    } else {
+        Inst::Split(skipped, taken)
+    }
+}
+
+// runs every thread of the program in step over the text, earlier threads
+// take priority so the match is the one a backtracking engine would find
+struct Runner<'a> {
+    regex: &'a Regex,
+    chars: &'a [char],
+    // the position each instruction was last added at, so a thread is only
+    // added once per position and empty loops end
+    seen: Vec<usize>,
+}
+
+impl Runner<'_> {
+    fn run(&mut self, first: usize) -> Option<(usize, usize)> {
+        let mut current: Vec<(usize, usize)> = Vec::new();
+        let mut next = Vec::new();
+        let mut found = None;
+
+        for pos in first..=self.chars.len() {
+            // a new match may start here until one is found
+            if found.is_none() {
+                self.add(&mut current, 0, pos, pos);
+            }
+
+            if current.is_empty() && found.is_some() {
+                break;
+            }
+
+            for &(pc, start) in &current {
+                let consumes = match &self.regex.program[pc] {
+                    Inst::Match => {
+                        found = Some((start, pos));
+
+                        // the threads after this one are worse matches
+                        break;
+                    }
+                    inst => self
+                        .chars
+                        .get(pos)
+                        .is_some_and(|&ch| self.matches_char(inst, ch)),
+                };
+
+                if consumes {
+                    self.add(&mut next, pc + 1, pos + 1, start);
+                }
+            }
+
+            current.clear();
+            std::mem::swap(&mut current, &mut next);
+        }
+
+        found
+    }
+
+    // follows jumps, splits and assertions to the instructions that match a
+    // character or the whole pattern, in order of preference
+    fn add(&mut self, list: &mut Vec<(usize, usize)>, pc: usize, pos: usize, start: usize) {
+        let mut stack = vec![pc];
+
+        while let Some(pc) = stack.pop() {
+            if self.seen[pc] == pos {
+                continue;
+            }
+
+            self.seen[pc] = pos;
+
+            match &self.regex.program[pc] {
+                Inst::Jump(to) => stack.push(*to),
+                Inst::Split(first, second) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == self.chars.len() => stack.push(pc + 1),
+                Inst::Boundary(at) if self.at_boundary(pos) == *at => stack.push(pc + 1),
+                Inst::Start | Inst::End | Inst::Boundary(_) => {}
+                _ => list.push((pc, start)),
+            }
+        }
+    }
+
+    fn at_boundary(&self, pos: usize) -> bool {
+        let word = |ch: Option<&char>| ch.is_some_and(|&ch| ch.is_alphanumeric() || ch == '_');
+
+        word(pos.checked_sub(1).and_then(|i| self.chars.get(i))) != word(self.chars.get(pos))
+    }
+
+    fn matches_char(&self, inst: &Inst, ch: char) -> bool {
+        match inst {
+            Inst::Char(expected) => self.same_char(*expected, ch),
+            Inst::Any => ch != '\n',
+            Inst::Class(class) => {
+                let (lower, upper) = case_variants(ch);
+                let variants = if self.regex.ignore_case {
+                    vec![ch, lower, upper]
+                } else {
+                    vec![ch]
+                };
+
+                let found = class.items.iter().any(|item| match item {
+                    ClassItem::Range(start, end) => variants
+                        .iter()
+                        .any(|variant| (*start..=*end).contains(variant)),
+                    ClassItem::Digit(positive) => ch.is_ascii_digit() == *positive,
+                    ClassItem::Word(positive) => (ch.is_alphanumeric() || ch == '_') == *positive,
+                    ClassItem::Space(positive) => ch.is_whitespace() == *positive,
+                    ClassItem::Named(test) => variants.iter().any(test),
+                });
+
+                found != class.negated
+            }
+            _ => false,
+        }
+    }
+
+    fn same_char(&self, expected: char, ch: char) -> bool {
+        expected == ch
+            || (self.regex.ignore_case && {
+                let (lower, upper) = case_variants(ch);
+
+                expected == lower || expected == upper
+            })
+    }
+}
+
+fn case_variants(ch: char) -> (char, char) {
+    (
+        ch.to_lowercase().next().unwrap_or(ch),
+        ch.to_uppercase().next().unwrap_or(ch),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn find<'a>(pattern: &str, text: &'a str) -> Option<&'a str> {
+        Regex::new(pattern)
+            .unwrap()
+            .find(text)
+            .map(|(start, end)| &text[start..end])
+    }
+
+    #[test]
+    fn matches_the_everyday_syntax() {
+        assert_eq!(
+            find("git (push|pull)", "run git pull now"),
+            Some("git pull")
+        );
+        assert_eq!(find("a.c", "xabcx"), Some("abc"));
+        assert_eq!(find("[a-c]+", "xxbcaz"), Some("bca"));
+        assert_eq!(find("[^a-c ]+", "abc def"), Some("def"));
+        assert_eq!(find(r"\d{2,3}", "a1234"), Some("123"));
+        assert_eq!(find(r"\w+\s\W", "foo_1 -x"), Some("foo_1 -"));
+        assert_eq!(find("^ab", "abab"), Some("ab"));
+        assert_eq!(find("ab$", "abab"), Some("ab"));
+        assert_eq!(find("^b", "ab"), None);
+        assert_eq!(find("colou?r", "color"), Some("color"));
+        assert_eq!(find("x{2}", "xxx"), Some("xx"));
+        assert_eq!(find("x{2,}", "xxxx"), Some("xxxx"));
+        assert_eq!(find("a{,2}", "a{,2}"), Some("a{,2}"));
+        assert_eq!(find(r"\.\*", "a.*b"), Some(".*"));
+        assert_eq!(find("(?i)TOKEN", "my token"), Some("token"));
+        assert_eq!(find("(?i)[A-Z]+", "abc1"), Some("abc"));
+    }
+
+    #[test]
+    fn prefers_what_a_backtracking_engine_would() {
+        assert_eq!(find("a+", "baaa"), Some("aaa"));
+        assert_eq!(find("a+?", "baaa"), Some("a"));
+        assert_eq!(find("<.*>", "<a><b>"), Some("<a><b>"));
+        assert_eq!(find("<.*?>", "<a><b>"), Some("<a>"));
+        assert_eq!(find("a|ab", "ab"), Some("a"));
+        assert_eq!(find("(a|ab)c", "abc"), Some("abc"));
+        assert_eq!(find("", "abc"), Some(""));
+    }
+
+    #[test]
+    fn knows_word_boundaries_and_posix_classes() {
+        assert_eq!(find(r"\bcat\b", "concat cat"), Some("cat"));
+        assert!(!Regex::new(r"\bcat\b").unwrap().is_match("concat"));
+        assert_eq!(find(r"\Bcat", "concat"), Some("cat"));
+        assert_eq!(find("[[:alpha:]]+", "12abc3"), Some("abc"));
+        assert_eq!(find("[[:digit:][:space:]]+", "ab1 2c"), Some("1 2"));
+        assert_eq!(find("[^[:alnum:]]", "ab-c"), Some("-"));
+        assert_eq!(find("(?i)[[:upper:]]", "abc"), Some("a"));
+    }
+
+    #[test]
+    fn nested_quantifiers_take_linear_time() {
+        let text = "a".repeat(30);
+        let started = Instant::now();
+
+        for pattern in ["(a*)*b", "(a+)+b", "(a|aa)*b", "(.*)*x", "(a?){30}a{30}b"] {
+            assert!(!Regex::new(pattern).unwrap().is_match(&text), "{}", pattern);
+        }
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(find("(a*)*", "aaa"), Some("aaa"));
+        assert_eq!(find("(a*)+b", "aab"), Some("aab"));
+    }
+
+    #[test]
+    fn finds_every_match_left_to_right() {
+        let regex = Regex::new("o+").unwrap();
+
+        assert_eq!(regex.find_all("foo boo o"), [(1, 3), (5, 7), (8, 9)]);
+        assert_eq!(Regex::new("x*").unwrap().find_all("axb"), [(1, 2)]);
+        assert_eq!(
+            Regex::new("é").unwrap().find_all("café é"),
+            [(3, 5), (6, 8)]
+        );
+    }
+
+    #[test]
+    fn refuses_syntax_it_does_not_know() {
+        for pattern in [
+            "(a",
+            "a)",
+            "[ab",
+            "*a",
+            "a**",
+            r"\1",
+            r"\p{L}",
+            r"a\",
+            "(?=a)",
+            "(?s).",
+            "[[:word:]]",
+            "[[=a=]]",
+            "[z-a]",
+            "a{3,1}",
+            "^*",
+            "a{20000}",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,2069 @@
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command as OsCommand, ExitStatus, Output, exit};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::budget;
+use crate::chain::{self, CallStack};
+use crate::checklist;
+use crate::config::{Alias, Config, expand_tilde, load_config, resolve_shell};
+use crate::dotenv;
+use crate::expand;
+use crate::export;
+use crate::history;
+use crate::hooks;
+use crate::index;
+use crate::jobs;
+use crate::json::Json;
+use crate::limits;
+use crate::lock;
+use crate::log::RunLog;
+use crate::notify;
+use crate::output;
+use crate::parallel;
+use crate::pin;
+use crate::policy;
+use crate::priority;
+use crate::record;
+use crate::sandbox;
+use crate::scripts;
+use crate::shims;
+use crate::sys;
+use crate::timeout::{self, Deadline};
+use crate::triage::{self, Choice};
+
+const DEFAULT_CAPTURE_LIMIT: usize = 64 * 1024;
+
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// the special and regular builtins of POSIX sh and the ones dash, bash and
+// zsh add, many also exist as programs in /bin that don't behave the same,
+// like `echo -e`, `kill %1` or `pwd -L`
+const SHELL_BUILTINS: [&str; 60] = [
+    ".", ":", "[", "alias", "bg", "bind", "break", "builtin", "caller", "cd", "command", "compgen",
+    "complete", "continue", "declare", "dirs", "disown", "echo", "enable", "eval", "exec", "exit",
+    "export", "false", "fc", "fg", "getopts", "hash", "help", "history", "jobs", "kill", "let",
+    "local", "logout", "mapfile", "newgrp", "popd", "printf", "pushd", "pwd", "read", "readonly",
+    "return", "set", "shift", "shopt", "source", "suspend", "test", "times", "trap", "true",
+    "type", "typeset", "ulimit", "umask", "unalias", "unset", "wait",
+];
+
+// the environment a `clean_env` alias starts from
+const CLEAN_ENV: [&str; 7] = ["HOME", "LOGNAME", "PATH", "SHELL", "TERM", "TMPDIR", "USER"];
+
+#[derive(Args, Clone)]
+pub struct RunArgs {
+    #[arg(required = true)]
+    pub alias: String,
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub args: Vec<String>,
+    #[arg(long, hide = true)]
+    pub internal: bool,
+    // the alias is given by the id `pintas export` put in launcher files
+    #[arg(long)]
+    pub id: bool,
+    #[arg(long)]
+    pub restricted: bool,
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    #[arg(long)]
+    pub capture_json: bool,
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_CAPTURE_LIMIT, requires = "capture_json")]
+    pub capture_limit: usize,
+    #[arg(long, conflicts_with = "capture_json")]
+    pub record: bool,
+    // stdout and stderr also go to this file
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["capture_json", "record"])]
+    pub tee: Option<PathBuf>,
+    #[arg(long)]
+    pub notify: bool,
+    #[arg(long, conflicts_with_all = ["capture_json", "record"])]
+    pub detach: bool,
+    // prints what would run instead of running it
+    #[arg(long, conflicts_with_all = ["capture_json", "record", "tee", "detach"])]
+    pub dry_run: bool,
+}
+
+pub fn run_alias(mut run: RunArgs) -> Result<()> {
+    let internal = run.internal;
+
+    // shims only need the one alias, which the index serves without parsing
+    // the whole config
+    let loaded = if internal {
+        index::load_alias(&run.alias)
+    } else {
+        load_config()
+    };
+
+    let config = match loaded {
+        Ok(cfg) => cfg,
+        Err(_) if internal && shims::shared_shims_dir().is_some() => {
+            shims::run_shadowed(&run.alias, &run.args)
+        }
+        Err(_) if internal => exit(126), // config not found, so alias can't exist
+        Err(e) => return Err(e).context("Failed to load pintas config"),
+    };
+
+    if run.id {
+        run.alias = export::alias_for_id(&config, &run.alias)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No alias has id '{}', export the launcher files again.",
+                    run.alias
+                )
+            })?
+            .to_string();
+    }
+
+    if let Some(owner) = config.aka_owner(&run.alias) {
+        run.alias = owner.to_string();
+    }
+
+    if !config.aliases.contains_key(&run.alias) {
+        if internal {
+            // the shim was provisioned for someone else's alias
+            if shims::shared_shims_dir().is_some() {
+                shims::run_shadowed(&run.alias, &run.args);
+            }
+
+            if shims::is_tombstone(&run.alias) {
+                eprintln!(
+                    "pintas: alias '{}' has been removed — run 'pintas prune' to delete this shim",
+                    run.alias
+                );
+            }
+
+            exit(126); // alias not found
+        }
+
+        return Err(anyhow!("Alias '{}' not found.", run.alias));
+    }
+
+    if !config.aliases[&run.alias].has_tags(&run.tags) {
+        return Err(anyhow!(
+            "Alias '{}' isn't tagged {}.",
+            run.alias,
+            run.tags.join(", ")
+        ));
+    }
+
+    if run.record {
+        let status = record::run_recorded(&run)?;
+
+        // the recorded pintas has already reported the failure
+        if !status.success() {
+            exit(sys::exit_code(&status));
+        }
+
+        return Ok(());
+    }
+
+    if run.detach {
+        return jobs::run_detached(&run);
+    }
+
+    let (name, used) = config.dispatch(&run.alias, &run.args);
+
+    run.alias = name;
+    run.args.drain(..used);
+
+    chain::check_cycles(&config, &run.alias)?;
+
+    if run.dry_run {
+        let mut runner = Runner {
+            restricted: run.restricted,
+            ..Runner::new(&config)
+        };
+
+        return runner.dry_run(&run.alias, &run.args, 0);
+    }
+
+    budget::check(&config, &run.alias)?;
+
+    let mut runner = Runner {
+        restricted: run.restricted,
+        captured: run.capture_json,
+        tee: run
+            .tee
+            .as_deref()
+            .map(RunLog::create)
+            .transpose()?
+            .map(Arc::new),
+        ..Runner::new(&config)
+    };
+
+    hooks::before_run(&config, &run.alias, run.capture_json)?;
+
+    if run.capture_json {
+        return runner.run_captured(&run);
+    }
+
+    // a shim with nothing left to do once the command ends hands its process
+    // over to it, so signals and the exit status are the command's own and
+    // no pintas lingers as its parent
+    if internal
+        && !run.notify
+        && run.tee.is_none()
+        && config
+            .lookup(&run.alias)
+            .is_some_and(|alias| can_exec(&config, alias))
+    {
+        return runner.exec(&run.alias, &run.args);
+    }
+
+    let started = Instant::now();
+    let mut status = runner.run(&run.alias, &run.args, !internal)?;
+    let duration = started.elapsed();
+
+    // a sub-alias isn't a key of its own, `g push` is found through `g`
+    let separator = if config
+        .lookup(&run.alias)
+        .is_some_and(|alias| alias.parallel)
+    {
+        " & "
+    } else {
+        " && "
+    };
+
+    history::record(
+        &config,
+        &run.alias,
+        &run.args,
+        &runner.expansion.join(separator),
+        duration,
+        &status,
+    );
+    hooks::after_run(&config, &run.alias, duration, &status, false);
+
+    // before the triage menu, which waits for whoever the notification
+    // calls back
+    if run.notify || config.lookup(&run.alias).is_some_and(|alias| alias.notify) {
+        notify::send(&run.alias, duration, &status);
+    }
+
+    if !status.success()
+        && config
+            .lookup(&run.alias)
+            .is_some_and(|alias| triage::wanted(alias, run.capture_json))
+    {
+        status = runner.triage(&run.alias, &run.args, status)?;
+    }
+
+    if internal {
+        exit(sys::exit_code(&status));
+    }
+
+    if !status.success() {
+        return Err(anyhow!(
+            "Command finished with an error (exit code: {})\n",
+            status
+        ));
+    }
+
+    Ok(())
+}
+
+// a pintas of its own for `run --record` and `run --detach`, with the flags
+// that still apply to the run
+pub fn rerun_command(run: &RunArgs) -> Result<OsCommand> {
+    let mut cmd =
+        OsCommand::new(env::current_exe().context("Failed to get current executable path")?);
+
+    cmd.arg("run");
+
+    if run.restricted {
+        cmd.arg("--restricted");
+    }
+
+    if run.notify {
+        cmd.arg("--notify");
+    }
+
+    if let Some(tee) = &run.tee {
+        cmd.arg("--tee").arg(tee);
+    }
+
+    for tag in &run.tags {
+        cmd.arg("--tag").arg(tag);
+    }
+
+    cmd.arg(&run.alias).args(&run.args);
+
+    Ok(cmd)
+}
+
+// `pintas which`: what running the alias would run, after placeholders,
+// platform variants and sub-aliases are resolved and with the aliases it
+// runs by reference, and what typing its name in a shell starts
+pub fn run_which(config: &Config, alias: &str, args: &[String]) -> Result<()> {
+    let alias = config.aka_owner(alias).unwrap_or(alias);
+
+    if !config.aliases.contains_key(alias) {
+        return Err(anyhow!("Alias '{}' not found.", alias));
+    }
+
+    let (name, used) = config.dispatch(alias, args);
+
+    chain::check_cycles(config, &name)?;
+
+    let mut runner = Runner::new(config);
+
+    runner.which(&name, &args[used..], 0)?;
+
+    let Some(shim) = shims::shim_path(config, alias)? else {
+        println!(
+            "Alias '{}' has no shim, it only runs with 'pintas run {}'.",
+            alias, alias
+        );
+
+        return Ok(());
+    };
+
+    let programs = sys::find_programs(&shims::shim_name(alias));
+    let is_shim = |path: &Path| path.parent() == shim.parent();
+
+    match programs.first() {
+        Some(first) if is_shim(first) => println!(
+            "Typing '{}' runs its shim '{}'.",
+            shims::shim_name(alias),
+            first.to_string_lossy()
+        ),
+        Some(first) => println!(
+            "Warning: typing '{}' runs '{}', which comes before the shim in PATH.",
+            shims::shim_name(alias),
+            first.to_string_lossy()
+        ),
+        None => println!(
+            "The shim '{}' isn't on PATH, see 'pintas init bash'.",
+            shim.to_string_lossy()
+        ),
+    }
+
+    for shadowed in programs.iter().skip_while(|path| !is_shim(path)).skip(1) {
+        println!("It shadows '{}'.", shadowed.to_string_lossy());
+    }
+
+    Ok(())
+}
+
+// `pintas expand`: the command an alias runs as one line of shell for
+// `eval "$(pintas expand foo)"`, and nothing else on stdout, arguments left
+// after the placeholders become the positional parameters of a function
+// around it
+pub fn run_expand(config: &Config, alias: &str, args: &[String]) -> Result<()> {
+    let alias = config.aka_owner(alias).unwrap_or(alias);
+
+    if !config.aliases.contains_key(alias) {
+        return Err(anyhow!("Alias '{}' not found.", alias));
+    }
+
+    let (name, used) = config.dispatch(alias, args);
+    let runner = Runner::new(config);
+    let (alias, commands, command_args) = runner.resolve(&name, &args[used..])?;
+
+    if !alias.deps.is_empty() || !alias.checklist.is_empty() {
+        return Err(anyhow!(
+            "Alias '{}' {}, which 'pintas expand' can't turn into one command, use 'pintas run' instead.",
+            name,
+            if alias.deps.is_empty() {
+                "is a checklist"
+            } else {
+                "has deps"
+            }
+        ));
+    }
+
+    let options = run_options(alias);
+
+    if !options.is_empty() {
+        return Err(anyhow!(
+            "Alias '{}' sets {}, which 'pintas expand' can't put into one command, use 'pintas run' instead.",
+            name,
+            options.join(", ")
+        ));
+    }
+
+    let mut lines = Vec::new();
+
+    for command in &commands {
+        if command.alias_refs().is_some() {
+            return Err(anyhow!(
+                "Alias '{}' runs other aliases, which 'pintas expand' can't turn into one command, use 'pintas run' instead.",
+                name
+            ));
+        }
+
+        let argv = match command {
+            Prepared::Shell(command) if interpreter(config, alias, command).posix => {
+                lines.push(command.trim_end().to_string());
+                continue;
+            }
+            Prepared::Shell(command) => {
+                let interpreter = interpreter(config, alias, command);
+
+                iter::once(interpreter.program)
+                    .chain(interpreter.args)
+                    .chain([interpreter.flag, command])
+                    .map(String::from)
+                    .collect()
+            }
+            Prepared::Argv(argv) => argv.clone(),
+        };
+
+        lines.push(expand::join_argv(&[argv, command_args.clone()].concat()));
+    }
+
+    let separator = if alias.parallel { " &\n" } else { " &&\n" };
+    let mut expanded = lines.join(separator);
+
+    if alias.parallel && lines.len() > 1 {
+        expanded.push_str(" &\nwait");
+    }
+
+    let posix_args = commands.iter().any(
+        |command| matches!(command, Prepared::Shell(command) if interpreter(config, alias, command).posix),
+    );
+
+    // run in a function so the arguments, or none, are its own positional
+    // parameters and the caller's stay as they were, while a `cd` or
+    // `export` still changes the shell that evals it
+    if posix_args {
+        expanded = format!(
+            "{function}() {{\nunset -f {function}\n{}\n}}\n{function}{}{}",
+            expanded,
+            if command_args.is_empty() { "" } else { " " },
+            expand::join_argv(&command_args),
+            function = EXPAND_FUNCTION
+        );
+    }
+
+    println!("{}", expanded);
+
+    Ok(())
+}
+
+const EXPAND_FUNCTION: &str = "__pintas_expand";
+
+// the options of an alias that change how its commands run rather than what
+// they are, which `pintas expand` would drop
+fn run_options(alias: &Alias) -> Vec<&'static str> {
+    [
+        ("cwd", alias.cwd.is_some()),
+        ("env", !alias.env.is_empty()),
+        ("env_file", alias.env_file.is_some()),
+        ("clean_env", alias.clean_env),
+        ("locale", alias.locale.is_some()),
+        ("tz", alias.tz.is_some()),
+        ("proxy", alias.proxy.is_some()),
+        ("no_proxy", alias.no_proxy.is_some()),
+        ("no_network", alias.no_network),
+        ("sandbox", alias.sandbox.is_some()),
+        ("limits", alias.limits.is_some()),
+        ("nice", alias.nice.is_some()),
+        ("ionice", alias.ionice.is_some()),
+        ("timeout", alias.timeout.is_some()),
+        ("retries", alias.retries > 0),
+        ("pre", alias.pre.is_some()),
+        ("post", alias.post.is_some()),
+        ("cleanup", alias.cleanup.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(option, _)| option)
+    .collect()
+}
+
+// a command ready to run, argv commands are executed without a shell
+enum Prepared {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+impl Prepared {
+    fn alias_refs(&self) -> Option<Vec<&str>> {
+        match self {
+            Prepared::Shell(command) => chain::alias_refs(command),
+            Prepared::Argv(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Prepared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Prepared::Shell(command) => write!(f, "{}", command),
+            Prepared::Argv(argv) => write!(f, "{}", expand::join_argv(argv)),
+        }
+    }
+}
+
+struct Runner<'a> {
+    config: &'a Config,
+    restricted: bool,
+    captured: bool,
+    stack: CallStack,
+    // the exit code a post hook receives as PINTAS_EXIT_CODE
+    post_exit_code: Option<i32>,
+    deadline: Option<Deadline>,
+    // shell commands run with `-x`, for a re-run from the triage menu
+    verbose: bool,
+    // the commands the alias that was run first expanded to
+    expansion: Vec<String>,
+    // `run --tee`, for the alias and every alias it runs
+    tee: Option<Arc<RunLog>>,
+}
+
+impl<'a> Runner<'a> {
+    // a runner for a plain run of the alias, the callers set what differs
+    fn new(config: &'a Config) -> Runner<'a> {
+        Runner {
+            config,
+            restricted: false,
+            captured: false,
+            stack: CallStack::default(),
+            post_exit_code: None,
+            deadline: None,
+            verbose: false,
+            expansion: Vec::new(),
+            tee: None,
+        }
+    }
+
+    fn resolve(
+        &self,
+        name: &str,
+        args: &[String],
+    ) -> Result<(&'a Alias, Vec<Prepared>, Vec<String>)> {
+        let alias = self
+            .config
+            .lookup(name)
+            .ok_or_else(|| anyhow!("Alias '{}' not found.", name))?;
+
+        if let Some(reason) = alias.unavailable_reason() {
+            return Err(anyhow!(
+                "Alias '{}' is not available here: {}.",
+                name,
+                reason
+            ));
+        }
+
+        let missing = alias.missing_programs();
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Alias '{}' needs programs that aren't installed or not on PATH: {}. Install them or add their directory to PATH.",
+                name,
+                missing.join(", ")
+            ));
+        }
+
+        pin::check(name, alias)?;
+        priority::check(name, alias)?;
+        limits::check(name, alias)?;
+        sandbox::check(name, alias)?;
+
+        if alias.no_network && alias.sandbox.is_none() && !sys::can_isolate_network() {
+            eprintln!(
+                "Warning: alias '{}' asks for no_network, but network isolation isn't available here. Running it with network access.",
+                name
+            );
+        }
+
+        // the same alias can open a TUI interactively and do the plain thing
+        // from cron or a pipe
+        let headless = alias.requires_tty && (self.captured || !sys::is_interactive());
+
+        // a script file is run directly so its shebang picks the interpreter
+        if let Some(path) = scripts::script_path(alias).filter(|_| !headless) {
+            let path = path?;
+            let (_, args) = self.prepare(name, alias, &[], args, true)?;
+
+            if !path.is_file() {
+                return Err(anyhow!(
+                    "Alias '{}' points at script '{}' which doesn't exist.",
+                    name,
+                    path.to_string_lossy()
+                ));
+            }
+
+            if self.restricted {
+                let content = fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read script '{}'", path.to_string_lossy())
+                })?;
+
+                policy::check_restricted(name, &content)?;
+            }
+
+            let command = Prepared::Argv(vec![path.to_string_lossy().into_owned()]);
+
+            policy::check_blocked(self.config, name, &command.to_string(), &args)?;
+
+            return Ok((alias, vec![command], args));
+        }
+
+        if let Some(argv) = alias.argv().filter(|_| !headless) {
+            let templates: Vec<&str> = argv.iter().map(String::as_str).collect();
+            let (argv, args) = self.prepare(name, alias, &templates, args, true)?;
+
+            if argv.is_empty() {
+                return Err(anyhow!("Alias '{}' has an empty argv command.", name));
+            }
+
+            let command = Prepared::Argv(argv);
+
+            if self.restricted {
+                policy::check_restricted(name, &command.to_string())?;
+            }
+
+            policy::check_blocked(self.config, name, &command.to_string(), &args)?;
+
+            return Ok((alias, vec![command], args));
+        }
+
+        let commands = if headless {
+            let headless = alias.headless.as_deref().ok_or_else(|| {
+                anyhow!(
+                    "Alias '{}' needs a terminal and has no headless command.",
+                    name
+                )
+            })?;
+
+            vec![headless]
+        } else {
+            alias.commands().ok_or_else(|| {
+                anyhow!(
+                    "Alias '{}' has no command for this platform ({}).",
+                    name,
+                    env::consts::OS
+                )
+            })?
+        };
+
+        let (mut commands, args) = self.prepare(name, alias, &commands, args, false)?;
+
+        for command in &mut commands {
+            if self.restricted {
+                policy::check_restricted(name, command)?;
+            }
+
+            if self.safe_args(alias) && interpreter(self.config, alias, command).posix {
+                policy::check_safe_args(name, command)?;
+            }
+
+            // forwards the arguments to simple aliases like `gs = "git status"`,
+            // scripts and other interpreters have them in their argv anyway
+            if alias.append_args
+                && !command.contains('\n')
+                && interpreter(self.config, alias, command).posix
+            {
+                command.push_str(" \"$@\"");
+            }
+
+            policy::check_blocked(self.config, name, command, &args)?;
+        }
+
+        Ok((
+            alias,
+            commands.into_iter().map(Prepared::Shell).collect(),
+            args,
+        ))
+    }
+
+    // returns the expanded templates and the arguments left after the named
+    // params were taken out, argument values are quoted for a POSIX shell
+    // and inserted as they are into argv words and other interpreters
+    fn prepare(
+        &self,
+        name: &str,
+        alias: &Alias,
+        templates: &[&str],
+        args: &[String],
+        argv: bool,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let (params, args) = expand::split_params(&alias.params, args)?;
+
+        if let Some(usage) = alias.arity_error(name, args.len()) {
+            return Err(anyhow!(usage));
+        }
+
+        // a value asked for once is reused by every step
+        let mut answers: HashMap<String, String> = HashMap::new();
+        let mut ask = |placeholder: &str| -> Result<String> {
+            if let Some(answer) = answers.get(placeholder) {
+                return Ok(answer.clone());
+            }
+
+            let answer = expand::prompt(placeholder, &prompt_message(alias, placeholder))?;
+
+            answers.insert(placeholder.to_string(), answer.clone());
+
+            Ok(answer)
+        };
+
+        let mut prepared = Vec::new();
+
+        for template in templates {
+            let quote = !argv && interpreter(self.config, alias, template).posix;
+            let template = expand::expand_vars(template, &self.config.vars);
+            let expanded = expand::expand_placeholders(
+                &template,
+                |placeholder, argument, filters, quoting| {
+                    // quoted for where it sits so the value stays a single word in
+                    // the shell command, a directory or branch can hold a `;` too
+                    let value =
+                        expand::named(&params, &alias.params, placeholder, argument, &mut ask)
+                            .or_else(|| expand::positional(&args, placeholder, argument, &mut ask))
+                            .or_else(|| output::placeholder(placeholder, argument))
+                            .or_else(|| expand::var(&self.config.vars, placeholder, argument))
+                            .or_else(|| expand::builtin(placeholder, argument))?
+                            .and_then(|value| expand::apply_filters(value, filters));
+
+                    match value {
+                        Ok(value) if quote => Some(Ok(expand::quote_for(&value, quoting))),
+                        value => Some(value),
+                    }
+                },
+            )?;
+
+            prepared.push(expanded);
+        }
+
+        Ok((prepared, args))
+    }
+
+    // asks what to do about a failed run until it is left or a re-run
+    // succeeds, returns the status of the last run
+    fn triage(
+        &mut self,
+        name: &str,
+        args: &[String],
+        mut status: ExitStatus,
+    ) -> Result<ExitStatus> {
+        let config = self.config;
+        let alias = config
+            .lookup(name)
+            .ok_or_else(|| anyhow!("Alias '{}' not found.", name))?;
+
+        loop {
+            match triage::ask(name, &status)? {
+                Choice::Rerun => {
+                    self.verbose = true;
+                    self.expansion.clear();
+                    status = self.run(name, args, true)?;
+
+                    if status.success() {
+                        return Ok(status);
+                    }
+                }
+                Choice::Output => triage::open_output(name, alias)?,
+                Choice::Edit => {
+                    triage::edit(name, alias)?;
+
+                    return Ok(status);
+                }
+                Choice::Explain => triage::explain(name, &self.expansion),
+                Choice::Quit => return Ok(status),
+            }
+        }
+    }
+
+    fn which(&mut self, name: &str, args: &[String], depth: usize) -> Result<()> {
+        self.stack.enter(name)?;
+
+        let result = self.which_entered(name, args, depth);
+
+        self.stack.leave();
+        result
+    }
+
+    // deps and references are shown where they run, nested under the alias
+    // that runs them
+    fn which_entered(&mut self, name: &str, args: &[String], depth: usize) -> Result<()> {
+        let (alias, commands, command_args) = self.resolve(name, args)?;
+        let indent = "  ".repeat(depth);
+
+        println!("{}{}", indent, name);
+
+        for dep in &alias.deps {
+            println!("{}  needs:", indent);
+            self.which(dep, &[], depth + 2)?;
+        }
+
+        if let Some(pre) = &alias.pre {
+            println!("{}  pre: {}", indent, pre);
+        }
+
+        for command in &commands {
+            let label = match command {
+                Prepared::Shell(command) => interpreter(self.config, alias, command).program,
+                Prepared::Argv(_) => "exec",
+            };
+
+            println!(
+                "{}  {}: {}",
+                indent,
+                label,
+                command
+                    .to_string()
+                    .trim_end()
+                    .replace('\n', &format!("\n{}    ", indent))
+            );
+
+            for target in command.alias_refs().unwrap_or_default() {
+                self.which(target, &[], depth + 2)?;
+            }
+        }
+
+        if !command_args.is_empty() {
+            println!("{}  args: {}", indent, expand::join_argv(&command_args));
+        }
+
+        if let Some(post) = &alias.post {
+            println!("{}  post: {}", indent, post);
+        }
+
+        if let Some(cleanup) = &alias.cleanup {
+            println!("{}  cleanup: {}", indent, cleanup);
+        }
+
+        Ok(())
+    }
+
+    fn dry_run(&mut self, name: &str, args: &[String], depth: usize) -> Result<()> {
+        self.stack.enter(name)?;
+
+        let result = self.dry_run_entered(name, args, depth);
+
+        self.stack.leave();
+        result
+    }
+
+    // `run --dry-run`: every program as it would be executed, with its
+    // working directory and the variables the alias sets, laid out like
+    // `pintas which`
+    fn dry_run_entered(&mut self, name: &str, args: &[String], depth: usize) -> Result<()> {
+        let (alias, commands, command_args) = self.resolve(name, args)?;
+        let indent = "  ".repeat(depth);
+
+        println!("{}{}", indent, name);
+
+        for dep in &alias.deps {
+            println!("{}  needs:", indent);
+            self.dry_run(dep, &[], depth + 2)?;
+        }
+
+        if let Some(pre) = &alias.pre {
+            println!("{}  pre: {}", indent, pre);
+        }
+
+        for command in &commands {
+            if let Some(targets) = command.alias_refs() {
+                println!("{}  runs: {}", indent, command);
+
+                for target in targets {
+                    self.dry_run(target, &[], depth + 2)?;
+                }
+
+                continue;
+            }
+
+            let cmd = self.build_command(name, alias, command, &command_args)?;
+
+            if let Some(cwd) = cmd.get_current_dir() {
+                println!("{}  cwd: {}", indent, cwd.to_string_lossy());
+            }
+
+            if alias.clean_env {
+                println!("{}  env: cleared", indent);
+            }
+
+            let mut vars: Vec<_> = cmd
+                .get_envs()
+                .filter_map(|(var, value)| Some((var.to_string_lossy(), value?.to_string_lossy())))
+                .collect();
+
+            vars.sort();
+
+            for (var, value) in vars {
+                println!("{}  env: {}={}", indent, var, value);
+            }
+
+            let argv: Vec<String> = iter::once(cmd.get_program())
+                .chain(cmd.get_args())
+                .map(|word| word.to_string_lossy().into_owned())
+                .collect();
+
+            println!(
+                "{}  exec: {}",
+                indent,
+                expand::join_argv(&argv).replace('\n', &format!("\n{}    ", indent))
+            );
+        }
+
+        if let Some(post) = &alias.post {
+            println!("{}  post: {}", indent, post);
+        }
+
+        if let Some(cleanup) = &alias.cleanup {
+            println!("{}  cleanup: {}", indent, cleanup);
+        }
+
+        Ok(())
+    }
+
+    // only returns if the command couldn't be executed
+    fn exec(&mut self, name: &str, args: &[String]) -> Result<()> {
+        self.stack.enter(name)?;
+
+        let (alias, commands, command_args) = self.resolve(name, args)?;
+        let command = commands
+            .first()
+            .ok_or_else(|| anyhow!("Alias '{}' has no command to run.", name))?;
+        let mut cmd = self.build_command(name, alias, command, &command_args)?;
+
+        history::mark_used(name, None);
+
+        Err(cmd.exec()).context("Failed to execute command")
+    }
+
+    fn safe_args(&self, alias: &Alias) -> bool {
+        alias.safe_args || self.config.settings.safe_args
+    }
+
+    fn run(&mut self, name: &str, args: &[String], announce: bool) -> Result<ExitStatus> {
+        self.stack.enter(name)?;
+
+        let result = self.run_entered(name, args, announce);
+
+        self.stack.leave();
+        result
+    }
+
+    fn run_entered(&mut self, name: &str, args: &[String], announce: bool) -> Result<ExitStatus> {
+        let (alias, commands, command_args) = self.resolve(name, args)?;
+        let _lock = lock::acquire(name, alias)?;
+
+        if self.expansion.is_empty() {
+            self.expansion = commands.iter().map(Prepared::to_string).collect();
+        }
+
+        // parallel steps need pintas alive to forward their output
+        let status = {
+            let _guard = (alias.cleanup.is_some() || alias.post.is_some() || alias.parallel)
+                .then(sys::SignalGuard::install);
+
+            match self.run_deps(name, alias, announce) {
+                Ok(None) => self.run_main(name, alias, &commands, &command_args, args, announce),
+                Ok(Some((_, status))) => Ok(status),
+                Err(e) => Err(e),
+            }
+        };
+
+        self.run_cleanup(name, alias, args);
+
+        status
+    }
+
+    // a `timeout` bounds the steps of an alias and the aliases they run, a
+    // nested one can only make the deadline earlier, returns the deadline to
+    // restore afterwards
+    fn start_timeout(&mut self, name: &str, alias: &Alias) -> Result<Option<Deadline>> {
+        let outer = self.deadline.clone();
+
+        let Some(limit) = &alias.timeout else {
+            return Ok(outer);
+        };
+
+        let duration = timeout::parse(limit).ok_or_else(|| {
+            anyhow!(
+                "Alias '{}' has an invalid timeout '{}', expected a duration like 30s, 5m or 1h.",
+                name,
+                limit
+            )
+        })?;
+        let at = Instant::now() + duration;
+
+        if outer.as_ref().is_none_or(|outer| at < outer.at) {
+            self.deadline = Some(Deadline {
+                at,
+                alias: name.to_string(),
+                limit: limit.clone(),
+            });
+        }
+
+        Ok(outer)
+    }
+
+    // `retries` runs the steps again after a failure, waiting `retry_delay`
+    // in between and twice as long after every attempt with `backoff`, a run
+    // interrupted with Ctrl-C isn't retried
+    fn retry<T>(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        mut attempt: impl FnMut(&mut Self) -> Result<T>,
+        status_of: impl Fn(&T) -> ExitStatus,
+    ) -> Result<T> {
+        let attempts = alias.retries.saturating_add(1);
+        let mut delay = match &alias.retry_delay {
+            Some(delay) => timeout::parse(delay).ok_or_else(|| {
+                anyhow!(
+                    "Alias '{}' has an invalid retry_delay '{}', expected a duration like 500ms, 2s or 1m.",
+                    name,
+                    delay
+                )
+            })?,
+            None => DEFAULT_RETRY_DELAY,
+        };
+        let mut codes = Vec::new();
+
+        loop {
+            let result = attempt(self)?;
+            let status = status_of(&result);
+            let interrupted = matches!(sys::exit_code(&status), 130 | 143);
+
+            codes.push(sys::exit_code(&status).to_string());
+
+            if status.success() || interrupted || codes.len() as u32 >= attempts {
+                if codes.len() > 1 && status.success() {
+                    eprintln!(
+                        "Alias '{}' succeeded on attempt {}/{} (exit codes: {}).",
+                        name,
+                        codes.len(),
+                        attempts,
+                        codes.join(", ")
+                    );
+                } else if codes.len() > 1 {
+                    eprintln!(
+                        "Alias '{}' failed after {} attempts (exit codes: {}).",
+                        name,
+                        codes.len(),
+                        codes.join(", ")
+                    );
+                }
+
+                return Ok(result);
+            }
+
+            eprintln!(
+                "Attempt {}/{} of alias '{}' failed (exit code: {}), retrying in {}s.",
+                codes.len(),
+                attempts,
+                name,
+                sys::exit_code(&status),
+                delay.as_secs_f64()
+            );
+
+            thread::sleep(delay);
+
+            if alias.backoff {
+                delay = delay.saturating_mul(2);
+            }
+        }
+    }
+
+    // the pre hook, the steps and the post hook, which only runs when the
+    // steps did
+    fn run_main(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        command_args: &[String],
+        args: &[String],
+        announce: bool,
+    ) -> Result<ExitStatus> {
+        if let Some(status) = self.run_pre(name, alias, args)? {
+            return Ok(status);
+        }
+
+        let status = self.retry(
+            name,
+            alias,
+            |runner| {
+                let outer = runner.start_timeout(name, alias)?;
+                let log = RunLog::open(name, alias, command_args)?;
+                let tee = runner.tee.clone();
+                let logs: Vec<&RunLog> = log.iter().chain(tee.as_deref()).collect();
+                let started = Instant::now();
+                let status = runner.run_steps(name, alias, commands, command_args, announce, &logs);
+
+                if let Some(log) = &log {
+                    log.finish(&status, started.elapsed());
+                }
+
+                runner.deadline = outer;
+                status
+            },
+            |status| *status,
+        );
+
+        if let Ok(status) = &status {
+            self.run_post(name, alias, args, status);
+        }
+
+        status
+    }
+
+    // deps run before the alias itself and take no arguments, returns the
+    // status of the first one that failed
+    fn run_deps<'b>(
+        &mut self,
+        name: &str,
+        alias: &'b Alias,
+        announce: bool,
+    ) -> Result<Option<(&'b str, ExitStatus)>> {
+        for dep in &alias.deps {
+            let status = self.run(dep, &[], announce)?;
+
+            if !status.success() {
+                eprintln!(
+                    "Dependency '{}' of alias '{}' failed (exit code: {}).",
+                    dep,
+                    name,
+                    sys::exit_code(&status)
+                );
+
+                return Ok(Some((dep, status)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // a step made of `pintas:<alias>` references runs those aliases in turn
+    fn run_refs(&mut self, targets: &[&str], announce: bool) -> Result<ExitStatus> {
+        let mut status = ExitStatus::default();
+
+        for target in targets {
+            status = self.run(target, &[], announce)?;
+
+            if !status.success() {
+                break;
+            }
+        }
+
+        Ok(status)
+    }
+
+    // steps run one after the other and stop at the first failure, like
+    // joining them with `&&`
+    fn run_steps(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        args: &[String],
+        announce: bool,
+        logs: &[&RunLog],
+    ) -> Result<ExitStatus> {
+        if !alias.checklist.is_empty() {
+            return self.run_checklist(name, alias, commands, args);
+        }
+
+        if alias.parallel && commands.len() > 1 {
+            return self.run_parallel(name, alias, commands, args, announce, logs);
+        }
+
+        let mut last = None;
+        let mut kept = Vec::new();
+
+        for (i, command) in commands.iter().enumerate() {
+            if announce && commands.len() > 1 {
+                println!("Executing step {}/{}: '{}'", i + 1, commands.len(), command);
+            } else if announce {
+                println!("Executing command: '{}'", command);
+            }
+
+            let status = match command.alias_refs() {
+                Some(targets) => self.run_refs(&targets, announce)?,
+                None if alias.keep_output || !logs.is_empty() => {
+                    let mut cmd = self.build_command(name, alias, command, args)?;
+                    let (status, output) =
+                        output::tee(&mut cmd, self.captured, self.deadline.as_ref(), logs)?;
+
+                    kept.extend(output);
+                    status
+                }
+                None => self.run_command(name, alias, command, args)?,
+            };
+
+            if alias.keep_output {
+                output::save(&kept)?;
+            }
+
+            if !status.success() && commands.len() > 1 {
+                eprintln!(
+                    "Step {}/{} of alias '{}' failed (exit code: {}): '{}'",
+                    i + 1,
+                    commands.len(),
+                    name,
+                    sys::exit_code(&status),
+                    command
+                );
+
+                return Ok(status);
+            }
+
+            last = Some(status);
+        }
+
+        Ok(last.unwrap_or_default())
+    }
+
+    // walks through a checklist one step at a time, asking before each one,
+    // and records what became of every step
+    fn run_checklist(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        args: &[String],
+    ) -> Result<ExitStatus> {
+        if !sys::is_interactive() {
+            return Err(anyhow!(
+                "Alias '{}' is a checklist and needs a terminal to ask about each step.",
+                name
+            ));
+        }
+
+        let mut record = checklist::Record::open(name)?;
+        let mut commands = commands.iter();
+        let total = alias.checklist.len();
+        let mut done = 0;
+
+        for (i, step) in alias.checklist.iter().enumerate() {
+            println!("Step {}/{}: {}", i + 1, total, step.description);
+
+            let command = step.cmd.as_ref().and_then(|_| commands.next());
+            let mut failure = None;
+
+            let result = match command {
+                Some(command) => {
+                    println!("  $ {}", command);
+
+                    let mut question = "Run, skip or abort? [r/s/a]";
+
+                    loop {
+                        match checklist::ask(question, "rsa")? {
+                            's' => break "skipped",
+                            'a' => break "aborted",
+                            _ => {}
+                        }
+
+                        let status = match command.alias_refs() {
+                            Some(targets) => self.run_refs(&targets, false)?,
+                            None => self.run_command(name, alias, command, args)?,
+                        };
+
+                        if status.success() {
+                            break "done";
+                        }
+
+                        eprintln!(
+                            "Step {}/{} of alias '{}' failed (exit code: {}).",
+                            i + 1,
+                            total,
+                            name,
+                            sys::exit_code(&status)
+                        );
+
+                        failure = Some(status);
+                        question = "Retry, skip or abort? [r/s/a]";
+                    }
+                }
+                None => match checklist::ask("Done, skip or abort? [d/s/a]", "dsa")? {
+                    'd' => "done",
+                    's' => "skipped",
+                    _ => "aborted",
+                },
+            };
+
+            let recorded = match failure {
+                Some(status) => {
+                    format!("{} (failed, exit code {})", result, sys::exit_code(&status))
+                }
+                None => result.to_string(),
+            };
+
+            record.step(i + 1, total, &step.description, &recorded)?;
+
+            if result == "done" {
+                done += 1;
+            }
+
+            if result == "aborted" {
+                println!("Checklist '{}' aborted at step {}/{}.", name, i + 1, total);
+
+                return Ok(failure.unwrap_or(sys::exit_status(1)));
+            }
+        }
+
+        println!(
+            "Checklist '{}' finished: {} of {} steps done.",
+            name, done, total
+        );
+
+        Ok(ExitStatus::default())
+    }
+
+    fn build_command(
+        &self,
+        name: &str,
+        alias: &Alias,
+        command: &Prepared,
+        args: &[String],
+    ) -> Result<OsCommand> {
+        let mut cmd = build_command(self.config, name, alias, command, args, self.verbose)?;
+
+        if let Some(code) = self.post_exit_code {
+            cmd.env("PINTAS_EXIT_CODE", code.to_string());
+        }
+
+        // the group is what gets killed when the deadline passes
+        if self.deadline.is_some() {
+            cmd.process_group(0);
+        }
+
+        Ok(cmd)
+    }
+
+    fn run_command(
+        &self,
+        name: &str,
+        alias: &Alias,
+        command: &Prepared,
+        args: &[String],
+    ) -> Result<ExitStatus> {
+        let mut cmd = self.build_command(name, alias, command, args)?;
+
+        // nested runs must keep stdout clean for the JSON envelope
+        if self.captured {
+            cmd.stdout(io::stderr());
+        }
+
+        timeout::status(&mut cmd, self.deadline.as_ref())
+    }
+
+    // the output of every step that ran, up to and including the first
+    // failure, aliases run by reference print to stderr like hooks do
+    fn capture_steps(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        command_args: &[String],
+        args: &[String],
+    ) -> Result<Vec<(String, Output)>> {
+        if let Some((dep, status)) = self.run_deps(name, alias, false)? {
+            return Ok(vec![(format!("pintas:{}", dep), empty_output(status))]);
+        }
+
+        if let Some(status) = self.run_pre(name, alias, args)? {
+            let pre = alias.pre.clone().unwrap_or_default();
+
+            return Ok(vec![(pre, empty_output(status))]);
+        }
+
+        let outputs = self.retry(
+            name,
+            alias,
+            |runner| {
+                let outer = runner.start_timeout(name, alias)?;
+                let outputs = runner.capture_main(name, alias, commands, command_args);
+
+                runner.deadline = outer;
+                outputs
+            },
+            |outputs| overall_status(outputs),
+        )?;
+
+        self.run_post(name, alias, args, &overall_status(&outputs));
+
+        Ok(outputs)
+    }
+
+    fn capture_main(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        args: &[String],
+    ) -> Result<Vec<(String, Output)>> {
+        if alias.parallel && commands.len() > 1 {
+            let mut steps = Vec::new();
+
+            for command in commands {
+                check_parallel_step(name, command)?;
+                steps.push((
+                    command.to_string(),
+                    self.build_command(name, alias, command, args)?,
+                ));
+            }
+
+            return parallel::capture(steps, alias.fail_fast, self.deadline.as_ref());
+        }
+
+        let mut outputs = Vec::new();
+
+        for command in commands {
+            let output = match command.alias_refs() {
+                Some(targets) => empty_output(self.run_refs(&targets, false)?),
+                None => timeout::output(
+                    &mut self.build_command(name, alias, command, args)?,
+                    self.deadline.as_ref(),
+                )?,
+            };
+            let failed = !output.status.success();
+
+            outputs.push((command.to_string(), output));
+
+            if failed {
+                break;
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    fn run_parallel(
+        &self,
+        name: &str,
+        alias: &Alias,
+        commands: &[Prepared],
+        args: &[String],
+        announce: bool,
+        logs: &[&RunLog],
+    ) -> Result<ExitStatus> {
+        let mut steps = Vec::new();
+
+        for (i, command) in commands.iter().enumerate() {
+            check_parallel_step(name, command)?;
+
+            if announce {
+                println!("Starting step {}/{}: '{}'", i + 1, commands.len(), command);
+            }
+
+            steps.push((
+                command.to_string(),
+                self.build_command(name, alias, command, args)?,
+            ));
+        }
+
+        parallel::run(
+            name,
+            steps,
+            alias.fail_fast,
+            self.captured,
+            self.deadline.as_ref(),
+            logs,
+        )
+    }
+
+    // a hook is either a shell command run in the context of the alias that
+    // owns it, or a `pintas:<alias>` reference resolved like a regular run
+    fn run_hook(
+        &mut self,
+        owner: &str,
+        alias: &Alias,
+        hook: &str,
+        args: &[String],
+    ) -> Result<ExitStatus> {
+        if let Some(target) = chain::alias_ref(hook) {
+            return self.run(target, args, false);
+        }
+
+        let (hooks, hook_args) = self.prepare(owner, alias, &[hook], args, false)?;
+
+        if self.restricted {
+            policy::check_restricted(owner, &hooks[0])?;
+        }
+
+        self.run_command(owner, alias, &Prepared::Shell(hooks[0].clone()), &hook_args)
+    }
+
+    // a failing pre hook stops the alias before its main command, returns its
+    // status in that case
+    fn run_pre(
+        &mut self,
+        name: &str,
+        alias: &Alias,
+        args: &[String],
+    ) -> Result<Option<ExitStatus>> {
+        let Some(pre) = &alias.pre else {
+            return Ok(None);
+        };
+
+        let status = self.run_hook(name, alias, pre, args)?;
+
+        if status.success() {
+            return Ok(None);
+        }
+
+        eprintln!(
+            "Pre hook of alias '{}' failed (exit code: {}).",
+            name,
+            sys::exit_code(&status)
+        );
+
+        Ok(Some(status))
+    }
+
+    // the post hook gets the exit code of the main command in
+    // PINTAS_EXIT_CODE, its own failure is only reported like a cleanup's
+    fn run_post(&mut self, name: &str, alias: &Alias, args: &[String], status: &ExitStatus) {
+        let Some(post) = &alias.post else {
+            return;
+        };
+
+        let outer = self.post_exit_code.replace(sys::exit_code(status));
+        let result = self.run_hook(name, alias, post, args);
+
+        self.post_exit_code = outer;
+
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: post hook for alias '{}' failed (exit code: {})",
+                name,
+                sys::exit_code(&status)
+            ),
+            Err(e) => eprintln!(
+                "Warning: failed to run post hook for alias '{}': {}",
+                name, e
+            ),
+        }
+    }
+
+    // the cleanup command runs no matter how the main command ended, with the
+    // same arguments and environment, its own failure is only reported
+    fn run_cleanup(&mut self, name: &str, alias: &Alias, args: &[String]) {
+        let Some(cleanup) = &alias.cleanup else {
+            return;
+        };
+
+        let result = {
+            let _guard = sys::SignalGuard::install();
+
+            self.run_hook(name, alias, cleanup, args)
+        };
+
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: cleanup for alias '{}' failed (exit code: {})",
+                name,
+                sys::exit_code(&status)
+            ),
+            Err(e) => eprintln!("Warning: failed to run cleanup for alias '{}': {}", name, e),
+        }
+    }
+
+    // runs the command with its output captured and prints a single JSON
+    // envelope instead, the exit code of pintas mirrors the command's
+    fn run_captured(&mut self, run: &RunArgs) -> Result<()> {
+        self.stack.enter(&run.alias)?;
+
+        let (alias, commands, command_args) = self.resolve(&run.alias, &run.args)?;
+
+        if !alias.checklist.is_empty() {
+            return Err(anyhow!(
+                "Alias '{}' is a checklist, which can't run with --capture-json.",
+                run.alias
+            ));
+        }
+
+        let _lock = lock::acquire(&run.alias, alias)?;
+
+        let started = Instant::now();
+        let outputs = {
+            let _guard = (alias.cleanup.is_some() || alias.post.is_some() || alias.parallel)
+                .then(sys::SignalGuard::install);
+
+            self.capture_steps(&run.alias, alias, &commands, &command_args, &run.args)
+        };
+        let duration = started.elapsed();
+        let separator = if alias.parallel { " & " } else { " && " };
+        let command = commands
+            .iter()
+            .map(Prepared::to_string)
+            .collect::<Vec<_>>()
+            .join(separator);
+
+        self.run_cleanup(&run.alias, alias, &run.args);
+        self.stack.leave();
+
+        let outputs = outputs?;
+        let output = Output {
+            status: overall_status(&outputs),
+            stdout: outputs
+                .iter()
+                .flat_map(|(_, output)| output.stdout.clone())
+                .collect(),
+            stderr: outputs
+                .iter()
+                .flat_map(|(_, output)| output.stderr.clone())
+                .collect(),
+        };
+
+        history::record(
+            self.config,
+            &run.alias,
+            &run.args,
+            &command,
+            duration,
+            &output.status,
+        );
+        hooks::after_run(self.config, &run.alias, duration, &output.status, true);
+
+        if run.notify || alias.notify {
+            notify::send(&run.alias, duration, &output.status);
+        }
+
+        if let Some(log) = RunLog::open(&run.alias, alias, &command_args)? {
+            log.write(&output.stdout);
+            log.write(&output.stderr);
+            log.finish(&Ok(output.status), duration);
+        }
+
+        if alias.keep_output {
+            output::save(&output.stdout)?;
+        }
+
+        let (stdout, stdout_truncated) = truncate_output(&output.stdout, run.capture_limit);
+        let (stderr, stderr_truncated) = truncate_output(&output.stderr, run.capture_limit);
+
+        let envelope = Json::object([
+            ("alias", Json::string(&run.alias)),
+            ("command", Json::string(&command)),
+            ("args", Json::strings(&run.args)),
+            (
+                "exit_code",
+                Json::Number(sys::exit_code(&output.status).into()),
+            ),
+            ("success", Json::Bool(output.status.success())),
+            ("duration_ms", Json::Number(duration.as_millis() as f64)),
+            ("stdout", Json::String(stdout)),
+            ("stdout_truncated", Json::Bool(stdout_truncated)),
+            ("stderr", Json::String(stderr)),
+            ("stderr_truncated", Json::Bool(stderr_truncated)),
+            (
+                "steps",
+                Json::Array(
+                    outputs
+                        .iter()
+                        .map(|(command, output)| {
+                            Json::object([
+                                ("command", Json::string(command)),
+                                (
+                                    "exit_code",
+                                    Json::Number(sys::exit_code(&output.status).into()),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "metadata",
+                Json::object([
+                    (
+                        "description",
+                        Json::optional_string(alias.description.as_deref()),
+                    ),
+                    ("locale", Json::optional_string(alias.locale.as_deref())),
+                    ("tz", Json::optional_string(alias.tz.as_deref())),
+                    ("pre", Json::optional_string(alias.pre.as_deref())),
+                    ("post", Json::optional_string(alias.post.as_deref())),
+                    ("cleanup", Json::optional_string(alias.cleanup.as_deref())),
+                    ("tags", Json::strings(&alias.tags)),
+                ]),
+            ),
+        ]);
+
+        println!("{}", envelope);
+
+        if !output.status.success() {
+            exit(sys::exit_code(&output.status));
+        }
+
+        Ok(())
+    }
+}
+
+// a single command with no history, hooks, retries, logs or lock after it
+fn can_exec(config: &Config, alias: &Alias) -> bool {
+    let single = |command: &str| chain::alias_refs(command).is_none();
+
+    !config.settings.history
+        && config.hooks.post_run.is_none()
+        && config.hooks.on_failure.is_none()
+        && (alias.argv().is_some()
+            || alias
+                .commands()
+                .is_some_and(|commands| match commands.as_slice() {
+                    [command] => single(command),
+                    _ => false,
+                }))
+        && alias.headless.as_deref().is_none_or(single)
+        && alias.deps.is_empty()
+        && alias.checklist.is_empty()
+        && alias.pre.is_none()
+        && alias.post.is_none()
+        && alias.cleanup.is_none()
+        && alias.timeout.is_none()
+        && alias.retries == 0
+        && !alias.keep_output
+        && !alias.log
+        && alias.log_file.is_none()
+        && !alias.notify
+        && !alias.exclusive
+        && !triage::wanted(alias, false)
+}
+
+// a step that the parallel runner can start as a process of its own
+fn check_parallel_step(name: &str, command: &Prepared) -> Result<()> {
+    if command.alias_refs().is_some() {
+        return Err(anyhow!(
+            "Alias '{}' can't run other aliases as parallel steps, use deps instead.",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+fn empty_output(status: ExitStatus) -> Output {
+    Output {
+        status,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    }
+}
+
+// `prompts` overrides the default of the declared argument name or the
+// placeholder itself
+fn prompt_message(alias: &Alias, placeholder: &str) -> String {
+    if let Some(message) = alias.prompts.get(placeholder) {
+        return message.clone();
+    }
+
+    let declared = placeholder
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| alias.args.get(index.checked_sub(1)?));
+
+    match declared {
+        Some(arg) => arg.name.clone(),
+        None => placeholder.to_string(),
+    }
+}
+
+// how a command is handed to its interpreter, the one named by the shebang
+// of a script or the `shell` of the alias, POSIX shells also get the alias
+// name as $0 and shell-quoted placeholder values
+struct Interpreter<'a> {
+    program: &'a str,
+    args: Vec<&'a str>,
+    flag: &'static str,
+    posix: bool,
+}
+
+// a shebang in the command wins over the alias's `shell`, which wins over
+// the one in `[settings]`
+fn interpreter<'a>(config: &'a Config, alias: &'a Alias, command: &'a str) -> Interpreter<'a> {
+    let (program, args) = match shebang(command) {
+        Some((program, args)) => (program, args),
+        None => (
+            match alias.shell.as_deref() {
+                Some(shell) => resolve_shell(Some(shell)),
+                None => config.settings.shell(),
+            },
+            Vec::new(),
+        ),
+    };
+    let base = Path::new(program)
+        .file_name()
+        .and_then(|base| base.to_str())
+        .unwrap_or(program);
+
+    // `python3.12` is still python
+    let (flag, posix) = match base.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+        "python" | "fish" => ("-c", false),
+        "node" | "ruby" | "perl" => ("-e", false),
+        _ => ("-c", true),
+    };
+
+    Interpreter {
+        program,
+        args,
+        flag,
+        posix,
+    }
+}
+
+// the words of a command that means the same with or without a shell: no
+// quotes, variables, globs, redirects or other operators, and a program on
+// PATH rather than a builtin, and whether it ends in the `"$@"` that
+// `append_args` adds
+fn simple_command(command: &str) -> Option<(Vec<&str>, bool)> {
+    let (command, forwards) = match command.strip_suffix(" \"$@\"") {
+        Some(command) => (command, true),
+        None => (command, false),
+    };
+
+    let plain = |c: char| c.is_ascii_alphanumeric() || " \t-_./:,+@%^=".contains(c);
+
+    if !command.chars().all(plain) {
+        return None;
+    }
+
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let program = *words.first()?;
+
+    if program.contains('=') || SHELL_BUILTINS.contains(&program) {
+        return None;
+    }
+
+    sys::find_program(program)?;
+
+    Some((words, forwards))
+}
+
+// `#!/bin/bash -e` is bash with `-e`, `#!/usr/bin/env python3` is python3
+// looked up on PATH, the shebang line itself is a comment to all of them
+fn shebang(command: &str) -> Option<(&str, Vec<&str>)> {
+    let line = command.trim_start().strip_prefix("#!")?.lines().next()?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?;
+
+    if program.ends_with("/env") {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    Some((program, words.collect()))
+}
+
+// the status of the first step that failed, or the last one
+// parallel steps a failing sibling cancelled end with SIGTERM, the step
+// that failed on its own is the one reported
+fn overall_status(outputs: &[(String, Output)]) -> ExitStatus {
+    let statuses = || outputs.iter().map(|(_, output)| output.status);
+
+    statuses()
+        .find(|status| !status.success() && status.signal() != Some(sys::SIGTERM))
+        .or_else(|| statuses().find(|status| !status.success()))
+        .or(outputs.last().map(|(_, output)| output.status))
+        .unwrap_or_default()
+}
+
+// `no_network` runs the program in a network namespace of its own, with
+// nothing but a loopback device that is down, `sandbox` in the sandbox
+// that takes care of the network too, `ionice` at its I/O priority
+fn new_command(name: &str, alias: &Alias, program: &str) -> Result<OsCommand> {
+    let mut argv = Vec::new();
+
+    if alias.sandbox.is_some() {
+        argv.extend(sandbox::prefix(name, alias)?);
+    } else if alias.no_network && sys::can_isolate_network() {
+        argv.extend(["unshare", "--net", "--map-root-user", "--"].map(String::from));
+    }
+
+    argv.extend(priority::ionice_prefix(alias));
+
+    let Some((first, rest)) = argv.split_first() else {
+        return Ok(OsCommand::new(program));
+    };
+
+    let mut cmd = OsCommand::new(first);
+
+    cmd.args(rest).arg(program);
+
+    Ok(cmd)
+}
+
+fn build_command(
+    config: &Config,
+    name: &str,
+    alias: &Alias,
+    command: &Prepared,
+    args: &[String],
+    trace: bool,
+) -> Result<OsCommand> {
+    // a PATH of the alias's own could find another program than ours
+    let own_path = alias.env.contains_key("PATH") || alias.env_file.is_some();
+    let simple = match command {
+        Prepared::Shell(command)
+            if !trace && !own_path && interpreter(config, alias, command).posix =>
+        {
+            simple_command(command)
+        }
+        _ => None,
+    };
+    // `sh -c` ignores arguments a command doesn't use, so does running
+    // it without one
+    let takes_args = simple.as_ref().is_none_or(|(_, forwards)| *forwards);
+
+    let mut cmd = match (command, simple) {
+        (_, Some((words, _))) => {
+            let mut cmd = new_command(name, alias, words[0])?;
+
+            cmd.args(&words[1..]);
+            cmd
+        }
+        (Prepared::Shell(command), None) => {
+            let interpreter = interpreter(config, alias, command);
+            let mut cmd = new_command(name, alias, interpreter.program)?;
+
+            cmd.args(&interpreter.args);
+
+            if trace && interpreter.posix {
+                cmd.arg("-x");
+            }
+
+            cmd.arg(interpreter.flag);
+            cmd.arg(command);
+
+            if interpreter.posix {
+                cmd.arg(name); // this becomes $0 in the script
+            }
+
+            cmd
+        }
+        (Prepared::Argv(argv), None) => {
+            let mut cmd = new_command(name, alias, &argv[0])?;
+
+            cmd.args(&argv[1..]);
+
+            cmd
+        }
+    };
+
+    if takes_args {
+        cmd.args(args); // these become $1, $2, ... or extra words of an argv
+    }
+
+    if alias.clean_env {
+        cmd.env_clear();
+        cmd.envs(
+            env::vars_os().filter(|(var, _)| var.to_str().is_some_and(|var| keeps_env(alias, var))),
+        );
+    }
+
+    priority::apply_nice(&mut cmd, alias);
+    limits::apply(&mut cmd, name, alias)?;
+
+    if let Some(locale) = &alias.locale {
+        cmd.env("LANG", locale);
+        cmd.env("LC_ALL", locale);
+    }
+
+    if let Some(tz) = &alias.tz {
+        cmd.env("TZ", tz);
+    }
+
+    // both spellings, tools disagree on which one they read
+    if let Some(proxy) = &alias.proxy {
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            cmd.env(var, proxy);
+        }
+    }
+
+    if let Some(no_proxy) = &alias.no_proxy {
+        cmd.env("NO_PROXY", no_proxy);
+        cmd.env("no_proxy", no_proxy);
+    }
+
+    if let Some(env_file) = &alias.env_file {
+        cmd.envs(dotenv::load(&expand_tilde(env_file))?);
+    }
+
+    // explicit variables win over the ones loaded from env_file
+    cmd.envs(&alias.env);
+
+    if let Some(cwd) = &alias.cwd {
+        let cwd = expand_tilde(cwd);
+
+        if !cwd.is_dir() {
+            return Err(anyhow!(
+                "Working directory '{}' of alias '{}' doesn't exist.",
+                cwd.to_string_lossy(),
+                name
+            ));
+        }
+
+        cmd.current_dir(cwd);
+    }
+
+    Ok(cmd)
+}
+
+// what `clean_env` keeps: the basics a program needs to find its way, the
+// variables pintas passes to nested runs and `keep_env`, where `AWS_*`
+// keeps every variable starting with `AWS_`
+fn keeps_env(alias: &Alias, var: &str) -> bool {
+    CLEAN_ENV.contains(&var)
+        || var.starts_with("PINTAS_")
+        || alias
+            .keep_env
+            .iter()
+            .any(|kept| match kept.strip_suffix('*') {
+                Some(prefix) => var.starts_with(prefix),
+                None => var == kept,
+            })
+}
+
+fn truncate_output(bytes: &[u8], limit: usize) -> (String, bool) {
+    if bytes.len() <= limit {
+        return (String::from_utf8_lossy(bytes).into_owned(), false);
+    }
+
+    (String::from_utf8_lossy(&bytes[..limit]).into_owned(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_command_runs_programs_directly() {
+        assert_eq!(simple_command("ls -la"), Some((vec!["ls", "-la"], false)));
+        assert_eq!(
+            simple_command("ls src \"$@\""),
+            Some((vec!["ls", "src"], true))
+        );
+    }
+
+    #[test]
+    fn simple_command_leaves_builtins_to_the_shell() {
+        for command in [
+            "echo hi",
+            "printf x",
+            "pwd -L",
+            "kill %1",
+            "test -f x",
+            "true",
+        ] {
+            assert_eq!(simple_command(command), None, "{}", command);
+        }
+    }
+
+    #[test]
+    fn simple_command_leaves_shell_syntax_to_the_shell() {
+        for command in [
+            "ls *.rs", "ls | wc", "ls > out", "ls $HOME", "FOO=1 ls", "ls 'a b'",
+        ] {
+            assert_eq!(simple_command(command), None, "{}", command);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::config::{Alias, Sandbox, expand_tilde};
+use crate::expand;
+use crate::sys;
+
+// makes a path and every mount below it read-only, then the writable paths
+// writable again, for unshare where there's no bwrap to do it
+const REMOUNT_SCRIPT: &str = r#"ro() {
+  mount --rbind "$1" "$1" || return 1
+  while read -r _ _ _ _ point _; do
+    case "$point" in "$1"|"${1%/}"/*) mount -o remount,bind,ro "$point" 2>/dev/null;; esac
+  done < /proc/self/mountinfo
+}
+rw() { mount --bind "$1" "$1" && mount -o remount,bind,rw "$1"; }
+"#;
+
+fn has_bwrap() -> bool {
+    cfg!(target_os = "linux") && sys::find_program("bwrap").is_some()
+}
+
+// whether `unshare` can give a child its own mount and network namespaces,
+// which needs unprivileged user namespaces
+fn can_unshare() -> bool {
+    static CAN_UNSHARE: OnceLock<bool> = OnceLock::new();
+
+    *CAN_UNSHARE.get_or_init(|| {
+        cfg!(target_os = "linux")
+            && sys::find_program("unshare").is_some()
+            && Command::new("unshare")
+                .args(["--mount", "--net", "--map-root-user", "true"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+    })
+}
+
+// a relative path is taken from the alias's working directory, which is
+// where the program starts
+fn paths(name: &str, alias: &Alias, paths: &[String]) -> Result<Vec<String>> {
+    let base = match &alias.cwd {
+        Some(cwd) => expand_tilde(cwd),
+        None => env::current_dir().context("Failed to get current directory")?,
+    };
+
+    paths
+        .iter()
+        .map(|path| {
+            let path = base.join(expand_tilde(path));
+
+            path.canonicalize()
+                .map(|path| path.to_string_lossy().into_owned())
+                .map_err(|_| {
+                    anyhow!(
+                        "Sandbox path '{}' of alias '{}' doesn't exist.",
+                        path.to_string_lossy(),
+                        name
+                    )
+                })
+        })
+        .collect()
+}
+
+// checks the sandbox before anything runs, an alias that asks for one never
+// runs without it
+pub fn check(name: &str, alias: &Alias) -> Result<()> {
+    prefix(name, alias).map(|_| ())
+}
+
+// the words that run a program in the alias's sandbox: bwrap where it's
+// installed, unshare with a mount namespace otherwise
+pub fn prefix(name: &str, alias: &Alias) -> Result<Vec<String>> {
+    let Some(sandbox) = &alias.sandbox else {
+        return Ok(Vec::new());
+    };
+
+    let readonly = paths(name, alias, &sandbox.readonly)?;
+    let writable = paths(name, alias, &sandbox.writable)?;
+    let no_network = isolates_network(alias, sandbox);
+
+    if has_bwrap() {
+        let mut prefix: Vec<String> = ["bwrap", "--dev-bind", "/", "/"].map(String::from).into();
+
+        for path in &readonly {
+            prefix.extend(["--ro-bind".to_string(), path.clone(), path.clone()]);
+        }
+
+        // /dev stays usable under a read-only root
+        if !readonly.is_empty() {
+            prefix.extend(["--dev", "/dev"].map(String::from));
+        }
+
+        for path in &writable {
+            prefix.extend(["--bind".to_string(), path.clone(), path.clone()]);
+        }
+
+        if no_network {
+            prefix.push("--unshare-net".to_string());
+        }
+
+        prefix.extend(["--die-with-parent", "--"].map(String::from));
+
+        return Ok(prefix);
+    }
+
+    if !can_unshare() {
+        return Err(anyhow!(
+            "Alias '{}' asks for a sandbox, but neither bwrap nor unshare with user namespaces is available here. Install bubblewrap to run it.",
+            name
+        ));
+    }
+
+    let mut prefix: Vec<String> = ["unshare", "--mount", "--map-root-user"]
+        .map(String::from)
+        .into();
+
+    if no_network {
+        prefix.push("--net".to_string());
+    }
+
+    prefix.push("--".to_string());
+
+    if readonly.is_empty() && writable.is_empty() {
+        return Ok(prefix);
+    }
+
+    let mut script = REMOUNT_SCRIPT.to_string();
+
+    for path in &readonly {
+        script.push_str(&format!("ro {} || exit 125\n", expand::shell_quote(path)));
+    }
+
+    for path in &writable {
+        script.push_str(&format!("rw {} || exit 125\n", expand::shell_quote(path)));
+    }
+
+    script.push_str("exec \"$@\"\n");
+    prefix.extend([
+        "sh".to_string(),
+        "-c".to_string(),
+        script,
+        "pintas-sandbox".to_string(),
+    ]);
+
+    Ok(prefix)
+}
+
+// `no_network` and `sandbox.network = false` mean the same inside a sandbox
+fn isolates_network(alias: &Alias, sandbox: &Sandbox) -> bool {
+    alias.no_network || sandbox.network == Some(false)
+}
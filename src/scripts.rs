@@ -0,0 +1,198 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command as OsCommand;
+
+use crate::config::{Alias, Config, expand_tilde, get_pintas_dir};
+
+const SCRIPT_TEMPLATE: &str = "#!/bin/sh\n\n";
+
+pub fn get_scripts_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("scripts"))
+}
+
+// `script = "deploy"` is `~/.pintas/scripts/deploy`, anything with a slash
+// is a path of its own that pintas doesn't manage
+pub fn script_path(alias: &Alias) -> Option<Result<PathBuf>> {
+    let script = alias.script.as_deref()?;
+
+    if script.contains('/') {
+        return Some(Ok(expand_tilde(script)));
+    }
+
+    Some(get_scripts_dir().map(|dir| dir.join(script)))
+}
+
+pub fn is_managed(alias: &Alias) -> bool {
+    alias
+        .script
+        .as_deref()
+        .is_some_and(|script| !script.contains('/'))
+}
+
+// creates an executable script from a template unless it's already there
+pub fn create_script(file: &str) -> Result<PathBuf> {
+    let dir = get_scripts_dir()?;
+    let path = dir.join(file);
+
+    fs::create_dir_all(&dir).context("Failed to create scripts directory")?;
+
+    if !path.exists() {
+        fs::write(&path, SCRIPT_TEMPLATE)
+            .with_context(|| format!("Failed to create '{}'", path.to_string_lossy()))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(path)
+}
+
+// `$VISUAL` or `$EDITOR` may carry arguments like `code -w`, so the shell
+// splits it
+pub fn open_editor(path: &PathBuf) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let status = OsCommand::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+
+    if !status.success() {
+        return Err(anyhow!("Editor '{}' exited with an error.", editor));
+    }
+
+    Ok(())
+}
+
+pub fn remove_script(alias: &Alias) -> Result<()> {
+    let Some(path) = script_path(alias).filter(|_| is_managed(alias)) else {
+        return Ok(());
+    };
+    let path = path?;
+
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove '{}'", path.to_string_lossy()))
+        }
+        _ => Ok(()),
+    }
+}
+
+// the aliases, sub-aliases included, whose script file doesn't exist
+pub fn missing_scripts(config: &Config) -> Vec<String> {
+    let mut missing = Vec::new();
+
+    for (name, alias) in &config.aliases {
+        collect_missing(name, alias, &mut missing);
+    }
+
+    missing.sort();
+
+    missing
+}
+
+fn collect_missing(name: &str, alias: &Alias, missing: &mut Vec<String>) {
+    if script_path(alias).is_some_and(|path| path.is_ok_and(|path| !path.is_file())) {
+        missing.push(name.to_string());
+    }
+
+    for (sub, alias) in &alias.sub {
+        collect_missing(&format!("{} {}", name, sub), alias, missing);
+    }
+}
+
+// files in `~/.pintas/scripts` that no alias points at anymore, aliases in
+// the trash keep theirs
+pub fn unused_scripts(config: &Config) -> Result<Vec<String>> {
+    let used = used_scripts(config);
+    let entries = match fs::read_dir(get_scripts_dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read scripts directory"),
+    };
+
+    let mut unused: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|file| !used.contains(file.as_str()))
+        .collect();
+
+    unused.sort();
+
+    Ok(unused)
+}
+
+fn used_scripts(config: &Config) -> BTreeSet<&str> {
+    let mut used = BTreeSet::new();
+
+    let trashed = config.trash.values().map(|entry| &entry.alias);
+
+    for alias in config.aliases.values().chain(trashed) {
+        collect_used(alias, &mut used);
+    }
+
+    used
+}
+
+fn collect_used<'a>(alias: &'a Alias, used: &mut BTreeSet<&'a str>) {
+    if let Some(script) = alias.script.as_deref().filter(|_| is_managed(alias)) {
+        used.insert(script);
+    }
+
+    for alias in alias.sub.values() {
+        collect_used(alias, used);
+    }
+}
+
+// a script copied in from elsewhere or checked out of a dotfiles repo may
+// have lost its executable bit
+pub fn sync_scripts(config: &Config) -> Result<()> {
+    let dir = get_scripts_dir()?;
+
+    for script in used_scripts(config) {
+        let path = dir.join(script);
+
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+
+        let mode = metadata.permissions().mode();
+
+        if mode & 0o111 != 0o111 {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode | 0o111)).with_context(
+                || format!("Failed to make '{}' executable", path.to_string_lossy()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn prune_scripts(config: &Config) -> Result<()> {
+    let dir = get_scripts_dir()?;
+    let unused = unused_scripts(config)?;
+
+    if unused.is_empty() {
+        println!("No unused scripts found.");
+
+        return Ok(());
+    }
+
+    for script in &unused {
+        fs::remove_file(dir.join(script))
+            .with_context(|| format!("Failed to remove script '{}'", script))?;
+    }
+
+    println!("Successfully pruned unused scripts: {}", unused.join(", "));
+
+    Ok(())
+}
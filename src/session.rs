@@ -0,0 +1,187 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use crate::config::{Config, get_pintas_dir};
+use crate::shims;
+use crate::sys;
+
+// set by `pintas init` to `<pid of the shell>-<when it started>`, the
+// timestamp keeps a shell that gets a recycled pid out of an older session
+pub const SESSION_VAR: &str = "PINTAS_SESSION";
+
+// a session lives in `~/.pintas/sessions/<id>/`, with its aliases in
+// `pintas.toml` and their shims in `shims/`, which the init snippet puts
+// first in PATH
+pub fn get_sessions_dir() -> Result<PathBuf> {
+    Ok(get_pintas_dir()?.join("sessions"))
+}
+
+fn layer_path(id: &str) -> Result<PathBuf> {
+    Ok(get_sessions_dir()?.join(id).join("pintas.toml"))
+}
+
+pub fn current_layer_path() -> Result<PathBuf> {
+    layer_path(&require_current()?)
+}
+
+pub fn current_shims_dir() -> Result<PathBuf> {
+    shims_dir(&require_current()?)
+}
+
+fn shims_dir(id: &str) -> Result<PathBuf> {
+    Ok(get_sessions_dir()?.join(id).join("shims"))
+}
+
+fn is_session_id(id: &str) -> bool {
+    id.split_once('-').is_some_and(|(pid, started)| {
+        !pid.is_empty()
+            && !started.is_empty()
+            && pid.bytes().all(|b| b.is_ascii_digit())
+            && started.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+pub fn current() -> Option<String> {
+    env::var(SESSION_VAR).ok().filter(|id| is_session_id(id))
+}
+
+pub fn require_current() -> Result<String> {
+    current().ok_or_else(|| {
+        anyhow!(
+            "Session aliases need the shell integration, add 'eval \"$(pintas init bash)\"' to your ~/.bashrc and open a new shell."
+        )
+    })
+}
+
+// a session ends with the shell that started it
+fn has_ended(id: &str) -> bool {
+    id.split_once('-')
+        .and_then(|(pid, _)| pid.parse().ok())
+        .is_none_or(|pid| !sys::is_running(pid))
+}
+
+// adds the aliases of the current session, an alias of the config with the
+// same name wins over them
+pub fn load_layer(mut config: Config) -> Result<Config> {
+    let Some(id) = current() else {
+        return Ok(config);
+    };
+
+    let path = layer_path(&id)?;
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(config);
+    };
+    let layer: Config = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse '{}'.", path.to_string_lossy()))?;
+
+    for (name, mut alias) in layer.aliases {
+        if config.aliases.contains_key(&name) {
+            eprintln!(
+                "Warning: session alias '{}' is hidden by the alias of the same name in the config.",
+                name
+            );
+            continue;
+        }
+
+        alias.session = true;
+        config.aliases.insert(name, alias);
+    }
+
+    Ok(config)
+}
+
+// the config without the aliases of the session, which never end up in the
+// config files or the index
+pub fn without_session(config: &Config) -> Config {
+    let mut config = config.clone();
+
+    config.aliases.retain(|_, alias| !alias.session);
+
+    config
+}
+
+pub fn save_layer(config: &Config) -> Result<()> {
+    let Some(id) = current() else {
+        return Ok(());
+    };
+
+    let layer = Config {
+        aliases: config
+            .aliases
+            .iter()
+            .filter(|(_, alias)| alias.session)
+            .map(|(name, alias)| (name.clone(), alias.clone()))
+            .collect(),
+        ..Default::default()
+    };
+    let path = layer_path(&id)?;
+
+    if layer.aliases.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).context("Failed to remove session aliases")
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let content = toml::to_string(&layer).context("Failed to serialize session aliases.")?;
+
+    fs::create_dir_all(path.parent().unwrap_or(&path))
+        .context("Failed to create session directory")?;
+    fs::write(&path, content).context("Failed to write session aliases")
+}
+
+// the shims of the session are rewritten as a whole, they are few
+pub fn sync_shims(config: &Config) -> Result<()> {
+    remove_ended()?;
+
+    let Some(id) = current() else {
+        return Ok(());
+    };
+
+    let dir = shims_dir(&id)?;
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir).context("Failed to clear session shims")?;
+    }
+
+    let shims = shims::session_shims(config)?;
+
+    if shims.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&dir).context("Failed to create session shims directory")?;
+
+    for (file, content) in shims.values() {
+        let path = dir.join(file);
+
+        fs::write(&path, content)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+// what is left of shells that have exited
+fn remove_ended() -> Result<()> {
+    let Ok(entries) = fs::read_dir(get_sessions_dir()?) else {
+        return Ok(());
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let id = entry.file_name().to_string_lossy().into_owned();
+
+        if is_session_id(&id) && has_ended(&id) {
+            fs::remove_dir_all(entry.path())
+                .with_context(|| format!("Failed to remove ended session '{}'", id))?;
+        }
+    }
+
+    Ok(())
+}
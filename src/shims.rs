@@ -0,0 +1,794 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{self, File};
+use std::iter;
+use std::os::unix::fs::{PermissionsExt, symlink};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command as OsCommand, exit};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::config::{Alias, Config, get_pintas_dir};
+use crate::expand::shell_quote;
+use crate::session;
+use crate::sha256;
+use crate::sys;
+
+// names of the aliases the last sync created shims for, a shim whose alias
+// is no longer in the config is a tombstone until it gets pruned
+const MANIFEST_FILENAME: &str = "shims.manifest";
+
+// what shims start with unless `[settings] shim_shebang` says otherwise
+const DEFAULT_SHEBANG: &str = "/bin/sh";
+
+// a shims directory several users have on PATH, like `/opt/pintas/shims`,
+// filled by whoever owns it while every shim runs the config of the user
+// who invokes it
+pub const SHARED_SHIMS_VAR: &str = "PINTAS_SHIMS_DIR";
+
+pub fn shared_shims_dir() -> Option<PathBuf> {
+    env::var_os(SHARED_SHIMS_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn get_shims_dir() -> Result<PathBuf> {
+    match shared_shims_dir() {
+        Some(dir) => Ok(dir),
+        None => Ok(get_pintas_dir()?.join("shims")),
+    }
+}
+
+// the manifest describes the directory, so a shared one keeps it next to it
+fn get_manifest_path() -> Result<PathBuf> {
+    match shared_shims_dir() {
+        Some(dir) => Ok(dir.with_extension("manifest")),
+        None => Ok(get_pintas_dir()?.join(MANIFEST_FILENAME)),
+    }
+}
+
+// a shared directory is only written by users who may, for everyone else it
+// is provisioned
+pub fn is_provisioned() -> bool {
+    shared_shims_dir().is_some_and(|dir| !sys::is_writable(dir.parent().unwrap_or(Path::new("/"))))
+}
+
+// in a shared directory, the shim of an alias the invoking user doesn't have
+// runs the program of that name further down PATH, as if the shim wasn't
+// there
+pub fn run_shadowed(alias: &str, args: &[String]) -> ! {
+    let name = shim_name(alias);
+    let shims_dir = get_shims_dir()
+        .ok()
+        .and_then(|dir| fs::canonicalize(dir).ok());
+    let next = sys::find_programs(&name)
+        .into_iter()
+        .find(|path| path.parent().and_then(|dir| fs::canonicalize(dir).ok()) != shims_dir);
+
+    let Some(program) = next else {
+        eprintln!("pintas: {}: command not found", name);
+        exit(127);
+    };
+
+    let error = OsCommand::new(&program).arg0(&name).args(args).exec();
+
+    eprintln!(
+        "pintas: failed to run '{}': {}",
+        program.to_string_lossy(),
+        error
+    );
+    exit(126);
+}
+
+pub fn read_manifest() -> Result<BTreeSet<String>> {
+    let path = get_manifest_path()?;
+
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+
+    let content = fs::read_to_string(&path).context("Failed to read shims manifest")?;
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn write_manifest(names: &BTreeSet<String>) -> Result<()> {
+    let content: String = names.iter().map(|name| format!("{}\n", name)).collect();
+    let path = get_manifest_path()?;
+
+    fs::write(&path, content).context("Failed to write shims manifest")?;
+    set_source_date(&path)
+}
+
+// shims live in one flat directory, so `docker/clean` gets the shim
+// `docker.clean`
+pub fn shim_name(alias: &str) -> String {
+    alias.replace('/', ".")
+}
+
+// every name that gets a shim, alias names and their `aka` synonyms, with
+// the alias it runs
+fn shim_owners(config: &Config) -> Vec<(&str, &str)> {
+    config
+        .aliases
+        .iter()
+        .flat_map(|(owner, alias)| {
+            iter::once(owner)
+                .chain(&alias.aka)
+                .map(move |name| (name.as_str(), owner.as_str()))
+        })
+        .collect()
+}
+
+pub fn check_shim_name(config: &Config, alias: &str) -> Result<()> {
+    let owners = shim_owners(config);
+    let aka = config.aliases.get(alias).map(|alias| &alias.aka);
+
+    for name in iter::once(alias).chain(aka.into_iter().flatten().map(String::as_str)) {
+        let shim = shim_name(name);
+
+        if let Some((_, other)) = owners
+            .iter()
+            .find(|(other_name, other)| *other != alias && shim_name(other_name) == shim)
+        {
+            return Err(anyhow!(
+                "Alias '{}' would get the same shim '{}' as alias '{}'.",
+                alias,
+                shim,
+                other
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_shim_names(config: &Config) -> Result<()> {
+    let mut seen = BTreeMap::new();
+
+    for (name, owner) in shim_owners(config) {
+        if let Some(other) = seen.insert(shim_name(name), owner) {
+            return Err(anyhow!(
+                "Aliases '{}' and '{}' would get the same shim '{}'.",
+                other,
+                owner,
+                shim_name(name)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn wants_shim(config: &Config, name: &str) -> bool {
+    owner_of(config, name).is_some_and(|alias| !alias.session && is_runnable(alias))
+}
+
+// where the shim of a name is, if it gets one
+pub fn shim_path(config: &Config, name: &str) -> Result<Option<PathBuf>> {
+    let Some(alias) = owner_of(config, name).filter(|alias| is_runnable(alias)) else {
+        return Ok(None);
+    };
+
+    let dir = if alias.session {
+        session::current_shims_dir()?
+    } else {
+        get_shims_dir()?
+    };
+
+    Ok(Some(dir.join(shim_name(name))))
+}
+
+fn owner_of<'a>(config: &'a Config, name: &str) -> Option<&'a Alias> {
+    config.aliases.get(config.aka_owner(name).unwrap_or(name))
+}
+
+fn is_runnable(alias: &Alias) -> bool {
+    (alias.commands().is_some() || alias.argv().is_some() || alias.script.is_some())
+        && alias.unavailable_on_machine_reason().is_none()
+}
+
+// file name and content of the shim of every alias that gets one, `aka`
+// names get a shim of their own that runs the alias listing them
+pub fn expected_shims(config: &Config) -> Result<BTreeMap<String, (String, String)>> {
+    shim_files(config, |name| wants_shim(config, name))
+}
+
+// the aliases of the current session get theirs in a directory of the
+// session instead
+pub fn session_shims(config: &Config) -> Result<BTreeMap<String, (String, String)>> {
+    shim_files(config, |name| {
+        owner_of(config, name).is_some_and(|alias| alias.session && is_runnable(alias))
+    })
+}
+
+fn shim_files(
+    config: &Config,
+    wanted: impl Fn(&str) -> bool,
+) -> Result<BTreeMap<String, (String, String)>> {
+    let pintas_path = env::current_exe().context("Failed to get current executable path")?;
+    let shebang = shebang(config);
+
+    Ok(shim_owners(config)
+        .into_iter()
+        .filter(|(name, _)| wanted(name))
+        .map(|(name, owner)| {
+            let content = format!(
+                "#!{}\nexec {} run --internal {} \"$@\"\n",
+                shebang,
+                shell_quote(&pintas_path.to_string_lossy()),
+                shell_quote(owner)
+            );
+
+            (name.to_string(), (shim_name(name), content))
+        })
+        .collect())
+}
+
+fn shebang(config: &Config) -> &str {
+    config
+        .settings
+        .shim_shebang
+        .as_deref()
+        .map_or(DEFAULT_SHEBANG, str::trim)
+}
+
+// the kernel runs the interpreter of a shebang by its absolute path, without
+// looking at PATH, so one that isn't there breaks every shim at once
+fn check_shebang(config: &Config) -> Result<()> {
+    let shebang = shebang(config);
+    let interpreter = shebang.split_whitespace().next().unwrap_or_default();
+
+    if !interpreter.starts_with('/') {
+        return Err(anyhow!(
+            "The shim shebang '{}' needs an absolute path, like '/usr/bin/env {}'.",
+            shebang,
+            interpreter
+        ));
+    }
+
+    if sys::find_program(interpreter).is_none() {
+        return Err(anyhow!(
+            "The shim interpreter '{}' doesn't exist or isn't executable, set shim_shebang in [settings] to one that does.",
+            interpreter
+        ));
+    }
+
+    Ok(())
+}
+
+// what `pintas init` prints, kept to what bash 3.2 and busybox ash understand
+pub fn init_script(shell: &str) -> Result<String> {
+    let shims_dir = get_shims_dir()?;
+
+    let sessions_dir = session::get_sessions_dir()?;
+
+    // every shell starts a session of its own, for `pintas add --session`
+    match shell {
+        "bash" => Ok(format!(
+            "# pintas shell integration for bash\n#\n# Add the following lines to your ~/.bashrc or ~/.profile:\n#\n  export {}=\"$$-$(date +%s)\"\n  export PATH={}/\"${}\"/shims:{}:\"$PATH\"\n",
+            session::SESSION_VAR,
+            shell_quote(&sessions_dir.to_string_lossy()),
+            session::SESSION_VAR,
+            shell_quote(&shims_dir.to_string_lossy())
+        )),
+        _ => Err(anyhow!("Shell '{}' not supported.", shell)),
+    }
+}
+
+// files in the shims directory that neither the manifest nor their content
+// mark as shims, e.g. scripts of the user's own
+fn foreign_files(shims_dir: &Path) -> Result<Vec<String>> {
+    let Ok(entries) = fs::read_dir(shims_dir) else {
+        return Ok(Vec::new());
+    };
+
+    let owned: BTreeSet<String> = read_manifest()?
+        .iter()
+        .map(|alias| shim_name(alias))
+        .collect();
+    let mut foreign: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !is_shim_file(&entry.path()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !owned.contains(name))
+        .collect();
+
+    foreign.sort();
+
+    Ok(foreign)
+}
+
+// whatever shebang it was written with
+fn is_shim_file(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| {
+        let mut lines = content.lines();
+
+        lines.next().is_some_and(|line| line.starts_with("#!"))
+            && lines
+                .next()
+                .is_some_and(|line| line.starts_with("exec ") && line.contains(" run --internal "))
+    })
+}
+
+// foreign files move on to the new generation unless `force` is set, one
+// that a shim would overwrite stops the sync
+fn keep_foreign_files(shims_dir: &Path, staging_dir: &Path, force: bool) -> Result<()> {
+    let foreign = foreign_files(shims_dir)?;
+
+    if foreign.is_empty() {
+        return Ok(());
+    }
+
+    if force {
+        println!(
+            "Removed files pintas didn't create from the shims directory: {}",
+            foreign.join(", ")
+        );
+
+        return Ok(());
+    }
+
+    if let Some(name) = foreign.iter().find(|name| staging_dir.join(name).exists()) {
+        return Err(anyhow!(
+            "'{}' in the shims directory wasn't created by pintas and would be replaced by a shim. Use 'pintas sync --force' to replace it.",
+            name
+        ));
+    }
+
+    for name in &foreign {
+        copy_entry(&shims_dir.join(name), &staging_dir.join(name))
+            .with_context(|| format!("Failed to keep '{}' in the shims directory", name))?;
+    }
+
+    eprintln!(
+        "Warning: kept files pintas didn't create in the shims directory: {} (run 'pintas sync --force' to delete them)",
+        foreign.join(", ")
+    );
+
+    Ok(())
+}
+
+// symlinks stay symlinks and directories are copied with everything in them
+fn copy_entry(from: &Path, to: &Path) -> Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+
+    if file_type.is_symlink() {
+        symlink(fs::read_link(from)?, to)?;
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+
+        for entry in fs::read_dir(from)? {
+            let name = entry?.file_name();
+
+            copy_entry(&from.join(&name), &to.join(&name))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+
+    Ok(())
+}
+
+// a plain directory becomes a link to a generation only when pintas made it:
+// its own `~/.pintas/shims` from before generations, or one with nothing but
+// shims in it, a shared directory set to e.g. `~/bin` is left alone
+fn check_plain_dir(shims_dir: &Path, own: bool) -> Result<()> {
+    let is_plain_dir = fs::symlink_metadata(shims_dir).is_ok_and(|meta| meta.is_dir());
+
+    if !is_plain_dir || own {
+        return Ok(());
+    }
+
+    let foreign = foreign_files(shims_dir)?;
+
+    if foreign.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "The shims directory '{}' wasn't created by pintas and holds other files ({}). Point {} at a directory of its own.",
+        shims_dir.to_string_lossy(),
+        foreign.join(", "),
+        SHARED_SHIMS_VAR
+    ))
+}
+
+pub fn sync_shims(config: &Config, force: bool) -> Result<()> {
+    let shims_dir = get_shims_dir()?;
+    let staging_dir = shims_dir.with_extension("new");
+
+    check_shim_names(config)?;
+    check_shebang(config)?;
+
+    if is_provisioned() {
+        eprintln!(
+            "Warning: the shared shims directory '{}' is provisioned by its owner, its shims change once they sync it.",
+            shims_dir.to_string_lossy()
+        );
+
+        return session::sync_shims(config);
+    }
+
+    check_plain_dir(&shims_dir, shared_shims_dir().is_none())?;
+
+    // the new set is built next to the live one, so shims keep working while
+    // a large sync is in progress
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).context("Failed to clear shims.new directory")?;
+    }
+
+    fs::create_dir_all(&staging_dir).context("Failed to create shims directory")?;
+
+    // a sync that fails part of the way leaves no half-built set behind
+    let shims = match build_generation(config, &shims_dir, &staging_dir, force) {
+        Ok(shims) => shims,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging_dir);
+
+            return Err(e);
+        }
+    };
+
+    write_manifest(&shims.into_keys().collect())?;
+
+    session::sync_shims(config)
+}
+
+// fills `staging_dir` and swaps it in, the shims it wrote by alias name
+fn build_generation(
+    config: &Config,
+    shims_dir: &Path,
+    staging_dir: &Path,
+    force: bool,
+) -> Result<BTreeMap<String, (String, String)>> {
+    // other users of a shared directory need to get in, whatever our umask
+    fs::set_permissions(staging_dir, fs::Permissions::from_mode(0o755))?;
+
+    let shims = expected_shims(config)?;
+    let mut fingerprint = String::new();
+
+    for (file, content) in shims.values() {
+        let shim_path = staging_dir.join(file);
+
+        fs::write(&shim_path, content)?;
+        fs::set_permissions(&shim_path, fs::Permissions::from_mode(0o755))?;
+        set_source_date(&shim_path)?;
+
+        fingerprint.push_str(&format!("{}\0{}\0", file, content));
+    }
+
+    keep_foreign_files(shims_dir, staging_dir, force)?;
+
+    // named after the content so the same config always ends up in the same
+    // place, byte for byte
+    let generation = format!(
+        "shims.{}",
+        &sha256::hex_digest(fingerprint.as_bytes())[..16]
+    );
+
+    swap_shims_dir(shims_dir, staging_dir, &generation)?;
+
+    Ok(shims)
+}
+
+// with SOURCE_DATE_EPOCH set, generated files get that modification time
+// instead of the time of the sync
+fn set_source_date(path: &Path) -> Result<()> {
+    let Some(epoch) = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+    else {
+        return Ok(());
+    };
+
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(UNIX_EPOCH + Duration::from_secs(epoch)))
+        .with_context(|| format!("Failed to set the time of '{}'", path.to_string_lossy()))
+}
+
+// `shims` is a symlink to the current generation, replacing a symlink with
+// rename is atomic even where replacing a non-empty directory isn't
+fn swap_shims_dir(shims_dir: &Path, staging_dir: &Path, generation: &str) -> Result<()> {
+    let generation = shims_dir.with_file_name(generation);
+    let link = shims_dir.with_extension("link");
+
+    // nothing changed, but a hand-edited shim is still put back file by file
+    if fs::read_link(shims_dir).is_ok_and(|current| shims_dir.with_file_name(current) == generation)
+    {
+        for entry in fs::read_dir(&generation)? {
+            let path = entry?.path();
+
+            if !staging_dir
+                .join(path.file_name().unwrap_or_default())
+                .exists()
+            {
+                remove_entry(&path)?;
+            }
+        }
+
+        // a directory can't be renamed over one that isn't empty
+        for entry in fs::read_dir(staging_dir)? {
+            let path = entry?.path();
+            let target = generation.join(path.file_name().unwrap_or_default());
+
+            if fs::symlink_metadata(&target).is_ok_and(|meta| meta.is_dir()) {
+                fs::remove_dir_all(&target)?;
+            }
+
+            fs::rename(&path, target)?;
+        }
+
+        return fs::remove_dir(staging_dir).context("Failed to clear shims.new directory");
+    }
+
+    if generation.exists() {
+        fs::remove_dir_all(&generation).context("Failed to clear old shims directory")?;
+    }
+
+    fs::rename(staging_dir, &generation).context("Failed to move new shims into place")?;
+
+    let previous = match fs::symlink_metadata(shims_dir) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::read_link(shims_dir).ok(),
+        // a plain directory from before generations were used, moving it
+        // aside leaves a short gap once
+        Ok(_) => {
+            let old = shims_dir.with_extension("old");
+
+            fs::rename(shims_dir, &old).context("Failed to move old shims aside")?;
+            Some(old)
+        }
+        Err(_) => None,
+    };
+
+    let _ = fs::remove_file(&link);
+
+    let swapped = symlink(generation.file_name().unwrap_or_default(), &link)
+        .and_then(|_| fs::rename(&link, shims_dir));
+
+    if swapped.is_err() {
+        // no symlinks here, fall back to renaming the directory itself
+        let _ = fs::remove_file(&link);
+        let _ = fs::remove_file(shims_dir);
+
+        fs::rename(&generation, shims_dir).context("Failed to move new shims into place")?;
+    }
+
+    if let Some(previous) = previous {
+        let previous = shims_dir.with_file_name(previous);
+
+        if previous != generation {
+            let _ = fs::remove_dir_all(previous);
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_entry(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+// shims left behind by aliases that were removed from the config without
+// syncing afterwards, e.g. by editing pintas.toml by hand
+pub fn stale_shims(config: &Config) -> Result<Vec<String>> {
+    let shims_dir = get_shims_dir()?;
+
+    Ok(read_manifest()?
+        .into_iter()
+        .filter(|name| !wants_shim(config, name) && shims_dir.join(shim_name(name)).is_file())
+        .collect())
+}
+
+pub fn is_tombstone(alias: &str) -> bool {
+    read_manifest().is_ok_and(|names| names.contains(alias))
+}
+
+pub fn prune_shims(config: &Config) -> Result<()> {
+    let shims_dir = get_shims_dir()?;
+
+    if is_provisioned() {
+        return Err(anyhow!(
+            "The shared shims directory '{}' is provisioned by its owner, only they can prune it.",
+            shims_dir.to_string_lossy()
+        ));
+    }
+    let stale = stale_shims(config)?;
+
+    if stale.is_empty() {
+        println!("No stale shims found.");
+
+        return Ok(());
+    }
+
+    let mut manifest = read_manifest()?;
+
+    for name in &stale {
+        fs::remove_file(shims_dir.join(shim_name(name)))
+            .with_context(|| format!("Failed to remove shim '{}'", name))?;
+        manifest.remove(name);
+    }
+
+    write_manifest(&manifest)?;
+
+    println!("Successfully pruned stale shims: {}", stale.join(", "));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn shim_files_are_recognized_with_any_shebang() {
+        let path = env::temp_dir().join(format!("pintas-shim-{}", process::id()));
+        let is_shim = |content: &str| {
+            fs::write(&path, content).unwrap();
+            is_shim_file(&path)
+        };
+
+        assert!(is_shim(
+            "#!/bin/sh\nexec '/usr/bin/pintas' run --internal gs \"$@\"\n"
+        ));
+        assert!(is_shim(
+            "#!/usr/bin/env dash\nexec '/usr/bin/pintas' run --internal gs \"$@\"\n"
+        ));
+        assert!(!is_shim("#!/bin/sh\necho hi\n"));
+        assert!(!is_shim("exec pintas run --internal gs\n"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    // an empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("pintas-shims-{}-{}", process::id(), name));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn stage(dir: &Path, files: &[&str]) -> PathBuf {
+        let staging_dir = dir.join("shims.new");
+
+        fs::create_dir_all(&staging_dir).unwrap();
+
+        for file in files {
+            fs::write(staging_dir.join(file), file).unwrap();
+        }
+
+        staging_dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn swap_points_the_link_at_the_new_generation() {
+        let dir = scratch("swap");
+        let shims_dir = dir.join("shims");
+
+        swap_shims_dir(&shims_dir, &stage(&dir, &["gs"]), "shims.a").unwrap();
+
+        assert_eq!(fs::read_link(&shims_dir).unwrap(), PathBuf::from("shims.a"));
+        assert_eq!(entries(&shims_dir), ["gs"]);
+
+        swap_shims_dir(&shims_dir, &stage(&dir, &["gs", "gp"]), "shims.b").unwrap();
+
+        assert_eq!(fs::read_link(&shims_dir).unwrap(), PathBuf::from("shims.b"));
+        assert_eq!(entries(&shims_dir), ["gp", "gs"]);
+        assert_eq!(entries(&dir), ["shims", "shims.b"]);
+    }
+
+    #[test]
+    fn swap_to_the_same_generation_puts_files_back() {
+        let dir = scratch("same");
+        let shims_dir = dir.join("shims");
+
+        swap_shims_dir(&shims_dir, &stage(&dir, &["gs"]), "shims.a").unwrap();
+        fs::write(shims_dir.join("gs"), "edited").unwrap();
+        fs::write(shims_dir.join("extra"), "extra").unwrap();
+        swap_shims_dir(&shims_dir, &stage(&dir, &["gs"]), "shims.a").unwrap();
+
+        assert_eq!(fs::read_link(&shims_dir).unwrap(), PathBuf::from("shims.a"));
+        assert_eq!(entries(&shims_dir), ["gs"]);
+        assert_eq!(fs::read_to_string(shims_dir.join("gs")).unwrap(), "gs");
+        assert_eq!(entries(&dir), ["shims", "shims.a"]);
+    }
+
+    #[test]
+    fn swap_replaces_a_plain_shims_directory() {
+        let dir = scratch("plain");
+        let shims_dir = dir.join("shims");
+
+        fs::create_dir_all(&shims_dir).unwrap();
+        fs::write(shims_dir.join("old"), "old").unwrap();
+        swap_shims_dir(&shims_dir, &stage(&dir, &["gs"]), "shims.a").unwrap();
+
+        assert_eq!(fs::read_link(&shims_dir).unwrap(), PathBuf::from("shims.a"));
+        assert_eq!(entries(&shims_dir), ["gs"]);
+        assert_eq!(entries(&dir), ["shims", "shims.a"]);
+    }
+
+    #[test]
+    fn plain_directories_of_others_are_refused() {
+        let dir = scratch("plain-foreign");
+        let bin = dir.join("bin");
+
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("backup.sh"), "#!/bin/sh\nrsync -a ~ /mnt\n").unwrap();
+
+        assert!(check_plain_dir(&bin, false).is_err());
+        assert!(check_plain_dir(&bin, true).is_ok());
+
+        fs::remove_file(bin.join("backup.sh")).unwrap();
+        fs::write(
+            bin.join("gs"),
+            "#!/bin/sh\nexec '/usr/bin/pintas' run --internal gs \"$@\"\n",
+        )
+        .unwrap();
+
+        assert!(check_plain_dir(&bin, false).is_ok());
+        assert!(check_plain_dir(&dir.join("missing"), false).is_ok());
+    }
+
+    #[test]
+    fn foreign_directories_are_kept_across_generations() {
+        let dir = scratch("foreign-dir");
+        let shims_dir = dir.join("shims");
+        let stage = |dir: &Path| {
+            let staging_dir = stage(dir, &[]);
+
+            fs::write(
+                staging_dir.join("gs"),
+                "#!/bin/sh\nexec '/usr/bin/pintas' run --internal gs \"$@\"\n",
+            )
+            .unwrap();
+
+            staging_dir
+        };
+
+        swap_shims_dir(&shims_dir, &stage(&dir), "shims.a").unwrap();
+        fs::create_dir_all(shims_dir.join("tools/lib")).unwrap();
+        fs::write(shims_dir.join("tools/lib/run.sh"), "run").unwrap();
+
+        for generation in ["shims.a", "shims.b"] {
+            let staging_dir = stage(&dir);
+
+            keep_foreign_files(&shims_dir, &staging_dir, false).unwrap();
+            swap_shims_dir(&shims_dir, &staging_dir, generation).unwrap();
+
+            assert_eq!(entries(&shims_dir), ["gs", "tools"]);
+            assert_eq!(
+                fs::read_to_string(shims_dir.join("tools/lib/run.sh")).unwrap(),
+                "run"
+            );
+        }
+
+        assert_eq!(entries(&dir), ["shims", "shims.b"]);
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use clap::Args;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::budget;
+use crate::config::Config;
+use crate::history;
+
+// the longest bar of `pintas top`
+const BAR_WIDTH: usize = 30;
+
+#[derive(Args, Clone)]
+pub struct TopArgs {
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub count: usize,
+    #[arg(long, default_value_t = 30)]
+    pub days: u64,
+}
+
+// `pintas stats`: how often the aliases ran and how long they took, from
+// the run history or the counts kept without one, followed by today's
+// budgets
+pub fn run_stats(config: &Config) -> Result<()> {
+    print_usage(config)?;
+    budget::print_budgets(config);
+
+    Ok(())
+}
+
+// runs in every project count, runs of aliases that are gone don't
+fn print_usage(config: &Config) -> Result<()> {
+    let usage = history::usage(config)?;
+    let mut used: Vec<_> = usage
+        .iter()
+        .filter(|(name, usage)| config.aliases.contains_key(*name) && usage.runs > 0)
+        .collect();
+
+    used.sort_by(|a, b| b.1.runs.cmp(&a.1.runs).then(a.0.cmp(b.0)));
+
+    if used.is_empty() {
+        println!("No runs recorded yet.");
+    } else {
+        println!("Most used:");
+
+        for (name, usage) in used {
+            // runs a shim handed over to the command have no duration
+            let average = match usage.average_ms() {
+                Some(ms) => format!(", {} on average", format_duration(ms)),
+                None => String::new(),
+            };
+
+            println!(
+                "  {:<15} {}{}, last run {}",
+                name,
+                plural(usage.runs, "run"),
+                average,
+                &history::format_timestamp(usage.last)[..10]
+            );
+        }
+    }
+
+    // an alias for another machine can't have run here
+    let mut never_used: Vec<_> = config
+        .aliases
+        .iter()
+        .filter(|(name, alias)| {
+            usage.get(name.as_str()).is_none_or(|usage| usage.runs == 0)
+                && alias.unavailable_on_machine_reason().is_none()
+        })
+        .map(|(name, _)| name)
+        .collect();
+
+    never_used.sort();
+
+    if !never_used.is_empty() {
+        println!();
+        println!("Never used:");
+
+        for name in never_used {
+            println!("  {}", name);
+        }
+    }
+
+    Ok(())
+}
+
+// `pintas top`: the most used aliases of the last days as bars, long names
+// near the top are the ones worth shortening
+pub fn run_top(config: &Config, top: &TopArgs) -> Result<()> {
+    let days = plural(top.days as usize, "day");
+    let mut runs: HashMap<&str, usize> = HashMap::new();
+
+    // without a history only the counts of all time are there
+    let (period, nothing) = if config.settings.history {
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs())
+            .saturating_sub(top.days * 86400);
+
+        for entry in history::read_entries()? {
+            if entry.started < since {
+                continue;
+            }
+
+            if let Some((name, _)) = config.aliases.get_key_value(&entry.alias) {
+                *runs.entry(name).or_default() += 1;
+            }
+        }
+
+        (
+            format!("last {}", days),
+            format!("No runs in the last {}.", days),
+        )
+    } else {
+        for (alias, usage) in history::counts() {
+            if let Some((name, _)) = config.aliases.get_key_value(&alias) {
+                runs.insert(name, usage.runs);
+            }
+        }
+
+        runs.retain(|_, runs| *runs > 0);
+
+        (
+            format!(
+                "all time (set history = true in [settings] for the last {})",
+                days
+            ),
+            "No runs recorded yet.".to_string(),
+        )
+    };
+
+    let mut top_runs: Vec<_> = runs.into_iter().collect();
+
+    top_runs.sort_by(|a, b| a.0.cmp(b.0));
+    top_runs.sort_by_key(|(_, runs)| Reverse(*runs));
+    top_runs.truncate(top.count);
+
+    let Some(most) = top_runs.first().map(|(_, runs)| *runs) else {
+        println!("{}", nothing);
+
+        return Ok(());
+    };
+
+    let name_width = top_runs
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    println!("Most used aliases, {}:", period);
+
+    for (name, runs) in top_runs {
+        // every alias that ran gets at least a sliver
+        let width = (runs * BAR_WIDTH).div_ceil(most);
+
+        println!(
+            "  {:name_width$}  {:BAR_WIDTH$}  {}",
+            name,
+            "█".repeat(width),
+            runs
+        );
+    }
+
+    Ok(())
+}
+
+pub fn plural(count: usize, noun: &str) -> String {
+    match (count, noun) {
+        (1, _) => format!("1 {}", noun),
+        (_, "alias") => format!("{} aliases", count),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+pub fn format_duration(ms: u128) -> String {
+    match ms {
+        0..1000 => format!("{}ms", ms),
+        1000..60_000 => format!("{:.1}s", ms as f64 / 1000.0),
+        60_000..3_600_000 => format!("{}m {}s", ms / 60_000, ms % 60_000 / 1000),
+        _ => format!("{}h {}m", ms / 3_600_000, ms % 3_600_000 / 60_000),
+    }
+}
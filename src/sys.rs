@@ -0,0 +1,284 @@
+use std::env;
+use std::ffi::{CString, c_char};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGKILL: i32 = 9;
+pub const SIGTERM: i32 = 15;
+
+unsafe extern "C" {
+    fn signal(signum: i32, handler: usize) -> usize;
+    fn kill(pid: i32, signum: i32) -> i32;
+    fn access(path: *const c_char, mode: i32) -> i32;
+    fn setsid() -> i32;
+    fn flock(fd: i32, operation: i32) -> i32;
+    fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+    fn getrlimit(resource: i32, rlim: *mut Rlimit) -> i32;
+    fn setrlimit(resource: i32, rlim: *const Rlimit) -> i32;
+}
+
+#[repr(C)]
+struct Rlimit {
+    current: u64,
+    max: u64,
+}
+
+pub const RLIMIT_CPU: i32 = 0;
+#[cfg(target_os = "linux")]
+pub const RLIMIT_AS: i32 = 9;
+#[cfg(not(target_os = "linux"))]
+pub const RLIMIT_AS: i32 = 5;
+#[cfg(target_os = "linux")]
+pub const RLIMIT_NOFILE: i32 = 7;
+#[cfg(not(target_os = "linux"))]
+pub const RLIMIT_NOFILE: i32 = 8;
+
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn ignore_signal(_signum: i32) {}
+
+extern "C" fn record_signal(signum: i32) {
+    PENDING_SIGNAL.store(signum, Ordering::SeqCst);
+}
+
+// keeps pintas alive while the child handles the signal, a handler (unlike
+// SIG_IGN) is reset on exec so the child still gets the default behaviour
+pub struct SignalGuard {
+    previous: Vec<(i32, usize)>,
+}
+
+impl SignalGuard {
+    pub fn install() -> Self {
+        Self::with_handler(ignore_signal)
+    }
+
+    // for children in a process group of their own, which the terminal
+    // doesn't signal, the signal is kept for `take_signal` to pass on
+    pub fn forwarding() -> Self {
+        PENDING_SIGNAL.store(0, Ordering::SeqCst);
+
+        Self::with_handler(record_signal)
+    }
+
+    fn with_handler(handler: extern "C" fn(i32)) -> Self {
+        let handler = handler as usize;
+        let previous = [SIGHUP, SIGINT, SIGQUIT, SIGTERM]
+            .into_iter()
+            .map(|signum| (signum, unsafe { signal(signum, handler) }))
+            .collect();
+
+        SignalGuard { previous }
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        for &(signum, handler) in &self.previous {
+            unsafe {
+                signal(signum, handler);
+            }
+        }
+    }
+}
+
+pub fn take_signal() -> Option<i32> {
+    let signum = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+
+    (signum != 0).then_some(signum)
+}
+
+// a process that exists but belongs to someone else can't be signalled,
+// but is still running
+pub fn is_running(pid: i32) -> bool {
+    const EPERM: i32 = 1;
+
+    let signalled = unsafe { kill(pid, 0) } == 0;
+
+    (signalled || io::Error::last_os_error().raw_os_error() == Some(EPERM)) && !is_zombie(pid)
+}
+
+// a process that ended but wasn't reaped yet still takes signals, on linux
+// its state says so
+fn is_zombie(pid: i32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok_and(|stat| {
+        stat.rsplit_once(')')
+            .is_some_and(|(_, rest)| rest.trim_start().starts_with('Z'))
+    })
+}
+
+// an exclusive lock on the whole file, held until it's closed, returns
+// false if another process holds it and `wait` is off
+pub fn lock_file(file: &fs::File, wait: bool) -> io::Result<bool> {
+    const LOCK_EX: i32 = 2;
+    const LOCK_NB: i32 = 4;
+
+    let operation = if wait { LOCK_EX } else { LOCK_EX | LOCK_NB };
+
+    loop {
+        if unsafe { flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+
+        let error = io::Error::last_os_error();
+
+        match error.kind() {
+            io::ErrorKind::WouldBlock if !wait => return Ok(false),
+            io::ErrorKind::Interrupted => continue,
+            _ => return Err(error),
+        }
+    }
+}
+
+// whether we may write to the path, asking the kernel covers ACLs and
+// read-only mounts that the mode bits don't show
+pub fn is_writable(path: &Path) -> bool {
+    const W_OK: i32 = 2;
+
+    CString::new(path.as_os_str().as_bytes())
+        .is_ok_and(|path| unsafe { access(path.as_ptr(), W_OK) } == 0)
+}
+
+// a session of its own leaves the terminal behind, for use in `pre_exec`
+pub fn start_session() -> io::Result<()> {
+    if unsafe { setsid() } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// the nice value of the calling process, for use in `pre_exec`, a value it
+// may not take leaves the priority as it is
+pub fn set_nice(nice: i32) {
+    const PRIO_PROCESS: i32 = 0;
+
+    unsafe {
+        setpriority(PRIO_PROCESS, 0, nice);
+    }
+}
+
+// lowers both the soft and the hard limit, so the program can't raise them
+// again, limits above the hard one stay at the hard one, for use in
+// `pre_exec`
+pub fn set_limit(resource: i32, soft: u64, hard: u64) -> io::Result<()> {
+    let mut rlimit = Rlimit { current: 0, max: 0 };
+
+    if unsafe { getrlimit(resource, &mut rlimit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let rlimit = Rlimit {
+        current: soft.min(rlimit.max),
+        max: hard.min(rlimit.max),
+    };
+
+    if unsafe { setrlimit(resource, &rlimit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// signals every process in the group led by `pgid`
+pub fn kill_group(pgid: u32, signum: i32) {
+    unsafe {
+        kill(-(pgid as i32), signum);
+    }
+}
+
+// shell convention: a child killed by a signal exits with 128 + signal
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signum| 128 + signum))
+        .unwrap_or(1)
+}
+
+// the status of a run that didn't end in a child process of its own
+pub fn exit_status(code: i32) -> ExitStatus {
+    ExitStatus::from_raw(code << 8)
+}
+
+// whether `unshare` can give a child its own network namespace, which needs
+// Linux and unprivileged user namespaces
+pub fn can_isolate_network() -> bool {
+    static CAN_ISOLATE: OnceLock<bool> = OnceLock::new();
+
+    *CAN_ISOLATE.get_or_init(|| {
+        cfg!(target_os = "linux")
+            && find_program("unshare").is_some()
+            && Command::new("unshare")
+                .args(["--net", "--map-root-user", "true"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+    })
+}
+
+// both ends on a terminal, a redirect on either side means a script
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+pub fn hostname() -> Option<&'static str> {
+    static HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
+
+    HOSTNAME
+        .get_or_init(|| {
+            let name = fs::read_to_string("/proc/sys/kernel/hostname")
+                .ok()
+                .or_else(|| {
+                    let output = Command::new("hostname").output().ok()?;
+
+                    String::from_utf8(output.stdout).ok()
+                })?;
+            let name = name.trim();
+
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .as_deref()
+}
+
+// `$SHELL`, the user's login shell, or sh without one
+pub fn login_shell() -> &'static str {
+    static LOGIN_SHELL: OnceLock<String> = OnceLock::new();
+
+    LOGIN_SHELL.get_or_init(|| env::var("SHELL").unwrap_or_else(|_| "sh".to_string()))
+}
+
+// an executable `name` on PATH, or at `name` itself when it's a path
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return is_executable(Path::new(name)).then(|| PathBuf::from(name));
+    }
+
+    find_programs(name).into_iter().next()
+}
+
+// every executable of that name on PATH, in the order a shell looks
+pub fn find_programs(name: &str) -> Vec<PathBuf> {
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .filter(|path| is_executable(path))
+        .collect()
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::process::{Child, Command as OsCommand, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sys;
+
+// what a run that was killed for taking too long exits with, like
+// timeout(1)
+pub const TIMED_OUT: i32 = 124;
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// how long a timed out command gets to exit after SIGTERM before SIGKILL
+pub const GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+// the point by which the commands of an alias with a `timeout` must be done
+#[derive(Clone)]
+pub struct Deadline {
+    pub at: Instant,
+    pub alias: String,
+    pub limit: String,
+}
+
+// `500ms`, `30s`, `5m`, `1h` or `90d`, a bare number is seconds
+pub fn parse(limit: &str) -> Option<Duration> {
+    let limit = limit.trim();
+    let split = limit
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(limit.len());
+    let (value, unit) = limit.split_at(split);
+    let value: f64 = value.parse().ok()?;
+
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => return None,
+    };
+
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+}
+
+pub fn status(cmd: &mut OsCommand, deadline: Option<&Deadline>) -> Result<ExitStatus> {
+    if deadline.is_none() {
+        return cmd.status().context("Failed to execute command");
+    }
+
+    let mut child = cmd.spawn().context("Failed to execute command")?;
+
+    wait(&mut child, deadline)
+}
+
+pub fn output(cmd: &mut OsCommand, deadline: Option<&Deadline>) -> Result<Output> {
+    if deadline.is_none() {
+        return cmd.output().context("Failed to execute command");
+    }
+
+    let child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute command")?;
+
+    wait_with_output(child, deadline)
+}
+
+// the pipes are drained on their own threads so a command that fills
+// them can still be waited for
+pub fn wait_with_output(mut child: Child, deadline: Option<&Deadline>) -> Result<Output> {
+    if deadline.is_none() {
+        return child
+            .wait_with_output()
+            .context("Failed to wait for command");
+    }
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    thread::scope(|scope| {
+        let stdout = scope.spawn(move || read_all(stdout));
+        let stderr = scope.spawn(move || read_all(stderr));
+        let status = wait(&mut child, deadline)?;
+
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    })
+}
+
+// a command past its deadline gets SIGTERM and then SIGKILL, sent to its
+// whole process group so whatever it started goes too
+pub fn wait(child: &mut Child, deadline: Option<&Deadline>) -> Result<ExitStatus> {
+    let Some(deadline) = deadline else {
+        return child.wait().context("Failed to wait for command");
+    };
+
+    let _guard = sys::SignalGuard::forwarding();
+
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to wait for command")? {
+            return Ok(status);
+        }
+
+        if let Some(signum) = sys::take_signal() {
+            sys::kill_group(child.id(), signum);
+        }
+
+        if Instant::now() >= deadline.at {
+            break;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    eprintln!(
+        "Alias '{}' timed out after {}.",
+        deadline.alias, deadline.limit
+    );
+
+    sys::kill_group(child.id(), sys::SIGTERM);
+
+    let grace = Instant::now() + GRACE_PERIOD;
+
+    while child
+        .try_wait()
+        .context("Failed to wait for command")?
+        .is_none()
+    {
+        if Instant::now() >= grace {
+            sys::kill_group(child.id(), sys::SIGKILL);
+            child.wait().context("Failed to wait for command")?;
+
+            break;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(sys::exit_status(TIMED_OUT))
+}
+
+pub fn read_all(stream: Option<impl Read>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    if let Some(mut stream) = stream {
+        let _ = stream.read_to_end(&mut buffer);
+    }
+
+    buffer
+}
@@ -0,0 +1,163 @@
+use anyhow::{Result, anyhow};
+use clap::Subcommand;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chain;
+use crate::config::{Alias, Config, TrashEntry};
+use crate::history;
+use crate::list;
+use crate::scripts;
+use crate::shims;
+
+const DEFAULT_TRASH_DAYS: u64 = 30;
+
+#[derive(Subcommand, Clone)]
+pub enum TrashCommand {
+    List,
+    Restore {
+        #[arg(required = true)]
+        alias: String,
+    },
+    Empty,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+fn retention_secs(config: &Config) -> u64 {
+    config.settings.trash_days.unwrap_or(DEFAULT_TRASH_DAYS) * 86400
+}
+
+// a removed alias goes to the trash unless `trash_days = 0` turns it off,
+// an alias removed again replaces the older copy
+pub fn discard(config: &mut Config, name: &str, alias: Alias) -> Result<()> {
+    if retention_secs(config) == 0 {
+        return scripts::remove_script(&alias);
+    }
+
+    let entry = TrashEntry {
+        removed: now(),
+        alias,
+    };
+
+    if let Some(older) = config.trash.insert(name.to_string(), entry) {
+        forget(config, &older.alias)?;
+    }
+
+    purge_expired(config)
+}
+
+// entries past their retention, along with the script files only they
+// still point at
+pub fn purge_expired(config: &mut Config) -> Result<()> {
+    let cutoff = now().saturating_sub(retention_secs(config));
+    let expired: Vec<String> = config
+        .trash
+        .iter()
+        .filter(|(_, entry)| entry.removed < cutoff)
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in expired {
+        if let Some(entry) = config.trash.remove(&name) {
+            forget(config, &entry.alias)?;
+        }
+    }
+
+    Ok(())
+}
+
+// a managed script stays as long as any alias, trashed or not, uses it
+fn forget(config: &Config, alias: &Alias) -> Result<()> {
+    let used = config
+        .aliases
+        .values()
+        .chain(config.trash.values().map(|entry| &entry.alias))
+        .any(|other| scripts::is_managed(other) && other.script == alias.script);
+
+    if used {
+        return Ok(());
+    }
+
+    scripts::remove_script(alias)
+}
+
+pub fn run_trash(config: &mut Config, command: &TrashCommand) -> Result<()> {
+    purge_expired(config)?;
+
+    match command {
+        TrashCommand::List => {
+            list_trash(config);
+
+            Ok(())
+        }
+        TrashCommand::Restore { alias } => restore(config, alias),
+        TrashCommand::Empty => {
+            let names: Vec<String> = config.trash.keys().cloned().collect();
+
+            for name in &names {
+                if let Some(entry) = config.trash.remove(name) {
+                    forget(config, &entry.alias)?;
+                }
+            }
+
+            if names.is_empty() {
+                println!("The trash is empty.");
+            } else {
+                println!("Successfully emptied the trash: {}", names.join(", "));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn list_trash(config: &Config) {
+    if config.trash.is_empty() {
+        println!("The trash is empty.");
+
+        return;
+    }
+
+    let retention = retention_secs(config);
+    let now = now();
+
+    for (name, entry) in &config.trash {
+        let left = (entry.removed + retention)
+            .saturating_sub(now)
+            .div_ceil(86400);
+
+        println!(
+            "{:<15} removed {}, kept {} more days: {}",
+            name,
+            &history::format_timestamp(entry.removed)[..10],
+            left,
+            list::command_summary(&entry.alias)
+        );
+    }
+}
+
+fn restore(config: &mut Config, alias: &str) -> Result<()> {
+    if config.aliases.contains_key(alias) {
+        return Err(anyhow!(
+            "Alias '{}' already exists. Remove it before restoring the one in the trash.",
+            alias
+        ));
+    }
+
+    let entry = config
+        .trash
+        .remove(alias)
+        .ok_or_else(|| anyhow!("Alias '{}' is not in the trash.", alias))?;
+
+    shims::check_shim_name(config, alias)?;
+    config.aliases.insert(alias.to_string(), entry.alias);
+    chain::check_cycles(config, alias)?;
+
+    println!("Successfully restored alias '{}'.", alias);
+
+    Ok(())
+}
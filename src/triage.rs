@@ -0,0 +1,96 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::process::{Command as OsCommand, ExitStatus};
+
+use crate::checklist;
+use crate::config::{Alias, defining_path};
+use crate::output;
+use crate::scripts;
+use crate::sys;
+
+pub enum Choice {
+    Rerun,
+    Output,
+    Edit,
+    Explain,
+    Quit,
+}
+
+// `triage = true` offers the menu after a failed run, only when someone is
+// at the terminal to answer it
+pub fn wanted(alias: &Alias, captured: bool) -> bool {
+    alias.triage && !captured && sys::is_interactive()
+}
+
+pub fn ask(name: &str, status: &ExitStatus) -> Result<Choice> {
+    let question = format!(
+        "Alias '{}' failed (exit code: {}). [r]e-run verbose, [o]pen output, [e]dit, e[x]plain, [q]uit?",
+        name,
+        sys::exit_code(status)
+    );
+
+    // end of input quits like `q` does
+    Ok(match checklist::ask(&question, "roexq")? {
+        'r' => Choice::Rerun,
+        'o' => Choice::Output,
+        'e' => Choice::Edit,
+        'x' => Choice::Explain,
+        _ => Choice::Quit,
+    })
+}
+
+// the output is only there for aliases that keep it
+pub fn open_output(name: &str, alias: &Alias) -> Result<()> {
+    let path = output::get_output_path()?;
+
+    if !alias.keep_output || !path.is_file() {
+        eprintln!(
+            "Alias '{}' doesn't keep its output, set keep_output = true to open it here next time.",
+            name
+        );
+
+        return Ok(());
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+
+    let status = OsCommand::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", pager))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start pager '{}'", pager))?;
+
+    if !status.success() {
+        return Err(anyhow!("Pager '{}' exited with an error.", pager));
+    }
+
+    Ok(())
+}
+
+// a script alias opens its script, any other the file that defines it
+pub fn edit(name: &str, alias: &Alias) -> Result<()> {
+    let path = match scripts::script_path(alias) {
+        Some(path) => path?,
+        None => defining_path(name, alias)?,
+    };
+
+    scripts::open_editor(&path)?;
+
+    println!(
+        "Edited '{}', run 'pintas run {}' again to try it.",
+        path.to_string_lossy(),
+        name
+    );
+
+    Ok(())
+}
+
+pub fn explain(name: &str, expansion: &[String]) {
+    println!("Alias '{}' expands to:", name);
+
+    for command in expansion {
+        println!("  {}", command.trim_end().replace('\n', "\n  "));
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, source_paths};
+use crate::sha256;
+use crate::shims::{expected_shims, get_shims_dir, read_manifest};
+
+// checks that the shims on disk are exactly what a sync of the current
+// config writes, `hashes` also prints sha256sum-style lines for the config
+// and shim files so provisioning tools can pin the whole state
+pub fn run_verify(config: &Config, hashes: bool) -> Result<()> {
+    let shims_dir = get_shims_dir()?;
+    let expected = expected_shims(config)?;
+    let mut problems = Vec::new();
+
+    for (alias, (file, content)) in &expected {
+        match fs::read_to_string(shims_dir.join(file)) {
+            Ok(actual) if actual == *content => {}
+            Ok(_) => problems.push(format!(
+                "shim '{}' differs from its alias '{}'",
+                file, alias
+            )),
+            Err(_) => problems.push(format!("shim '{}' for alias '{}' is missing", file, alias)),
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(&shims_dir) {
+        let mut unexpected: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| !expected.values().any(|(file, _)| file == name))
+            .collect();
+
+        unexpected.sort();
+
+        for name in unexpected {
+            problems.push(format!("'{}' doesn't belong to any alias", name));
+        }
+    }
+
+    if read_manifest()? != expected.keys().cloned().collect() {
+        problems.push("the shims manifest is out of date".to_string());
+    }
+
+    if hashes {
+        let mut files: Vec<_> = source_paths()?;
+
+        files.extend(expected.values().map(|(file, _)| shims_dir.join(file)));
+
+        for file in files {
+            print_hash(&file)?;
+        }
+    }
+
+    for problem in &problems {
+        println!("Problem: {}", problem);
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("Shims don't match the config, run 'pintas sync'."));
+    }
+
+    println!("Shims match the config.");
+
+    Ok(())
+}
+
+fn print_hash(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    let content =
+        fs::read(path).with_context(|| format!("Failed to read '{}'.", path.to_string_lossy()))?;
+
+    println!(
+        "{}  {}",
+        sha256::hex_digest(&content),
+        path.to_string_lossy()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::json::Json;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const GIT_COMMIT: &str = env!("PINTAS_GIT_COMMIT");
+const BUILD_DATE: &str = env!("PINTAS_BUILD_DATE");
+const TARGET: &str = env!("PINTAS_TARGET");
+const FEATURES: &str = env!("PINTAS_FEATURES");
+
+fn features() -> Vec<&'static str> {
+    FEATURES
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .collect()
+}
+
+pub fn print_version(build_info: bool, json: bool) {
+    if json {
+        let mut fields = vec![("version", Json::string(VERSION))];
+
+        if build_info {
+            fields.extend([
+                ("git_commit", Json::string(GIT_COMMIT)),
+                ("build_date", Json::string(BUILD_DATE)),
+                ("target", Json::string(TARGET)),
+                ("features", Json::strings(features())),
+            ]);
+        }
+
+        println!("{}", Json::object(fields));
+
+        return;
+    }
+
+    println!("pintas {}", VERSION);
+
+    if build_info {
+        let features = features();
+
+        println!("commit:   {}", GIT_COMMIT);
+        println!("built:    {}", BUILD_DATE);
+        println!("target:   {}", TARGET);
+        println!(
+            "features: {}",
+            if features.is_empty() {
+                "(none)".to_string()
+            } else {
+                features.join(", ")
+            }
+        );
+    }
+}
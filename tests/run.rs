@@ -0,0 +1,549 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static PROJECTS: AtomicUsize = AtomicUsize::new(0);
+
+// a project of its own with `config` as its pintas.toml and a home of its
+// own, so runs don't see each other or the real `~/.pintas`
+fn project(config: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "pintas-test-{}-{}",
+        std::process::id(),
+        PROJECTS.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("home")).unwrap();
+    fs::write(dir.join("pintas.toml"), config).unwrap();
+
+    dir
+}
+
+fn pintas(dir: &PathBuf, args: &[&str]) -> Output {
+    pintas_with_shell(dir, args, "/bin/sh")
+}
+
+// commands run under the login shell unless the config names one, so the
+// tests pick it rather than whoever runs them
+fn pintas_with_shell(dir: &PathBuf, args: &[&str], shell: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pintas"))
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", dir.join("home"))
+        .env("SHELL", shell)
+        .env_remove("PINTAS_SESSION")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+// what the command printed, without the `Executing command` line before it
+fn printed(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.starts_with("Executing "))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[test]
+fn safe_args_keeps_arguments_in_single_quotes_from_running() {
+    let dir = project(
+        r#"
+[settings]
+safe_args = true
+
+[aliases]
+note = "echo 'note: {1}'"
+"#,
+    );
+
+    let output = pintas(&dir, &["run", "note", "foo; echo PWNED"]);
+
+    assert!(output.status.success());
+    assert_eq!(printed(&output), ["note: foo; echo PWNED"]);
+}
+
+#[test]
+fn safe_args_keeps_arguments_in_double_quotes_from_running() {
+    let dir = project(
+        r#"
+[settings]
+safe_args = true
+
+[aliases]
+note = "echo \"note: {1}\""
+"#,
+    );
+
+    let output = pintas(&dir, &["run", "note", "x $(echo PWNED) `echo PWNED`"]);
+
+    assert!(output.status.success());
+    assert_eq!(printed(&output), ["note: x $(echo PWNED) `echo PWNED`"]);
+}
+
+#[test]
+fn sub_alias_runs_and_is_recorded() {
+    let dir = project(
+        r#"
+[settings]
+history = true
+
+[aliases.g]
+cmd = "echo top"
+
+[aliases.g.sub]
+push = "echo pushing"
+fail = "exit 3"
+"#,
+    );
+
+    let output = pintas(&dir, &["run", "g", "push"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(printed(&output), ["pushing"]);
+
+    let output = pintas(&dir, &["run", "g", "fail"]);
+
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("exit status: 3"));
+
+    let output = pintas(&dir, &["history", "g push"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("echo pushing"), "{:?}", output);
+}
+
+#[test]
+fn defaults_without_a_dash_are_an_error() {
+    let dir = project(
+        r#"
+[aliases]
+co = "echo {1:main}"
+"#,
+    );
+
+    let output = pintas(&dir, &["run", "co"]);
+
+    assert!(!output.status.success());
+    assert!(printed(&output).is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("{1:-main}"));
+}
+
+#[test]
+fn restricted_runs_refuse_denied_commands_by_name() {
+    let dir = project(
+        r#"
+[aliases]
+up = "/usr/bin/sudo apt upgrade"
+"#,
+    );
+
+    let output = pintas(&dir, &["run", "--restricted", "up"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("denied pattern 'sudo'"));
+}
+
+#[test]
+fn builtin_placeholders_are_quoted() {
+    let parent = project("");
+    let dir = parent.join("a b;echo PWNED");
+
+    fs::create_dir_all(dir.join("home")).unwrap();
+    fs::write(
+        dir.join("pintas.toml"),
+        "[aliases]\nhere = \"printf '%s\\\\n' {cwd} \\\"at {cwd}\\\" 'in {cwd}'\"\n",
+    )
+    .unwrap();
+
+    let output = pintas(&dir, &["run", "here"]);
+    let cwd = dir.to_string_lossy();
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        printed(&output),
+        [
+            cwd.to_string(),
+            format!("at {}", cwd),
+            format!("in {}", cwd)
+        ]
+    );
+}
+
+#[test]
+fn grep_finds_aliases_by_name_and_command() {
+    let dir = project(
+        r#"
+[aliases]
+deploy = "./deploy.sh --env prod"
+gs = "git status"
+gp = "git push"
+"#,
+    );
+
+    let output = pintas(&dir, &["grep", "^g"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "gp\n  command:     git push\ngs\n  command:     git status\n"
+    );
+
+    let output = pintas(&dir, &["grep", "-i", "PUSH|--ENV"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "deploy\n  command:     ./deploy.sh --env prod\ngp\n  command:     git push\n"
+    );
+
+    let output = pintas(&dir, &["grep", "svn"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No alias matches 'svn'"));
+}
+
+#[test]
+fn grep_refuses_malformed_patterns() {
+    let dir = project("[aliases]\ngs = \"git status\"\n");
+
+    for pattern in ["(git", "[[:word:]]", r"\1"] {
+        let output = pintas(&dir, &["grep", pattern]);
+
+        assert!(!output.status.success(), "{}", pattern);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid regex"));
+    }
+}
+
+#[test]
+fn grep_with_nested_quantifiers_finishes() {
+    let dir = project(&format!("[aliases]\nlong = \"echo {}\"\n", "a".repeat(30)));
+    let started = std::time::Instant::now();
+    let output = pintas(&dir, &["grep", "(a*)*b"]);
+
+    assert!(!output.status.success());
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn lint_finds_secrets_in_vars_env_files_and_scripts() {
+    let dir = project(
+        r#"
+[vars]
+token = "sk_live_1234"
+region = "eu"
+
+[aliases]
+deploy = "./deploy --token ${token} --region {var:region}"
+fetch = "curl -H {var:token} example.com"
+dotenv = { cmd = "env", env_file = "app.env" }
+scripted = { script = "./release.sh" }
+clean = "echo {var:region}"
+"#,
+    );
+
+    fs::write(
+        dir.join("policy.toml"),
+        "secret_patterns = [\"sk_live_\\\\w+\"]\n",
+    )
+    .unwrap();
+    fs::write(dir.join("app.env"), "API_KEY=sk_live_5678\n").unwrap();
+    fs::write(
+        dir.join("release.sh"),
+        "#!/bin/sh\nexport KEY=sk_live_9999\n",
+    )
+    .unwrap();
+
+    let output = pintas(&dir, &["lint", "--policy", "policy.toml"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        printed(&output),
+        [
+            "deploy: looks like it contains a secret ('sk_live_\\w+')",
+            "dotenv: looks like it contains a secret ('sk_live_\\w+')",
+            "fetch: looks like it contains a secret ('sk_live_\\w+')",
+            "scripted: looks like it contains a secret ('sk_live_\\w+')",
+        ]
+    );
+}
+
+#[test]
+fn expand_keeps_the_callers_positional_parameters() {
+    let dir = project(
+        r#"
+[aliases]
+count = "echo \"$# $2\""
+here = "cd sub"
+"#,
+    );
+
+    fs::create_dir(dir.join("sub")).unwrap();
+
+    let count = stdout(&pintas(&dir, &["expand", "count", "big world", "and more"]));
+    let here = stdout(&pintas(&dir, &["expand", "here"]));
+    let script = format!(
+        "set -- caller args\n{}{}echo \"$# $1 $(basename \"$PWD\")\"\n",
+        count, here
+    );
+    let output = Command::new("sh")
+        .args(["-c", &script])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output), "2 and more\n2 caller sub\n");
+}
+
+#[test]
+fn expand_refuses_aliases_with_run_options() {
+    let dir = project(
+        r#"
+[aliases]
+build = { cmd = "make", cwd = "src", env = { CC = "clang" } }
+slow = { cmd = "sleep 10", timeout = "1s" }
+"#,
+    );
+
+    let output = pintas(&dir, &["expand", "build"]);
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("sets cwd, env, which 'pintas expand'")
+    );
+
+    let output = pintas(&dir, &["expand", "slow"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("sets timeout"));
+}
+
+#[test]
+fn shims_run_from_the_index_and_notice_hand_edits() {
+    let dir = project(
+        r#"
+[aliases]
+hi = "echo hello"
+"#,
+    );
+
+    let output = pintas(&dir, &["sync"]);
+
+    assert!(output.status.success(), "{:?}", output);
+
+    let index_dir = dir.join("home/.pintas/index");
+    let index = fs::read_dir(&index_dir).unwrap().next().unwrap().unwrap();
+    let name = index.file_name().into_string().unwrap();
+
+    assert_eq!(name.len(), 16);
+    assert!(name.chars().all(|c| c.is_ascii_hexdigit()));
+
+    assert!(
+        fs::read_to_string(index.path())
+            .unwrap()
+            .starts_with("pintas-index 1\nsource ")
+    );
+
+    let shim = dir.join("home/.pintas/shims/hi");
+    let run_shim = || {
+        Command::new(&shim)
+            .current_dir(&dir)
+            .env("HOME", dir.join("home"))
+            .env_remove("PINTAS_SESSION")
+            .output()
+            .unwrap()
+    };
+
+    assert_eq!(stdout(&run_shim()), "hello\n");
+
+    // a different length, so the stamp changes even within the same tick
+    fs::write(
+        dir.join("pintas.toml"),
+        "[aliases]\nhi = \"echo edited by hand\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(stdout(&run_shim()), "edited by hand\n");
+}
+
+#[test]
+fn the_index_is_faster_than_a_full_parse() {
+    let mut config = String::from("[aliases]\n");
+
+    for i in 0..3000 {
+        config.push_str(&format!(
+            "a{i} = {{ cmd = \"echo {i}\", description = \"alias number {i}\", tags = [\"t\"] }}\n"
+        ));
+    }
+
+    let dir = project(&config);
+
+    assert!(pintas(&dir, &["sync"]).status.success());
+
+    // the best of a few runs, so a busy machine doesn't decide it
+    let fastest = |args: &[&str]| {
+        (0..3)
+            .map(|_| {
+                let started = std::time::Instant::now();
+                let output = pintas(&dir, args);
+
+                assert_eq!(printed(&output), ["5"]);
+
+                started.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+
+    let indexed = fastest(&["run", "--internal", "a5"]);
+    let parsed = fastest(&["run", "a5"]);
+
+    assert!(
+        indexed * 2 < parsed,
+        "{:?} from the index, {:?} parsed",
+        indexed,
+        parsed
+    );
+}
+
+#[test]
+fn usage_is_counted_without_a_history() {
+    let dir = project(
+        r#"
+[aliases]
+gs = "echo gs"
+gl = "echo gl"
+never = "true"
+"#,
+    );
+
+    for alias in ["gs", "gl", "gs"] {
+        assert!(pintas(&dir, &["run", alias]).status.success());
+    }
+
+    let stats = stdout(&pintas(&dir, &["stats"]));
+
+    assert!(
+        stats.starts_with("Most used:\n  gs              2 runs, "),
+        "{}",
+        stats
+    );
+    assert!(stats.contains("\n  gl              1 run, "), "{}", stats);
+    assert!(stats.contains("Never used:\n  never\n"), "{}", stats);
+
+    let top = stdout(&pintas(&dir, &["top"]));
+
+    assert!(top.starts_with("Most used aliases, all time"), "{}", top);
+
+    let output = pintas(&dir, &["list", "--sort", "used", "--names-only"]);
+
+    assert_eq!(stdout(&output), "gs\ngl\nnever\n");
+}
+
+#[test]
+fn commands_and_hooks_run_under_the_same_shell() {
+    let config = "[hooks]\npre_run = \"hook\"\n\n[aliases]\nwhich = \"alias\"\n";
+    let dir = project(config);
+    let shell = dir.join("fake-shell");
+
+    // says which command it got instead of running it
+    fs::write(&shell, "#!/bin/sh\necho \"$2 under $0\"\n").unwrap();
+    fs::set_permissions(&shell, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let shell = shell.to_string_lossy().into_owned();
+    let output = pintas_with_shell(&dir, &["run", "which"], &shell);
+
+    assert_eq!(
+        printed(&output),
+        [
+            format!("hook under {}", shell),
+            format!("alias under {}", shell)
+        ]
+    );
+
+    fs::write(
+        dir.join("pintas.toml"),
+        format!("[settings]\nshell = \"{}\"\n\n{}", shell, config),
+    )
+    .unwrap();
+
+    let output = pintas_with_shell(&dir, &["run", "which"], "/bin/sh");
+
+    assert_eq!(
+        printed(&output),
+        [
+            format!("hook under {}", shell),
+            format!("alias under {}", shell)
+        ]
+    );
+}
+
+#[test]
+fn sync_leaves_directories_it_didnt_create_alone() {
+    let dir = project("[aliases]\ngs = \"git status\"\n");
+    let bin = dir.join("bin");
+
+    fs::create_dir(&bin).unwrap();
+    fs::write(bin.join("backup.sh"), "#!/bin/sh\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pintas"))
+        .arg("sync")
+        .current_dir(&dir)
+        .env("HOME", dir.join("home"))
+        .env("PINTAS_SHIMS_DIR", &bin)
+        .env_remove("PINTAS_SESSION")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wasn't created by pintas"));
+    assert!(!fs::symlink_metadata(&bin).unwrap().is_symlink());
+    assert!(bin.join("backup.sh").is_file());
+    assert!(!dir.join("bin.new").exists());
+}
+
+#[test]
+fn a_failed_sync_cleans_up_after_itself() {
+    let dir = project("[aliases]\ngs = \"git status\"\n");
+    let shims_dir = dir.join("home/.pintas/shims");
+
+    fs::create_dir_all(&shims_dir).unwrap();
+    fs::write(shims_dir.join("gs"), "#!/bin/sh\necho mine\n").unwrap();
+
+    let output = pintas(&dir, &["sync"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("would be replaced by a shim"));
+    assert!(!dir.join("home/.pintas/shims.new").exists());
+    assert_eq!(
+        fs::read_to_string(shims_dir.join("gs")).unwrap(),
+        "#!/bin/sh\necho mine\n"
+    );
+}
+
+#[test]
+fn list_joins_steps_like_they_run() {
+    let dir = project(
+        r#"
+[aliases.build]
+steps = ["make", "make install"]
+
+[aliases.watch]
+steps = ["npm run css", "npm run js"]
+parallel = true
+"#,
+    );
+
+    let output = pintas(&dir, &["list"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("\"make && make install\""));
+    assert!(stdout(&output).contains("\"npm run css & npm run js\""));
+}